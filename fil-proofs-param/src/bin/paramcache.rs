@@ -5,7 +5,7 @@ use std::str::FromStr;
 use dialoguer::{theme::ColorfulTheme, MultiSelect};
 use filecoin_proofs::{
     constants::{
-        DefaultPieceHasher, POREP_PARTITIONS, PUBLISHED_SECTOR_SIZES, WINDOW_POST_CHALLENGE_COUNT,
+        DefaultPieceHasher, POREP_PARTITIONS, SUPPORTED_SECTOR_SIZES, WINDOW_POST_CHALLENGE_COUNT,
         WINDOW_POST_SECTOR_COUNT, WINNING_POST_CHALLENGE_COUNT, WINNING_POST_SECTOR_COUNT,
     },
     parameters::{public_params, window_post_public_params, winning_post_public_params},
@@ -187,6 +187,7 @@ fn generate_params_porep(sector_size: u64, api_version: ApiVersion) {
             ),
             porep_id: [0; 32],
             api_version,
+            challenge_derivation: None,
         }
     );
 }
@@ -201,7 +202,7 @@ pub fn main() {
     // If no sector-sizes were given provided via. the CLI, display an interactive menu. Otherwise,
     // filter out invalid CLI sector-size arguments.
     if opts.sector_sizes.is_empty() {
-        let sector_size_strings: Vec<String> = PUBLISHED_SECTOR_SIZES
+        let sector_size_strings: Vec<String> = SUPPORTED_SECTOR_SIZES
             .iter()
             .map(|sector_size| {
                 let human_size = sector_size
@@ -221,11 +222,11 @@ pub fn main() {
             .interact()
             .expect("interaction failed")
             .into_iter()
-            .map(|i| PUBLISHED_SECTOR_SIZES[i])
+            .map(|i| SUPPORTED_SECTOR_SIZES[i])
             .collect();
     } else {
         opts.sector_sizes.retain(|size| {
-            if PUBLISHED_SECTOR_SIZES.contains(size) {
+            if SUPPORTED_SECTOR_SIZES.contains(size) {
                 true
             } else {
                 let human_size = size
@@ -213,12 +213,14 @@ pub fn run(
                 validate_cache_for_commit::<_, _, DefaultOctLCTree>(
                     &replica_info.private_replica_info.cache_dir_path(),
                     &replica_info.private_replica_info.replica_path(),
+                    None,
                 )?;
 
                 let phase1_output = seal_commit_phase1::<_, DefaultOctLCTree>(
                     cfg,
                     &replica_info.private_replica_info.cache_dir_path(),
                     &replica_info.private_replica_info.replica_path(),
+                    None,
                     PROVER_ID,
                     *sector_id,
                     TICKET_BYTES,
@@ -317,6 +319,7 @@ fn generate_params(i: &ProdbenchInputs) {
         partitions,
         porep_id: dummy_porep_id,
         api_version: i.api_version(),
+        challenge_derivation: None,
     });
 }
 
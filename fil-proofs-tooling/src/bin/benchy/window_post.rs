@@ -147,6 +147,7 @@ pub fn run_window_post_bench<Tree: 'static + MerkleTreeTrait>(
             phase1_output,
             cache_dir.path(),
             sealed_file.path(),
+            None,
         )
     })
     .expect("failed in seal_pre_commit_phase2");
@@ -155,15 +156,17 @@ pub fn run_window_post_bench<Tree: 'static + MerkleTreeTrait>(
     let seed = [0u8; 32];
     let comm_r = seal_pre_commit_output.comm_r;
 
-    let validate_cache_for_commit_measurement =
-        measure(|| validate_cache_for_commit::<_, _, Tree>(cache_dir.path(), sealed_file.path()))
-            .expect("failed to validate cache for commit");
+    let validate_cache_for_commit_measurement = measure(|| {
+        validate_cache_for_commit::<_, _, Tree>(cache_dir.path(), sealed_file.path(), None)
+    })
+    .expect("failed to validate cache for commit");
 
     let seal_commit_phase1_measurement = measure(|| {
         seal_commit_phase1::<_, Tree>(
             porep_config,
             cache_dir.path(),
             sealed_file.path(),
+            None,
             PROVER_ID,
             sector_id,
             TICKET_BYTES,
@@ -147,6 +147,7 @@ fn porep_info(sector_size: u64, api_version: ApiVersion) -> (CircuitInfo, usize)
             partitions,
             porep_id: [0; 32],
             api_version,
+            challenge_derivation: None,
         }
     );
     (info, partitions.into())
@@ -109,6 +109,7 @@ pub fn create_replicas<Tree: 'static + MerkleTreeTrait>(
         ),
         porep_id,
         api_version,
+        challenge_derivation: None,
     };
 
     let mut out: Vec<(SectorId, PreCommitReplicaOutput<Tree>)> = Default::default();
@@ -200,7 +201,7 @@ pub fn create_replicas<Tree: 'static + MerkleTreeTrait>(
                     &sealed_files[i],
                     &phase1,
                 )?;
-                seal_pre_commit_phase2(porep_config, phase1, &cache_dirs[i], &sealed_files[i])
+                seal_pre_commit_phase2(porep_config, phase1, &cache_dirs[i], &sealed_files[i], None)
             })
             .collect::<Result<Vec<_>, _>>()
     })
@@ -0,0 +1,180 @@
+use std::mem::size_of;
+
+use anyhow::Result;
+use bellperson::{bls::Bls12, util_cs::bench_cs::BenchCS, Circuit};
+use merkletree::merkle::{get_merkle_tree_cache_size, get_merkle_tree_len};
+use storage_proofs_core::{
+    api_version::ApiVersion, compound_proof::CompoundProof, settings::SETTINGS, util::NODE_SIZE,
+};
+use storage_proofs_porep::stacked::{LayerChallenges, StackedCompound, StackedDrg};
+use storage_proofs_post::fallback::{FallbackPoSt, FallbackPoStCircuit, FallbackPoStCompound};
+
+use crate::{
+    constants::{
+        layers, porep_partitions, rows_to_discard, sector_shape_arities, window_post_sector_count,
+        with_sector_shape, DefaultPieceHasher, ShapeFn, DRG_DEGREE, EXP_DEGREE,
+        WINDOW_POST_CHALLENGE_COUNT,
+    },
+    parameters::{public_params, window_post_public_params},
+    types::{MerkleTreeTrait, PaddedBytesAmount, PoStConfig, SectorSize},
+    PoStType,
+};
+
+/// Returns the number of constraints `seal_commit_phase2` proves for a sector of
+/// `sector_size` bytes, summed over all of its partitions.
+///
+/// The count is derived from the current partition, layer and challenge settings for the
+/// sector size, by counting the constraints of circuits with one and two challenges.
+pub fn estimate_c2_constraints(sector_size: u64) -> Result<u64> {
+    with_sector_shape(sector_size, PoRepConstraints { sector_size })?
+}
+
+/// Returns the number of constraints of a single partition Window PoSt over
+/// `window_post_sector_count` sectors of `sector_size` bytes.
+///
+/// The count is derived from the current sector and challenge counts for the sector size,
+/// by counting the constraints of circuits over one and two sectors.
+pub fn estimate_window_post_constraints(sector_size: u64) -> Result<u64> {
+    with_sector_shape(sector_size, WindowPoStConstraints { sector_size })?
+}
+
+/// The phases of sealing a sector, as estimated by `estimate_memory_usage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SealPhase {
+    PreCommit1,
+    PreCommit2,
+    Commit1,
+    Commit2,
+}
+
+/// Returns an approximate peak of the memory, in bytes, used by `phase` when sealing a single
+/// sector of `sector_size` bytes, for planning how many sectors a machine seals at once.
+///
+/// The figures are analytical, derived from the sector size, layers, tree shapes and circuit
+/// size, and leave out the process itself and the Groth parameters `Commit2` loads:
+///
+/// - `PreCommit1` holds either the staged data and tree_d, or the layer being labeled, the
+///   previous layer and the window of the parents cache kept in memory, which is
+///   `sdr_parents_cache_size` nodes.
+/// - `PreCommit2` holds the data and the columns and tree_c of one base tree at a time.
+/// - `Commit1` holds the cached rows of tree_r_last.
+/// - `Commit2` holds the evaluations of every partition's constraints in an FFT domain.
+pub fn estimate_memory_usage(sector_size: u64, phase: SealPhase) -> Result<u64> {
+    let sector_bytes = sector_size as usize;
+    let nodes = sector_bytes / NODE_SIZE;
+    let (base, sub, top) = sector_shape_arities(sector_size)?;
+    let base_tree_count = sub.max(1) * top.max(1);
+    let base_tree_leafs = nodes / base_tree_count;
+    let parents_cache_window = nodes.min(SETTINGS.sdr_parents_cache_size as usize)
+        * (DRG_DEGREE + EXP_DEGREE)
+        * size_of::<u32>();
+
+    let bytes = match phase {
+        SealPhase::PreCommit1 => {
+            let tree_d = get_merkle_tree_len(nodes, 2)? * NODE_SIZE;
+            (sector_bytes + tree_d).max(2 * sector_bytes + parents_cache_window)
+        }
+        SealPhase::PreCommit2 => {
+            let columns = layers(sector_size)? * sector_bytes / base_tree_count;
+            let tree_c = get_merkle_tree_len(base_tree_leafs, base)? * NODE_SIZE;
+            sector_bytes + columns + tree_c
+        }
+        SealPhase::Commit1 => {
+            let cached_rows =
+                get_merkle_tree_cache_size(base_tree_leafs, base, rows_to_discard(sector_size)?)?;
+            cached_rows * NODE_SIZE * base_tree_count
+        }
+        SealPhase::Commit2 => {
+            let partitions = usize::from(porep_partitions(sector_size)?);
+            let constraints = estimate_c2_constraints(sector_size)? as usize / partitions;
+            // The a, b and c evaluations of a partition, each a field element per point.
+            partitions * 3 * constraints.next_power_of_two() * NODE_SIZE
+        }
+    };
+
+    Ok(bytes as u64)
+}
+
+struct PoRepConstraints {
+    sector_size: u64,
+}
+
+impl ShapeFn for PoRepConstraints {
+    type Output = Result<u64>;
+
+    fn call<Tree: 'static + MerkleTreeTrait>(self) -> Self::Output {
+        let partitions = usize::from(porep_partitions(self.sector_size)?);
+        let public_params = public_params::<Tree>(
+            PaddedBytesAmount(self.sector_size),
+            partitions,
+            [0; 32],
+            ApiVersion::V1_1_0,
+        )?;
+        let layers = public_params.layer_challenges.layers();
+        let challenges = public_params.layer_challenges.challenges_count_all();
+
+        let constraints = |count: usize| -> Result<usize> {
+            let mut public_params = public_params.clone();
+            public_params.layer_challenges = LayerChallenges::new(layers, count);
+
+            count_constraints(
+                <StackedCompound<Tree, DefaultPieceHasher> as CompoundProof<
+                    StackedDrg<'_, Tree, DefaultPieceHasher>,
+                    _,
+                >>::blank_circuit(&public_params),
+            )
+        };
+
+        let per_partition = extrapolate(constraints(1)?, constraints(2)?, challenges);
+        Ok(per_partition * partitions as u64)
+    }
+}
+
+struct WindowPoStConstraints {
+    sector_size: u64,
+}
+
+impl ShapeFn for WindowPoStConstraints {
+    type Output = Result<u64>;
+
+    fn call<Tree: 'static + MerkleTreeTrait>(self) -> Self::Output {
+        let constraints = |sector_count: usize| -> Result<usize> {
+            let post_config = PoStConfig {
+                sector_size: SectorSize(self.sector_size),
+                challenge_count: WINDOW_POST_CHALLENGE_COUNT,
+                sector_count,
+                typ: PoStType::Window,
+                priority: false,
+                api_version: ApiVersion::V1_1_0,
+            };
+            let public_params = window_post_public_params::<Tree>(&post_config)?;
+
+            count_constraints(<FallbackPoStCompound<Tree> as CompoundProof<
+                FallbackPoSt<'_, Tree>,
+                FallbackPoStCircuit<Tree>,
+            >>::blank_circuit(&public_params))
+        };
+
+        Ok(extrapolate(
+            constraints(1)?,
+            constraints(2)?,
+            window_post_sector_count(self.sector_size)?,
+        ))
+    }
+}
+
+fn count_constraints<C: Circuit<Bls12>>(circuit: C) -> Result<usize> {
+    let mut cs = BenchCS::new();
+    circuit.synthesize(&mut cs)?;
+
+    Ok(cs.num_constraints())
+}
+
+/// Every challenged node (or sector) adds the same constraints, so the count for `count` of
+/// them follows from the counts for one and two.
+fn extrapolate(one: usize, two: usize, count: usize) -> u64 {
+    let per_unit = two - one;
+    let fixed = one - per_unit;
+
+    (fixed + per_unit * count) as u64
+}
@@ -1,53 +1,68 @@
+use std::collections::HashMap;
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
 use anyhow::{ensure, Context, Result};
-use bincode::deserialize;
-use filecoin_hashers::Hasher;
+use bincode::{deserialize, serialized_size};
+use filecoin_hashers::{HashFunction, Hasher};
 use fr32::{write_unpadded, Fr32Reader};
 use log::{info, trace};
 use memmap::MmapOptions;
-use merkletree::store::{DiskStore, LevelCacheStore, StoreConfig};
+use merkletree::{
+    merkle::{get_merkle_tree_cache_size, get_merkle_tree_len},
+    store::{DiskStore, LevelCacheStore, StoreConfig},
+};
+use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use sha2::{Digest, Sha256};
 use storage_proofs_core::{
     cache_key::CacheKey,
     measurements::{measure_op, Operation},
     merkle::get_base_tree_count,
     pieces::generate_piece_commitment_bytes_from_source,
     sector::SectorId,
-    util::default_rows_to_discard,
-};
-use storage_proofs_porep::{
-    stacked::{generate_replica_id, PersistentAux, StackedDrg, TemporaryAux},
-    PoRep,
+    util::{default_rows_to_discard, NODE_SIZE},
 };
+use storage_proofs_porep::stacked::{generate_replica_id, PersistentAux, StackedDrg, TemporaryAux};
 use typenum::Unsigned;
 
 use crate::{
     commitment_reader::CommitmentReader,
+    commitment_writer::CommitmentWriter,
     constants::{
-        DefaultBinaryTree, DefaultOctTree, DefaultPieceDomain, DefaultPieceHasher,
+        layers, rows_to_discard, with_sector_shape, DefaultBinaryTree, DefaultOctTree,
+        DefaultPieceDomain, DefaultPieceHasher, DefaultTreeDomain, DefaultTreeHasher, ShapeFn,
         MINIMUM_RESERVED_BYTES_FOR_PIECE_IN_FULLY_ALIGNED_SECTOR as MINIMUM_PIECE_SIZE,
     },
+    error::Error,
     parameters::public_params,
     pieces::{get_piece_alignment, sum_piece_bytes_with_alignment},
     types::{
         Commitment, MerkleTreeTrait, PaddedBytesAmount, PieceInfo, PoRepConfig,
-        PoRepProofPartitions, ProverId, SealPreCommitPhase1Output, Ticket, UnpaddedByteIndex,
-        UnpaddedBytesAmount,
+        PoRepProofPartitions, ProverId, SealPreCommitPhase1Output, SealedSectorInfo, Ticket,
+        UnpaddedByteIndex, UnpaddedBytesAmount, BINARY_ARITY,
     },
 };
 
+mod estimates;
 mod fake_seal;
 mod post_util;
 mod seal;
+#[cfg(feature = "test-helpers")]
+mod test_helpers;
+mod update;
 mod util;
 mod window_post;
 mod winning_post;
 
+pub use estimates::*;
 pub use fake_seal::*;
 pub use post_util::*;
 pub use seal::*;
+#[cfg(feature = "test-helpers")]
+pub use test_helpers::*;
+pub use update::*;
 pub use util::*;
 pub use window_post::*;
 pub use winning_post::*;
@@ -108,8 +123,8 @@ pub fn get_unsealed_range<T: Into<PathBuf> + AsRef<Path>, Tree: 'static + Merkle
 
 /// Unseals the sector read from `sealed_sector` and returns the bytes for a
 /// piece whose first (unpadded) byte begins at `offset` and ends at `offset`
-/// plus `num_bytes`, inclusive. Note that the entire sector is unsealed each
-/// time this function is called.
+/// plus `num_bytes`, inclusive. Note that all layers of the sector are labeled
+/// each time this function is called, but only the requested range is decoded.
 ///
 /// # Arguments
 ///
@@ -156,13 +171,20 @@ where
         &porep_config.porep_id,
     );
 
-    let mut data = Vec::new();
-    sealed_sector.read_to_end(&mut data)?;
+    // Only read the sealed nodes which cover the requested range.
+    let window = sealed_node_window(offset, num_bytes);
+    io::copy(
+        &mut (&mut sealed_sector).take(window.start as u64),
+        &mut io::sink(),
+    )?;
+    let mut data = vec![0u8; window.end - window.start];
+    sealed_sector.read_exact(&mut data)?;
 
     let res = unseal_range_inner::<_, _, Tree>(
         porep_config,
         cache_path,
         &mut data,
+        window.start,
         unsealed_output,
         replica_id,
         offset,
@@ -176,8 +198,8 @@ where
 
 /// Unseals the sector read from `sealed_sector` and returns the bytes for a
 /// piece whose first (unpadded) byte begins at `offset` and ends at `offset`
-/// plus `num_bytes`, inclusive. Note that the entire sector is unsealed each
-/// time this function is called.
+/// plus `num_bytes`, inclusive. Note that all layers of the sector are labeled
+/// each time this function is called, but only the requested range is decoded.
 ///
 /// # Arguments
 ///
@@ -211,6 +233,7 @@ where
 {
     info!("unseal_range_mapped:start");
     ensure!(comm_d != [0; 32], "Invalid all zero commitment (comm_d)");
+    validate_replica(&sealed_path, u64::from(porep_config.sector_size))?;
 
     let comm_d =
         as_safe_commitment::<<DefaultPieceHasher as Hasher>::Domain, _>(&comm_d, "comm_d")?;
@@ -228,11 +251,17 @@ where
         .write(true)
         .open(&sealed_path)?;
     let mut data = unsafe { MmapOptions::new().map_copy(&mapped_file)? };
+    let window = sealed_node_window(offset, num_bytes);
+    ensure!(
+        window.end <= data.len(),
+        "requested range exceeds the sealed sector"
+    );
 
     let result = unseal_range_inner::<_, _, Tree>(
         porep_config,
         cache_path,
-        &mut data,
+        &mut data[window.clone()],
+        window.start,
         unsealed_output,
         replica_id,
         offset,
@@ -243,21 +272,31 @@ where
     result
 }
 
-/// Unseals the sector read from `sealed_sector` and returns the bytes for a
-/// piece whose first (unpadded) byte begins at `offset` and ends at `offset`
-/// plus `num_bytes`, inclusive. Note that the entire sector is unsealed each
-/// time this function is called.
+/// Returns the range of padded bytes in the sealed sector which holds the
+/// nodes covering `num_bytes` unpadded bytes starting at `offset`.
+fn sealed_node_window(offset: UnpaddedByteIndex, num_bytes: UnpaddedBytesAmount) -> Range<usize> {
+    let offset_padded: PaddedBytesAmount = UnpaddedBytesAmount::from(offset).into();
+    let num_bytes_padded: PaddedBytesAmount = num_bytes.into();
+    let start = usize::from(offset_padded);
+    let end = start + usize::from(num_bytes_padded);
+
+    let first_node = start / NODE_SIZE;
+    let last_node = (end + NODE_SIZE - 1) / NODE_SIZE;
+    first_node * NODE_SIZE..last_node * NODE_SIZE
+}
+
+/// Unseals the sealed nodes in `data`, which start at byte `data_start` of the
+/// sealed sector, and writes the bytes for a piece whose first (unpadded) byte
+/// begins at `offset` and ends at `offset` plus `num_bytes`, inclusive.
 ///
 /// # Arguments
 ///
 /// * `porep_config` - porep configuration containing the sector size.
 /// * `cache_path` - path to the directory in which the sector data's Merkle Tree is written.
-/// * `sealed_sector` - a byte source from which we read sealed sector data.
+/// * `data` - the sealed nodes covering the requested range, see `sealed_node_window`.
+/// * `data_start` - the byte index of `data` in the sealed sector.
 /// * `unsealed_output` - a byte sink to which we write unsealed, un-bit-padded sector bytes.
-/// * `prover_id` - the prover-id that sealed the sector.
-/// * `sector_id` - the sector-id of the sealed sector.
-/// * `comm_d` - the commitment to the sector's data.
-/// * `ticket` - the ticket that was used to generate the sector's replica-id.
+/// * `replica_id` - the replica-id of the sealed sector.
 /// * `offset` - the byte index in the unsealed sector of the first byte that we want to read.
 /// * `num_bytes` - the number of bytes that we want to read.
 #[allow(clippy::too_many_arguments)]
@@ -265,6 +304,7 @@ fn unseal_range_inner<P, W, Tree>(
     porep_config: PoRepConfig,
     cache_path: P,
     data: &mut [u8],
+    data_start: usize,
     mut unsealed_output: W,
     replica_id: <Tree::Hasher as Hasher>::Domain,
     offset: UnpaddedByteIndex,
@@ -297,8 +337,14 @@ where
     let offset_padded: PaddedBytesAmount = UnpaddedBytesAmount::from(offset).into();
     let num_bytes_padded: PaddedBytesAmount = num_bytes.into();
 
-    StackedDrg::<Tree, DefaultPieceHasher>::extract_all(&pp, &replica_id, data, Some(config))?;
-    let start: usize = offset_padded.into();
+    StackedDrg::<Tree, DefaultPieceHasher>::extract_range(
+        &pp,
+        &replica_id,
+        data,
+        config,
+        data_start / NODE_SIZE,
+    )?;
+    let start = usize::from(offset_padded) - data_start;
     let end = start + usize::from(num_bytes_padded);
     let unsealed = &data[start..end];
 
@@ -347,6 +393,28 @@ pub fn generate_piece_commitment<T: Read>(
     result
 }
 
+/// Generates the piece commitments of the pieces stored in the files of `pieces` in parallel,
+/// like calling `generate_piece_commitment` for each of them. The piece infos are returned in
+/// the order of `pieces`.
+///
+/// # Arguments
+///
+/// * `pieces` - the paths of the files holding unprocessed piece bytes, with the number of
+/// unpadded user-bytes which can be read from each file.
+pub fn compute_piece_commitments(
+    pieces: &[(PathBuf, UnpaddedBytesAmount)],
+) -> Result<Vec<PieceInfo>> {
+    pieces
+        .par_iter()
+        .map(|(path, piece_size)| {
+            let file =
+                File::open(path).with_context(|| format!("could not open piece {:?}", path))?;
+            generate_piece_commitment(file, *piece_size)
+                .with_context(|| format!("could not generate commitment of piece {:?}", path))
+        })
+        .collect()
+}
+
 /// Computes a NUL-byte prefix and/or suffix for `source` using the provided
 /// `piece_lengths` and `piece_size` (such that the `source`, after
 /// preprocessing, will occupy a subtree of a merkle tree built using the bytes
@@ -421,6 +489,77 @@ where
     result
 }
 
+/// Like `add_piece`, but reads the piece from several sources, such as the chunk files it is
+/// stored in. The piece is the concatenation of `sources`, of which the source at index `i`
+/// provides exactly `sizes[i]` bytes, and it is preprocessed across the segment boundaries
+/// as if it were read from a single source.
+///
+/// # Arguments
+///
+/// * `sources` - readable sources of consecutive segments of the unprocessed piece bytes.
+/// * `sizes` - the number of unpadded user-bytes which are read from each source.
+/// * `target` - a writer where we will write the processed piece bytes.
+/// * `piece_lengths` - the number of bytes for each previous piece in the sector.
+pub fn add_piece_from_readers<W: Write>(
+    sources: Vec<Box<dyn Read>>,
+    sizes: &[UnpaddedBytesAmount],
+    target: W,
+    piece_lengths: &[UnpaddedBytesAmount],
+) -> Result<(PieceInfo, UnpaddedBytesAmount)> {
+    ensure!(
+        sources.len() == sizes.len(),
+        "add_piece_from_readers: {} sources but {} sizes",
+        sources.len(),
+        sizes.len()
+    );
+
+    let piece_size = sizes
+        .iter()
+        .fold(UnpaddedBytesAmount(0), |total, size| total + *size);
+    let segments = sources
+        .into_iter()
+        .zip(sizes.iter().map(|size| u64::from(*size)))
+        .collect::<Vec<_>>();
+    let source = SegmentsReader {
+        segments: segments.into_iter(),
+        current: None,
+    };
+
+    add_piece(source, target, piece_size, piece_lengths)
+}
+
+/// Reads the concatenation of segments of known sizes, failing if a segment ends early.
+struct SegmentsReader {
+    segments: std::vec::IntoIter<(Box<dyn Read>, u64)>,
+    current: Option<(Box<dyn Read>, u64)>,
+}
+
+impl Read for SegmentsReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.current {
+                Some((_, 0)) | None => match self.segments.next() {
+                    Some(segment) => self.current = Some(segment),
+                    None => return Ok(0),
+                },
+                Some((ref mut source, ref mut remaining)) => {
+                    let len = std::cmp::min(buf.len() as u64, *remaining) as usize;
+                    let n = source.read(&mut buf[..len])?;
+                    if n == 0 && len != 0 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "piece segment ended before its size",
+                        ));
+                    }
+                    *remaining -= n as u64;
+
+                    return Ok(n);
+                }
+            }
+        }
+    }
+}
+
 fn ensure_piece_size(piece_size: UnpaddedBytesAmount) -> Result<()> {
     ensure!(
         piece_size >= UnpaddedBytesAmount(MINIMUM_PIECE_SIZE),
@@ -464,6 +603,67 @@ where
     add_piece(source, target, piece_size, Default::default())
 }
 
+/// Returns a writer which preprocesses the piece bytes written to it into `target`, like
+/// `write_and_preprocess` does for a reader.
+///
+/// The piece commitment is computed while the data is streamed, so the padded bytes don't need
+/// to be read again. Calling `finish` on the writer returns the number of padded bytes written
+/// to `target` together with the piece info.
+///
+/// # Arguments
+///
+/// * `target` - a writer where we will write the processed piece bytes.
+pub fn write_and_preprocess_streaming<W: Write>(target: W) -> CommitmentWriter<W> {
+    CommitmentWriter::new(target)
+}
+
+/// Like `write_and_preprocess`, but reports progress while streaming `source` to `target`.
+///
+/// `source` is read in fixed-size chunks and `progress` is invoked with the number of unpadded
+/// bytes consumed from `source` after every chunk, so callers can track progress without
+/// buffering the piece in memory.
+///
+/// # Arguments
+///
+/// * `source` - a readable source of unprocessed piece bytes.
+/// * `target` - a writer where we will write the processed piece bytes.
+/// * `piece_size` - the number of unpadded user-bytes which can be read from source before EOF.
+/// * `progress` - a callback receiving the number of bytes consumed by each chunk.
+pub fn add_piece_streaming<R, W, F>(
+    source: R,
+    target: W,
+    piece_size: UnpaddedBytesAmount,
+    progress: F,
+) -> Result<(PieceInfo, UnpaddedBytesAmount)>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(u64),
+{
+    let source = ProgressReader {
+        inner: source,
+        progress,
+    };
+
+    write_and_preprocess(source, target, piece_size)
+}
+
+struct ProgressReader<R, F> {
+    inner: R,
+    progress: F,
+}
+
+impl<R: Read, F: FnMut(u64)> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            (self.progress)(n as u64);
+        }
+
+        Ok(n)
+    }
+}
+
 // Verifies if a DiskStore specified by a config (or set of 'required_configs' is consistent).
 fn verify_store(config: &StoreConfig, arity: usize, required_configs: usize) -> Result<()> {
     let store_path = StoreConfig::data_path(&config.path, &config.id);
@@ -637,12 +837,282 @@ where
     result
 }
 
+/// Checks that the trees needed by `seal_commit_phase1` have the sizes expected for the sector
+/// size and tree shape, so that truncated files are caught before starting the commit.
+///
+/// tree_d is looked up in `cache_path`, tree_c and tree_r_last in `tree_dir` if they were
+/// written there by `seal_pre_commit_phase2`, and in `cache_path` otherwise. The rows of
+/// tree_r_last discarded are read from the t_aux in `cache_path`.
+///
+/// Returns `Error::CacheFileCorrupt` for the first file with an unexpected size.
+pub fn validate_cache_dir<R, Tree: MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    cache_path: R,
+    tree_dir: Option<&Path>,
+) -> Result<()>
+where
+    R: AsRef<Path>,
+{
+    info!("validate_cache_dir:start");
+
+    let cache_path = cache_path.as_ref();
+    // The layout of t_aux doesn't depend on the tree shape.
+    let t_aux: TemporaryAux<DefaultOctTree, DefaultPieceHasher> =
+        deserialize(&read_cache_file(cache_path, CacheKey::TAux)?)?;
+    let tree_d_id = CacheKey::CommDTree.to_string();
+
+    for (id, expected) in tree_file_sizes::<Tree>(
+        u64::from(porep_config.sector_size),
+        t_aux.tree_r_last_config.rows_to_discard,
+    )? {
+        let dir = match tree_dir {
+            Some(tree_dir) if id != tree_d_id => tree_dir,
+            _ => cache_path,
+        };
+        let path = StoreConfig::data_path(dir, &id);
+        let actual = fs::metadata(&path)
+            .with_context(|| format!("missing cache file {:?}", path))?
+            .len();
+        ensure!(
+            actual == expected,
+            Error::CacheFileCorrupt {
+                path,
+                expected,
+                actual
+            }
+        );
+    }
+
+    info!("validate_cache_dir:finish");
+    Ok(())
+}
+
+/// Returns the size in bytes of every file a sealed sector of `sector_size` leaves for
+/// `seal_commit_phase1`, keyed by id.
+///
+/// The trees are keyed by their store id, like `tree-c` or `tree-c-0` for split trees, and are
+/// found at `StoreConfig::data_path` in the cache directory. p_aux is keyed by its file name,
+/// and the replica by `replica`. t_aux is left out, as its size depends on the length of the
+/// cache path stored in it.
+pub fn expected_cache_file_sizes(sector_size: u64) -> Result<HashMap<String, u64>> {
+    let mut sizes: HashMap<_, _> = with_sector_shape(sector_size, TreeFileSizes { sector_size })??
+        .into_iter()
+        .collect();
+
+    let p_aux = PersistentAux::<DefaultTreeDomain>::default();
+    sizes.insert(CacheKey::PAux.to_string(), serialized_size(&p_aux)?);
+    sizes.insert("replica".to_string(), sector_size);
+
+    Ok(sizes)
+}
+
+struct TreeFileSizes {
+    sector_size: u64,
+}
+
+impl ShapeFn for TreeFileSizes {
+    type Output = Result<Vec<(String, u64)>>;
+
+    fn call<Tree: 'static + MerkleTreeTrait>(self) -> Self::Output {
+        tree_file_sizes::<Tree>(self.sector_size, rows_to_discard(self.sector_size)?)
+    }
+}
+
+/// Returns the store id and size in bytes of tree_d, tree_c and tree_r_last, with
+/// `rows_to_discard` rows of tree_r_last discarded.
+fn tree_file_sizes<Tree: MerkleTreeTrait>(
+    sector_size: u64,
+    rows_to_discard: usize,
+) -> Result<Vec<(String, u64)>> {
+    let nodes = sector_size as usize / NODE_SIZE;
+    let tree_count = get_base_tree_count::<Tree>();
+    let arity = Tree::Arity::to_usize();
+    let base_tree_leafs = nodes / tree_count;
+
+    let tree_d_len = get_merkle_tree_len(nodes, BINARY_ARITY)?;
+    let tree_c_len = get_merkle_tree_len(base_tree_leafs, arity)?;
+    let tree_r_last_len = get_merkle_tree_cache_size(base_tree_leafs, arity, rows_to_discard)?;
+
+    let bytes = |len: usize| (len * NODE_SIZE) as u64;
+    let mut sizes = vec![(CacheKey::CommDTree.to_string(), bytes(tree_d_len))];
+    for (key, len) in &[
+        (CacheKey::CommCTree, tree_c_len),
+        (CacheKey::CommRLastTree, tree_r_last_len),
+    ] {
+        if tree_count == 1 {
+            sizes.push((key.to_string(), bytes(*len)));
+        } else {
+            for i in 0..tree_count {
+                sizes.push((format!("{}-{}", key, i), bytes(*len)));
+            }
+        }
+    }
+
+    Ok(sizes)
+}
+
+/// Recovers the commitments and tree configs of the sector sealed with `cache_dir` from the
+/// p_aux and t_aux files written by `seal_pre_commit_phase2`, without reading the replica.
+///
+/// comm_r is recomputed from comm_c and comm_r_last, and comm_d is the root of tree_d if it's
+/// still in the cache directory. Returns `Error::MissingCacheFile` if p_aux or t_aux is absent.
+pub fn inspect_sealed_sector(cache_dir: &Path) -> Result<SealedSectorInfo> {
+    let p_aux: PersistentAux<DefaultTreeDomain> =
+        deserialize(&read_cache_file(cache_dir, CacheKey::PAux)?)?;
+    // The layout of t_aux doesn't depend on the tree shape.
+    let t_aux: TemporaryAux<DefaultOctTree, DefaultPieceHasher> =
+        deserialize(&read_cache_file(cache_dir, CacheKey::TAux)?)?;
+
+    let tree_d_len = t_aux
+        .tree_d_config
+        .size
+        .context("t_aux has no tree_d size")?;
+    let sector_size = ((tree_d_len + 1) / BINARY_ARITY * NODE_SIZE) as u64;
+
+    let tree_d_path = StoreConfig::data_path(cache_dir, &t_aux.tree_d_config.id);
+    let comm_d = if tree_d_path.exists() {
+        let tree_d = fs::read(&tree_d_path)
+            .with_context(|| format!("could not read file tree_d={:?}", tree_d_path))?;
+        ensure!(
+            tree_d.len() == tree_d_len * NODE_SIZE,
+            Error::CacheFileCorrupt {
+                path: tree_d_path,
+                expected: (tree_d_len * NODE_SIZE) as u64,
+                actual: tree_d.len() as u64,
+            }
+        );
+        // The root is the last node of the tree.
+        let mut comm_d = [0u8; 32];
+        comm_d.copy_from_slice(&tree_d[tree_d.len() - NODE_SIZE..]);
+        Some(comm_d)
+    } else {
+        None
+    };
+
+    let comm_r = <DefaultTreeHasher as Hasher>::Function::hash2(&p_aux.comm_c, &p_aux.comm_r_last);
+
+    Ok(SealedSectorInfo {
+        sector_size,
+        comm_r: commitment_from_fr(comm_r.into()),
+        comm_d,
+        comm_c: commitment_from_fr(p_aux.comm_c.into()),
+        comm_r_last: commitment_from_fr(p_aux.comm_r_last.into()),
+        tree_d_config: t_aux.tree_d_config,
+        tree_c_config: t_aux.tree_c_config,
+        tree_r_last_config: t_aux.tree_r_last_config,
+    })
+}
+
+fn read_cache_file(cache_dir: &Path, key: CacheKey) -> Result<Vec<u8>> {
+    let path = cache_dir.join(key.to_string());
+    fs::read(&path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => Error::MissingCacheFile(path).into(),
+        _ => anyhow::Error::new(err).context(format!("could not read file {:?}", path)),
+    })
+}
+
+/// Checks that the replica at `path` is exactly `sector_size` bytes long, so that a truncated
+/// or padded replica is caught before its trees are read.
+///
+/// Returns `Error::ReplicaSizeMismatch` if the size differs.
+pub fn validate_replica(path: &Path, sector_size: u64) -> Result<()> {
+    let actual = fs::metadata(path)
+        .with_context(|| format!("missing replica {:?}", path))?
+        .len();
+    ensure!(
+        actual == sector_size,
+        Error::ReplicaSizeMismatch {
+            expected: sector_size,
+            actual
+        }
+    );
+
+    Ok(())
+}
+
+/// Returns the SHA-256 checksum of every label layer in `cache_dir`, in layer order, so the
+/// layers written by `seal_pre_commit_phase1` can be checked after being shipped to the host
+/// running `seal_pre_commit_phase2`.
+///
+/// Returns `Error::MissingCacheFile` if a layer is absent and `Error::CacheFileCorrupt` if one
+/// doesn't have the size of a sector.
+pub fn layer_checksums(cache_dir: &Path, sector_size: u64) -> Result<Vec<[u8; 32]>> {
+    info!("layer_checksums:start");
+
+    let checksums = (1..=layers(sector_size)?)
+        .into_par_iter()
+        .map(|layer| layer_checksum(cache_dir, layer, sector_size))
+        .collect();
+
+    info!("layer_checksums:finish");
+    checksums
+}
+
+/// Checks the label layers in `cache_dir` against the `checksums` returned by
+/// `layer_checksums` on the host that created them.
+///
+/// Returns `Error::LayerChecksumMismatch` for the first layer which differs.
+pub fn verify_layer_checksums(
+    cache_dir: &Path,
+    sector_size: u64,
+    checksums: &[[u8; 32]],
+) -> Result<()> {
+    let actual = layer_checksums(cache_dir, sector_size)?;
+    ensure!(
+        checksums.len() == actual.len(),
+        "expected checksums of {} layers, got {}",
+        actual.len(),
+        checksums.len()
+    );
+
+    for (layer, (expected, actual)) in (1..).zip(checksums.iter().zip(&actual)) {
+        ensure!(expected == actual, Error::LayerChecksumMismatch { layer });
+    }
+
+    Ok(())
+}
+
+fn layer_checksum(cache_dir: &Path, layer: usize, sector_size: u64) -> Result<[u8; 32]> {
+    let path = StoreConfig::data_path(cache_dir, &CacheKey::label_layer(layer));
+    let mut file = File::open(&path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => Error::MissingCacheFile(path.clone()).into(),
+        _ => anyhow::Error::new(err).context(format!("could not open layer={:?}", path)),
+    })?;
+    let actual = file.metadata()?.len();
+    ensure!(
+        actual == sector_size,
+        Error::CacheFileCorrupt {
+            path,
+            expected: sector_size,
+            actual,
+        }
+    );
+
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .with_context(|| format!("could not read layer={:?}", path))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    let mut checksum = [0u8; 32];
+    checksum.copy_from_slice(&hasher.finalize());
+    Ok(checksum)
+}
+
 // Checks for the existence of the replica data and t_aux, which in
 // turn allows us to verify the tree d, tree r, tree c, and the
-// labels.
+// labels. tree c and tree r are looked up in `tree_dir` if they were
+// written there by `seal_pre_commit_phase2`.
 pub fn validate_cache_for_commit<R, T, Tree: MerkleTreeTrait>(
     cache_path: R,
     replica_path: T,
+    tree_dir: Option<&Path>,
 ) -> Result<()>
 where
     R: AsRef<Path>,
@@ -682,8 +1152,11 @@ where
 
         let mut res: TemporaryAux<Tree, DefaultPieceHasher> = deserialize(&t_aux_bytes)?;
 
-        // Switch t_aux to the passed in cache_path
+        // Switch t_aux to the passed in cache_path and tree_dir
         res.set_cache_path(&cache_path);
+        if let Some(tree_dir) = tree_dir {
+            res.set_tree_path(tree_dir);
+        }
         res
     };
 
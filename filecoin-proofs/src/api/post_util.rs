@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::Path;
 
@@ -69,6 +69,56 @@ pub fn generate_fallback_sector_challenges<Tree: 'static + MerkleTreeTrait>(
         "invalid post config type"
     );
 
+    let sector_challenges = leaf_challenges::<Tree>(post_config, randomness, pub_sectors)?;
+
+    info!("generate_sector_challenges:finish");
+
+    Ok(sector_challenges)
+}
+
+/// Returns the leaves of each sector which a Window proof-of-spacetime over `sector_ids`
+/// challenges, so that the regions of the trees it reads can be prefetched.
+///
+/// The sectors are proven in the order of their ids, as `generate_window_post` does with the
+/// replicas, so the leaves are the ones the prover opens regardless of the order of
+/// `sector_ids`.
+pub fn window_post_challenged_leaves<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    sector_ids: &[SectorId],
+) -> Result<HashMap<SectorId, Vec<usize>>> {
+    info!("window_post_challenged_leaves:start");
+    ensure!(
+        post_config.typ == PoStType::Window,
+        "invalid post config type"
+    );
+
+    let mut sorted_ids = sector_ids.to_vec();
+    sorted_ids.sort();
+    sorted_ids.dedup();
+
+    let leaves = leaf_challenges::<Tree>(post_config, randomness, &sorted_ids)?
+        .into_iter()
+        .map(|(sector_id, challenges)| {
+            let leaves = challenges
+                .into_iter()
+                .map(|challenge| challenge as usize)
+                .collect();
+            (sector_id, leaves)
+        })
+        .collect();
+
+    info!("window_post_challenged_leaves:finish");
+
+    Ok(leaves)
+}
+
+/// Derives the challenged leaves of the sectors in `pub_sectors`, in the order they are proven.
+fn leaf_challenges<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    pub_sectors: &[SectorId],
+) -> Result<BTreeMap<SectorId, Vec<u64>>> {
     let randomness_safe: <Tree::Hasher as Hasher>::Domain =
         as_safe_commitment(randomness, "randomness")?;
 
@@ -115,8 +165,6 @@ pub fn generate_fallback_sector_challenges<Tree: 'static + MerkleTreeTrait>(
         }
     }
 
-    info!("generate_sector_challenges:finish");
-
     Ok(sector_challenges)
 }
 
@@ -131,10 +179,10 @@ pub fn generate_single_vanilla_proof<Tree: 'static + MerkleTreeTrait>(
     info!("generate_single_vanilla_proof:start: {:?}", sector_id);
 
     let tree = &replica
-        .merkle_tree(post_config.sector_size)
+        .mmap_tree(post_config.sector_size)
         .with_context(|| {
             format!(
-                "generate_single_vanilla_proof: merkle_tree failed: {:?}",
+                "generate_single_vanilla_proof: mmap_tree failed: {:?}",
                 sector_id
             )
         })?;
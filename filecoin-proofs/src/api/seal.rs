@@ -1,44 +1,64 @@
+use std::collections::HashSet;
 use std::fs::{self, metadata, File, OpenOptions};
-use std::io::Write;
+use std::io::{BufReader, Read, Write};
 use std::path::{Path, PathBuf};
-
-use anyhow::{ensure, Context, Result};
-use bellperson::bls::Fr;
+use std::sync::Arc;
+
+use anyhow::{bail, ensure, Context, Result};
+use bellperson::{
+    bls::{Bls12, Fr},
+    groth16::PreparedVerifyingKey,
+    util_cs::test_cs::TestConstraintSystem,
+    Circuit,
+};
 use bincode::{deserialize, serialize};
 use filecoin_hashers::{Domain, Hasher};
 use log::{info, trace};
 use memmap::MmapOptions;
 use merkletree::store::{DiskStore, Store, StoreConfig};
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
 use storage_proofs_core::{
     cache_key::CacheKey,
     compound_proof::{self, CompoundProof},
     drgraph::Graph,
+    enter_span,
     measurements::{measure_op, Operation},
     merkle::{create_base_merkle_tree, BinaryMerkleTree, MerkleTreeTrait},
     multi_proof::MultiProof,
     proof::ProofScheme,
     sector::SectorId,
-    util::default_rows_to_discard,
+    util::{default_rows_to_discard, NODE_SIZE},
     Data,
 };
 use storage_proofs_porep::stacked::{
-    self, generate_replica_id, ChallengeRequirements, StackedCompound, StackedDrg, Tau,
-    TemporaryAux, TemporaryAuxCache,
+    self, generate_replica_id, ChallengeRequirements, StackedBucketGraph, StackedCompound,
+    StackedDrg, Tau, TemporaryAux, TemporaryAuxCache,
 };
 
 use crate::{
-    api::{as_safe_commitment, commitment_from_fr, get_base_tree_leafs, get_base_tree_size},
-    caches::{get_stacked_params, get_stacked_verifying_key},
+    api::{
+        as_safe_commitment, clear_cache, commitment_from_fr, get_base_tree_leafs,
+        get_base_tree_size, validate_cache_dir,
+    },
+    caches::{
+        get_stacked_params, get_stacked_verifying_key, get_stacked_verifying_key_for_version,
+    },
     constants::{
-        DefaultBinaryTree, DefaultPieceDomain, DefaultPieceHasher, POREP_MINIMUM_CHALLENGES,
+        porep_minimum_challenges, rows_to_discard, with_sector_shape, DefaultBinaryTree,
+        DefaultPieceDomain, DefaultPieceHasher, DefaultTreeDomain, DefaultTreeHasher, ShapeFn,
         SINGLE_PARTITION_PROOF_LEN,
     },
-    parameters::setup_params,
-    pieces::{self, verify_pieces},
+    data_tree_builder::IncrementalDataTreeBuilder,
+    error::Error,
+    param::{verifying_key_fingerprint, verifying_key_from_bytes},
+    pieces::{self, validate_piece_placement, verify_pieces},
     types::{
-        Commitment, PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, ProverId,
-        SealCommitOutput, SealCommitPhase1Output, SealPreCommitOutput, SealPreCommitPhase1Output,
-        SectorSize, Ticket, BINARY_ARITY,
+        CommD, Commitment, LabelControl, LabelProgress, PaddedBytesAmount, PieceInfo, PoRepConfig,
+        PoRepProofPartitions, ProverId, ReplicaWriteConfig, SealCommitOutput,
+        SealCommitPhase1Output, SealOutput, SealPreCommitOutput, SealPreCommitPhase1Output,
+        SealVerifyInput, SectorSize, Ticket, UnpaddedBytesAmount, BINARY_ARITY,
+        DIRECT_IO_ALIGNMENT,
     },
 };
 
@@ -57,8 +77,212 @@ where
     R: AsRef<Path>,
     S: AsRef<Path>,
     T: AsRef<Path>,
+{
+    seal_pre_commit_phase1_with_progress(
+        porep_config,
+        cache_path,
+        in_path,
+        out_path,
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+        true,
+        |_| LabelControl::Continue,
+    )
+}
+
+/// Like `seal_pre_commit_phase1`, but calls `progress` after each layer is labeled.
+///
+/// Returning `LabelControl::Break` stops sealing with
+/// `storage_proofs_core::error::Error::Cancelled`. Completed layers are left in
+/// `cache_path` together with a checkpoint of the sector they belong to.
+///
+/// With `resume` set, layers found in `cache_path` are reused if the checkpoint matches the
+/// sector id, ticket and sector size, so calling this again with the same arguments continues
+/// from the last completed layer. Otherwise, any existing layers are discarded first.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_pre_commit_phase1_with_progress<R, S, T, F, Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    cache_path: R,
+    in_path: S,
+    out_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+    resume: bool,
+    progress: F,
+) -> Result<SealPreCommitPhase1Output<Tree>>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+    F: FnMut(LabelProgress) -> LabelControl,
+{
+    seal_pre_commit_phase1_inner(
+        porep_config,
+        cache_path,
+        in_path,
+        out_path,
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+        resume,
+        progress,
+        &ReplicaWriteConfig::default(),
+        None,
+    )
+}
+
+/// Like `seal_pre_commit_phase1`, but writes the unsealed data to `out_path` as configured by
+/// `write_config`, such as through a larger buffer on slow disks. The replica is the same for
+/// any `write_config`.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_pre_commit_phase1_with_write_config<R, S, T, Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    cache_path: R,
+    in_path: S,
+    out_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+    write_config: &ReplicaWriteConfig,
+) -> Result<SealPreCommitPhase1Output<Tree>>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    seal_pre_commit_phase1_inner(
+        porep_config,
+        cache_path,
+        in_path,
+        out_path,
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+        true,
+        |_| LabelControl::Continue,
+        write_config,
+        None,
+    )
+}
+
+/// The graph labeled by `seal_pre_commit_phase1`, together with the porep config it was built
+/// for, see `porep_graph`.
+pub struct PoRepGraph<Tree: MerkleTreeTrait> {
+    porep_config: PoRepConfig,
+    graph: StackedBucketGraph<Tree::Hasher>,
+}
+
+impl<Tree: MerkleTreeTrait> PoRepGraph<Tree> {
+    /// Returns the porep config the graph was built for.
+    pub fn porep_config(&self) -> PoRepConfig {
+        self.porep_config
+    }
+
+    /// Returns whether the graph is the one `porep_config` seals with, which only depends on
+    /// the sector size, porep id and api version.
+    pub fn is_for(&self, porep_config: PoRepConfig) -> bool {
+        self.porep_config.sector_size == porep_config.sector_size
+            && self.porep_config.porep_id == porep_config.porep_id
+            && self.porep_config.api_version == porep_config.api_version
+    }
+}
+
+/// Builds the graph labeled by `seal_pre_commit_phase1` for `porep_config`.
+///
+/// The graph only depends on the sector size, porep id and api version, so one graph can be
+/// shared by concurrent `seal_pre_commit_phase1_with_graph` calls for any sectors sealed with
+/// the same `porep_config`.
+pub fn porep_graph<Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+) -> Result<Arc<PoRepGraph<Tree>>> {
+    let public_params =
+        StackedDrg::<Tree, DefaultPieceHasher>::setup(&porep_config.setup_params()?)?;
+
+    Ok(Arc::new(PoRepGraph {
+        porep_config,
+        graph: public_params.graph,
+    }))
+}
+
+/// Like `seal_pre_commit_phase1`, but labels `graph` instead of building the graph again.
+///
+/// `graph` must be built by `porep_graph` for the same sector size, porep id and api version
+/// as `porep_config`, otherwise an error is returned. The labels are the same as those of
+/// `seal_pre_commit_phase1`.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_pre_commit_phase1_with_graph<R, S, T, Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    graph: &PoRepGraph<Tree>,
+    cache_path: R,
+    in_path: S,
+    out_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+) -> Result<SealPreCommitPhase1Output<Tree>>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    ensure!(
+        graph.is_for(porep_config),
+        "graph was built for {:?}, which doesn't seal like {:?}",
+        graph.porep_config,
+        porep_config
+    );
+
+    seal_pre_commit_phase1_inner(
+        porep_config,
+        cache_path,
+        in_path,
+        out_path,
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+        true,
+        |_| LabelControl::Continue,
+        &ReplicaWriteConfig::default(),
+        Some(&graph.graph),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn seal_pre_commit_phase1_inner<R, S, T, F, Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    cache_path: R,
+    in_path: S,
+    out_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+    resume: bool,
+    mut progress: F,
+    write_config: &ReplicaWriteConfig,
+    graph: Option<&StackedBucketGraph<Tree::Hasher>>,
+) -> Result<SealPreCommitPhase1Output<Tree>>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+    F: FnMut(LabelProgress) -> LabelControl,
 {
     info!("seal_pre_commit_phase1:start: {:?}", sector_id);
+    let _span = enter_span!(
+        "seal_pre_commit_phase1",
+        sector_id = u64::from(sector_id),
+        sector_size = u64::from(porep_config.sector_size)
+    );
 
     // Sanity check all input path types.
     ensure!(
@@ -73,6 +297,7 @@ where
         metadata(cache_path.as_ref())?.is_dir(),
         "cache_path must be a directory"
     );
+    validate_piece_placement(u64::from(porep_config.sector_size), piece_infos)?;
 
     let sector_bytes = usize::from(PaddedBytesAmount::from(porep_config));
     fs::metadata(&in_path)
@@ -82,7 +307,7 @@ where
         .with_context(|| format!("could not read out_path={:?}", out_path.as_ref().display()))?;
 
     // Copy unsealed data to output location, where it will be sealed in place.
-    fs::copy(&in_path, &out_path).with_context(|| {
+    write_replica(in_path.as_ref(), out_path.as_ref(), write_config).with_context(|| {
         format!(
             "could not copy in_path={:?} to out_path={:?}",
             in_path.as_ref().display(),
@@ -105,28 +330,27 @@ where
             .with_context(|| format!("could not mmap out_path={:?}", out_path.as_ref().display()))?
     };
 
-    let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-            porep_config.porep_id,
-            porep_config.api_version,
-        )?,
-        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
-        priority: false,
+    let setup_params = porep_config.setup_params()?;
+    let public_params = match graph {
+        Some(graph) => {
+            ensure!(
+                graph.size() == setup_params.nodes,
+                "graph has {} nodes, expected {}",
+                graph.size(),
+                setup_params.nodes
+            );
+            stacked::PublicParams::new(graph.clone(), setup_params.layer_challenges)
+        }
+        None => StackedDrg::<Tree, DefaultPieceHasher>::setup(&setup_params)?,
     };
 
-    let compound_public_params = <StackedCompound<Tree, DefaultPieceHasher> as CompoundProof<
-        StackedDrg<'_, Tree, DefaultPieceHasher>,
-        _,
-    >>::setup(&compound_setup_params)?;
-
     info!("building merkle tree for the original data");
     let (config, comm_d) = measure_op(Operation::CommD, || -> Result<_> {
+        let _span = enter_span!("build_tree_d");
         let base_tree_size = get_base_tree_size::<DefaultBinaryTree>(porep_config.sector_size)?;
         let base_tree_leafs = get_base_tree_leafs::<DefaultBinaryTree>(base_tree_size)?;
         ensure!(
-            compound_public_params.vanilla_params.graph.size() == base_tree_leafs,
+            public_params.graph.size() == base_tree_leafs,
             "graph size and leaf size don't match"
         );
 
@@ -174,10 +398,22 @@ where
         &porep_config.porep_id,
     );
 
-    let labels = StackedDrg::<Tree, DefaultPieceHasher>::replicate_phase1(
-        &compound_public_params.vanilla_params,
+    let checkpoint = LabelCheckpoint {
+        sector_size: u64::from(porep_config.sector_size),
+        replica_id: replica_id.into_bytes(),
+    };
+    prepare_label_checkpoint(
+        cache_path.as_ref(),
+        &checkpoint,
+        public_params.layer_challenges.layers(),
+        resume,
+    )?;
+
+    let labels = StackedDrg::<Tree, DefaultPieceHasher>::replicate_phase1_with_progress(
+        &public_params,
         &replica_id,
         config.clone(),
+        &mut progress,
     )?;
 
     let out = SealPreCommitPhase1Output {
@@ -190,18 +426,151 @@ where
     Ok(out)
 }
 
+/// Copies the unsealed data at `in_path` to `out_path` as configured by `write_config`.
+///
+/// With direct I/O, the last write is padded with zeros to `DIRECT_IO_ALIGNMENT`. That's the
+/// same as the zero padding of the sector, and the file is truncated to the sector size after.
+fn write_replica(in_path: &Path, out_path: &Path, write_config: &ReplicaWriteConfig) -> Result<()> {
+    let buffer_size = match write_config.buffer_size {
+        Some(buffer_size) => buffer_size,
+        None => {
+            ensure!(
+                !write_config.direct_io,
+                "direct I/O needs a replica write buffer size"
+            );
+            fs::copy(in_path, out_path)?;
+            return Ok(());
+        }
+    };
+    ensure!(
+        buffer_size > 0,
+        "replica write buffer size must not be zero"
+    );
+    if write_config.direct_io {
+        ensure!(
+            buffer_size % DIRECT_IO_ALIGNMENT == 0,
+            "replica write buffer size {} is not a multiple of {} for direct I/O",
+            buffer_size,
+            DIRECT_IO_ALIGNMENT
+        );
+    }
+
+    let mut input = File::open(in_path)?;
+    let mut options = OpenOptions::new();
+    options.write(true).truncate(true);
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        if write_config.direct_io {
+            options.custom_flags(libc::O_DIRECT);
+        }
+    }
+    let mut output = options.open(out_path)?;
+
+    // Direct I/O needs a buffer aligned in memory as well.
+    let mut buffer = vec![0u8; buffer_size + DIRECT_IO_ALIGNMENT];
+    let start = buffer.as_ptr().align_offset(DIRECT_IO_ALIGNMENT);
+    let buffer = &mut buffer[start..start + buffer_size];
+    loop {
+        let mut filled = 0;
+        while filled < buffer_size {
+            match input.read(&mut buffer[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        if filled == 0 {
+            break;
+        }
+
+        let len = if write_config.direct_io {
+            let len =
+                (filled + DIRECT_IO_ALIGNMENT - 1) / DIRECT_IO_ALIGNMENT * DIRECT_IO_ALIGNMENT;
+            for byte in &mut buffer[filled..len] {
+                *byte = 0;
+            }
+            len
+        } else {
+            filled
+        };
+        output.write_all(&buffer[..len])?;
+
+        if filled < buffer_size {
+            break;
+        }
+    }
+    // Where `O_DIRECT` isn't available, the data is at least on disk before it's sealed.
+    if write_config.direct_io {
+        output.sync_data()?;
+    }
+
+    Ok(())
+}
+
+/// Identifies the sector the label layers in a cache directory were created for. The replica
+/// id covers the prover id, sector id, ticket, comm_d and porep id the labels depend on.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct LabelCheckpoint {
+    sector_size: u64,
+    replica_id: Vec<u8>,
+}
+
+/// Removes the label layers in `cache_path` unless they can be resumed from, and records
+/// `checkpoint` as the sector the layers are created for.
+fn prepare_label_checkpoint(
+    cache_path: &Path,
+    checkpoint: &LabelCheckpoint,
+    layers: usize,
+    resume: bool,
+) -> Result<()> {
+    let checkpoint_path = cache_path.join(CacheKey::LabelCheckpoint.to_string());
+    let matches = resume
+        && fs::read(&checkpoint_path)
+            .ok()
+            .and_then(|bytes| deserialize::<LabelCheckpoint>(&bytes).ok())
+            .map_or(false, |existing| existing == *checkpoint);
+    if matches {
+        return Ok(());
+    }
+
+    if resume {
+        info!("no matching label checkpoint found, labeling from the first layer");
+    }
+    for layer in 1..=layers {
+        let layer_path = StoreConfig::data_path(cache_path, &CacheKey::label_layer(layer));
+        if layer_path.exists() {
+            fs::remove_file(&layer_path)
+                .with_context(|| format!("could not remove layer={:?}", layer_path))?;
+        }
+    }
+
+    fs::write(&checkpoint_path, serialize(checkpoint)?)
+        .with_context(|| format!("could not write label checkpoint={:?}", checkpoint_path))
+}
+
+/// Builds tree_c and tree_r_last of the replica at `replica_path` and returns its comm_r.
+///
+/// The trees are written to `tree_dir` if given, such as a larger disk than the one holding
+/// `cache_path`, and to `cache_path` otherwise. A `tree_dir` has to be passed to
+/// `seal_commit_phase1` too, as the trees are looked up in `cache_path` otherwise.
 #[allow(clippy::too_many_arguments)]
 pub fn seal_pre_commit_phase2<R, S, Tree: 'static + MerkleTreeTrait>(
     porep_config: PoRepConfig,
     phase1_output: SealPreCommitPhase1Output<Tree>,
     cache_path: S,
     replica_path: R,
+    tree_dir: Option<&Path>,
 ) -> Result<SealPreCommitOutput>
 where
     R: AsRef<Path>,
     S: AsRef<Path>,
 {
     info!("seal_pre_commit_phase2:start");
+    let _span = enter_span!(
+        "seal_pre_commit_phase2",
+        sector_size = u64::from(porep_config.sector_size)
+    );
 
     // Sanity check all input path types.
     ensure!(
@@ -212,6 +581,9 @@ where
         metadata(replica_path.as_ref())?.is_file(),
         "replica_path must be a file"
     );
+    if let Some(tree_dir) = tree_dir {
+        ensure!(metadata(tree_dir)?.is_dir(), "tree_dir must be a directory");
+    }
 
     let SealPreCommitPhase1Output {
         mut labels,
@@ -265,12 +637,7 @@ where
     };
 
     let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-            porep_config.porep_id,
-            porep_config.api_version,
-        )?,
+        vanilla_params: porep_config.setup_params()?,
         partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
         priority: false,
     };
@@ -280,14 +647,17 @@ where
         _,
     >>::setup(&compound_setup_params)?;
 
-    let (tau, (p_aux, t_aux)) = StackedDrg::<Tree, DefaultPieceHasher>::replicate_phase2(
-        &compound_public_params.vanilla_params,
-        labels,
-        data,
-        data_tree,
-        config,
-        replica_path.as_ref().to_path_buf(),
-    )?;
+    let (tau, (p_aux, t_aux)) =
+        StackedDrg::<Tree, DefaultPieceHasher>::replicate_phase2_with_tree_path(
+            &compound_public_params.vanilla_params,
+            labels,
+            data,
+            data_tree,
+            config,
+            replica_path.as_ref().to_path_buf(),
+            Some(rows_to_discard(u64::from(porep_config.sector_size))?),
+            tree_dir.map(Path::to_path_buf),
+        )?;
 
     let comm_r = commitment_from_fr(tau.comm_r.into());
 
@@ -314,11 +684,16 @@ where
     Ok(out)
 }
 
+/// Generates the vanilla proofs of the replica at `replica_path` for `seed`.
+///
+/// `tree_dir` is the directory tree_c and tree_r_last were written to by
+/// `seal_pre_commit_phase2`, if not `cache_path`.
 #[allow(clippy::too_many_arguments)]
 pub fn seal_commit_phase1<T: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
     porep_config: PoRepConfig,
     cache_path: T,
     replica_path: T,
+    tree_dir: Option<&Path>,
     prover_id: ProverId,
     sector_id: SectorId,
     ticket: Ticket,
@@ -327,6 +702,11 @@ pub fn seal_commit_phase1<T: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
     piece_infos: &[PieceInfo],
 ) -> Result<SealCommitPhase1Output<Tree>> {
     info!("seal_commit_phase1:start: {:?}", sector_id);
+    let _span = enter_span!(
+        "seal_commit_phase1",
+        sector_id = u64::from(sector_id),
+        sector_size = u64::from(porep_config.sector_size)
+    );
 
     // Sanity check all input path types.
     ensure!(
@@ -337,6 +717,9 @@ pub fn seal_commit_phase1<T: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
         metadata(replica_path.as_ref())?.is_file(),
         "replica_path must be a file"
     );
+    if let Some(tree_dir) = tree_dir {
+        ensure!(metadata(tree_dir)?.is_dir(), "tree_dir must be a directory");
+    }
 
     let SealPreCommitOutput { comm_d, comm_r } = pre_commit;
 
@@ -362,8 +745,11 @@ pub fn seal_commit_phase1<T: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
 
         let mut res: TemporaryAux<_, _> = deserialize(&t_aux_bytes)?;
 
-        // Switch t_aux to the passed in cache_path
+        // Switch t_aux to the passed in cache_path and tree_dir
         res.set_cache_path(cache_path);
+        if let Some(tree_dir) = tree_dir {
+            res.set_tree_path(tree_dir);
+        }
         res
     };
 
@@ -400,12 +786,7 @@ pub fn seal_commit_phase1<T: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
     };
 
     let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-            porep_config.porep_id,
-            porep_config.api_version,
-        )?,
+        vanilla_params: porep_config.setup_params()?,
         partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
         priority: false,
     };
@@ -427,7 +808,9 @@ pub fn seal_commit_phase1<T: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
         &public_inputs,
         &vanilla_proofs,
     )?;
-    ensure!(sanity_check, "Invalid vanilla proof generated");
+    if !sanity_check {
+        return Err(Error::InvalidProof).context("Invalid vanilla proof generated");
+    }
 
     let out = SealCommitPhase1Output {
         vanilla_proofs,
@@ -442,6 +825,79 @@ pub fn seal_commit_phase1<T: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
     Ok(out)
 }
 
+/// Seals a sector by running all four sealing phases, from the staged sector at `in_path` to
+/// the commit proof. The replica is written to `out_path`.
+///
+/// The layers and trees only needed to generate the proof are deleted from `cache_path` once
+/// the proof is generated, unless `keep_intermediates` is set.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_all<R, S, T, Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    cache_path: R,
+    in_path: S,
+    out_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    piece_infos: &[PieceInfo],
+    keep_intermediates: bool,
+) -> Result<SealOutput>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    info!("seal_all:start: {:?}", sector_id);
+
+    let phase1_output = seal_pre_commit_phase1::<_, _, _, Tree>(
+        porep_config,
+        cache_path.as_ref(),
+        in_path,
+        out_path.as_ref(),
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+    )?;
+    let pre_commit = seal_pre_commit_phase2(
+        porep_config,
+        phase1_output,
+        cache_path.as_ref(),
+        out_path.as_ref(),
+        None,
+    )?;
+    let (comm_r, comm_d) = (pre_commit.comm_r, pre_commit.comm_d);
+
+    validate_cache_dir::<_, Tree>(porep_config, cache_path.as_ref(), None)?;
+
+    let commit_phase1_output = seal_commit_phase1::<_, Tree>(
+        porep_config,
+        cache_path.as_ref(),
+        out_path.as_ref(),
+        None,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        pre_commit,
+        piece_infos,
+    )?;
+    if !keep_intermediates {
+        clear_cache::<Tree>(cache_path.as_ref())?;
+    }
+
+    let SealCommitOutput { proof } =
+        seal_commit_phase2(porep_config, commit_phase1_output, prover_id, sector_id)?;
+
+    info!("seal_all:finish: {:?}", sector_id);
+    Ok(SealOutput {
+        comm_r,
+        comm_d,
+        proof,
+    })
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn seal_commit_phase2<Tree: 'static + MerkleTreeTrait>(
     porep_config: PoRepConfig,
@@ -450,6 +906,11 @@ pub fn seal_commit_phase2<Tree: 'static + MerkleTreeTrait>(
     sector_id: SectorId,
 ) -> Result<SealCommitOutput> {
     info!("seal_commit_phase2:start: {:?}", sector_id);
+    let _span = enter_span!(
+        "seal_commit_phase2",
+        sector_id = u64::from(sector_id),
+        sector_size = u64::from(porep_config.sector_size)
+    );
 
     let SealCommitPhase1Output {
         vanilla_proofs,
@@ -484,12 +945,7 @@ pub fn seal_commit_phase2<Tree: 'static + MerkleTreeTrait>(
     );
 
     let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-            porep_config.porep_id,
-            porep_config.api_version,
-        )?,
+        vanilla_params: porep_config.setup_params()?,
         partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
         priority: false,
     };
@@ -500,6 +956,7 @@ pub fn seal_commit_phase2<Tree: 'static + MerkleTreeTrait>(
     >>::setup(&compound_setup_params)?;
 
     info!("snark_proof:start");
+    let snark_span = enter_span!("snark_proof");
     let groth_proofs = StackedCompound::<Tree, DefaultPieceHasher>::circuit_proofs(
         &public_inputs,
         vanilla_proofs,
@@ -507,6 +964,7 @@ pub fn seal_commit_phase2<Tree: 'static + MerkleTreeTrait>(
         &groth_params,
         compound_public_params.priority,
     )?;
+    drop(snark_span);
     info!("snark_proof:finish");
 
     let proof = MultiProof::new(groth_proofs, &groth_params.pvk);
@@ -519,7 +977,7 @@ pub fn seal_commit_phase2<Tree: 'static + MerkleTreeTrait>(
 
     // Verification is cheap when parameters are cached,
     // and it is never correct to return a proof which does not verify.
-    verify_seal::<Tree>(
+    let verified = verify_seal::<Tree>(
         porep_config,
         comm_r,
         comm_d,
@@ -530,6 +988,17 @@ pub fn seal_commit_phase2<Tree: 'static + MerkleTreeTrait>(
         &buf,
     )
     .context("post-seal verification sanity check failed")?;
+    if !verified {
+        let fingerprint = verifying_key_fingerprint(&porep_config)
+            .map(hex::encode)
+            .unwrap_or_else(|_| "unavailable".to_string());
+        return Err(Error::InvalidProof).with_context(|| {
+            format!(
+                "post-seal verification sanity check failed (verifying key fingerprint {})",
+                fingerprint
+            )
+        });
+    }
 
     let out = SealCommitOutput { proof: buf };
 
@@ -537,11 +1006,169 @@ pub fn seal_commit_phase2<Tree: 'static + MerkleTreeTrait>(
     Ok(out)
 }
 
+/// Synthesizes the circuits `seal_commit_phase2` would prove for `phase1_output` and checks
+/// that all of their constraints are satisfied, without generating a proof.
+///
+/// No groth parameters are needed, which makes this a cheap way to catch circuit regressions.
+/// Fails with the partition and the path of the first unsatisfied constraint, if any.
+pub fn seal_commit_phase2_check<Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    phase1_output: SealCommitPhase1Output<Tree>,
+) -> Result<()> {
+    info!("seal_commit_phase2_check:start");
+
+    let SealCommitPhase1Output {
+        vanilla_proofs,
+        comm_d,
+        comm_r,
+        replica_id,
+        seed,
+        ..
+    } = phase1_output;
+
+    ensure!(comm_d != [0; 32], "Invalid all zero commitment (comm_d)");
+    ensure!(comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
+    ensure!(
+        vanilla_proofs.len() == usize::from(PoRepProofPartitions::from(porep_config)),
+        "expected vanilla proofs for {} partitions, got {}",
+        usize::from(PoRepProofPartitions::from(porep_config)),
+        vanilla_proofs.len()
+    );
+
+    let comm_r_safe = as_safe_commitment(&comm_r, "comm_r")?;
+    let comm_d_safe = DefaultPieceDomain::try_from_bytes(&comm_d)?;
+
+    let public_inputs = stacked::PublicInputs {
+        replica_id,
+        tau: Some(stacked::Tau {
+            comm_d: comm_d_safe,
+            comm_r: comm_r_safe,
+        }),
+        k: None,
+        seed,
+    };
+
+    let compound_setup_params = compound_proof::SetupParams {
+        vanilla_params: porep_config.setup_params()?,
+        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
+        priority: false,
+    };
+
+    let compound_public_params = <StackedCompound<Tree, DefaultPieceHasher> as CompoundProof<
+        StackedDrg<'_, Tree, DefaultPieceHasher>,
+        _,
+    >>::setup(&compound_setup_params)?;
+    let public_params = &compound_public_params.vanilla_params;
+
+    for (k, vanilla_proof) in vanilla_proofs.iter().enumerate() {
+        let circuit = <StackedCompound<Tree, DefaultPieceHasher> as CompoundProof<
+            StackedDrg<'_, Tree, DefaultPieceHasher>,
+            _,
+        >>::circuit(
+            &public_inputs,
+            Default::default(),
+            vanilla_proof,
+            public_params,
+            Some(k),
+        )?;
+        let inputs = StackedCompound::<Tree, DefaultPieceHasher>::generate_public_inputs(
+            &public_inputs,
+            public_params,
+            Some(k),
+        )?;
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        circuit
+            .synthesize(&mut cs)
+            .with_context(|| format!("failed to synthesize the circuit of partition {}", k))?;
+
+        if let Some(constraint) = cs.which_is_unsatisfied() {
+            bail!(
+                "partition {}: constraint {} is not satisfied",
+                k,
+                constraint
+            );
+        }
+        ensure!(
+            cs.verify(&inputs),
+            "partition {}: the circuit inputs don't match the public inputs",
+            k
+        );
+    }
+
+    info!("seal_commit_phase2_check:finish");
+    Ok(())
+}
+
+/// Runs `seal_commit_phase2` for a batch of sectors of the same prover.
+///
+/// The groth parameters for every sector size in the batch are loaded once up front, and are
+/// then shared by all proofs through the parameter memory cache. The outputs are the same as
+/// calling `seal_commit_phase2` for each sector individually.
+///
+/// # Arguments
+///
+/// * `porep_configs` - the porep config of each sector.
+/// * `phase1_outputs` - the output of `seal_commit_phase1` for each sector.
+/// * `prover_id` - the prover-id that sealed the sectors.
+/// * `sector_ids` - the sector-id of each sector.
+pub fn seal_commit_phase2_batch<Tree: 'static + MerkleTreeTrait>(
+    porep_configs: &[PoRepConfig],
+    phase1_outputs: Vec<SealCommitPhase1Output<Tree>>,
+    prover_id: ProverId,
+    sector_ids: &[SectorId],
+) -> Result<Vec<SealCommitOutput>> {
+    info!("seal_commit_phase2_batch:start");
+
+    ensure!(
+        porep_configs.len() == phase1_outputs.len() && porep_configs.len() == sector_ids.len(),
+        "inconsistent batch: {} porep configs, {} phase1 outputs, {} sector ids",
+        porep_configs.len(),
+        phase1_outputs.len(),
+        sector_ids.len(),
+    );
+
+    let mut loaded_sector_sizes = HashSet::new();
+    for porep_config in porep_configs {
+        if loaded_sector_sizes.insert(u64::from(porep_config.sector_size)) {
+            get_stacked_params::<Tree>(*porep_config)?;
+        }
+    }
+
+    let outputs = porep_configs
+        .iter()
+        .zip(phase1_outputs.into_iter())
+        .zip(sector_ids.iter())
+        .map(|((porep_config, phase1_output), sector_id)| {
+            seal_commit_phase2(*porep_config, phase1_output, prover_id, *sector_id)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    info!("seal_commit_phase2_batch:finish");
+    Ok(outputs)
+}
+
+/// Returns the `comm_d` of an empty (all zero) sector of `sector_size` bytes, as used for
+/// committed capacity sectors.
+///
+/// All subtrees of the same height are equal in a zero filled sector, so this hashes a
+/// single node per tree level instead of the whole sector.
+pub fn zero_comm_d(sector_size: u64) -> Result<Commitment> {
+    let size = UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size));
+    let piece_info = pieces::zero_padding(size)
+        .with_context(|| format!("invalid sector size {}", sector_size))?;
+
+    Ok(piece_info.commitment)
+}
+
 /// Computes a sectors's `comm_d` given its pieces.
 ///
+/// Only the piece commitments are needed, not the data: the alignment between pieces and the
+/// unfilled end of the sector are committed as zeros, the same way `add_piece` pads them.
+///
 /// # Arguments
 ///
-/// * `porep_config` - this sector's porep config that contains the number of bytes in the sector.
+/// * `sector_size` - the number of bytes in the sector.
 /// * `piece_infos` - the piece info (commitment and byte length) for each piece in this sector.
 pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Result<Commitment> {
     info!("compute_comm_d:start");
@@ -552,6 +1179,151 @@ pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Res
     result
 }
 
+/// Computes a sector's `comm_d` given its pieces, using `H` as the piece hasher.
+///
+/// `compute_comm_d` is equivalent to calling this with `DefaultPieceHasher`.
+///
+/// # Arguments
+///
+/// * `sector_size` - the number of bytes in the sector.
+/// * `piece_infos` - the piece info (commitment and byte length) for each piece in this sector.
+pub fn compute_comm_d_with_hasher<H: Hasher>(
+    sector_size: SectorSize,
+    piece_infos: &[PieceInfo],
+) -> Result<Commitment> {
+    info!("compute_comm_d_with_hasher:start");
+
+    let result = pieces::compute_comm_d_with_hasher::<H>(sector_size, piece_infos);
+
+    info!("compute_comm_d_with_hasher:finish");
+    result
+}
+
+/// Returns true if `expected` is the comm_d of the staged sector at `path`, without doing any
+/// of the other work of sealing.
+///
+/// The file holds the preprocessed sector data, as written by `add_piece`. Like
+/// `seal_pre_commit_phase1`, space after the end of the file is committed as zeros.
+///
+/// # Arguments
+///
+/// * `path` - path to the staged sector file.
+/// * `sector_size` - the number of bytes in the sector.
+/// * `expected` - the comm_d the data is expected to have.
+pub fn verify_comm_d_for_file(path: &Path, sector_size: u64, expected: CommD) -> Result<bool> {
+    info!("verify_comm_d_for_file:start");
+
+    let file =
+        File::open(path).with_context(|| format!("could not open path={:?}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut builder = IncrementalDataTreeBuilder::new(SectorSize(sector_size))?;
+
+    let mut buf = vec![0u8; 1 << 20];
+    let mut bytes_read = 0;
+    loop {
+        let n = reader
+            .read(&mut buf)
+            .with_context(|| format!("could not read path={:?}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        builder.add_leaves(&buf[..n])?;
+        bytes_read += n;
+    }
+
+    // Complete a partial last node with zeros.
+    let remainder = bytes_read % NODE_SIZE;
+    if remainder != 0 {
+        builder.add_leaves(&[0u8; NODE_SIZE][remainder..])?;
+    }
+    let comm_d = builder.finalize()?;
+
+    info!("verify_comm_d_for_file:finish");
+    Ok(comm_d == Commitment::from(expected))
+}
+
+/// Derives the replica-id of a sector the same way sealing does.
+///
+/// # Arguments
+///
+/// * `prover_id` - the prover-id that sealed the sector.
+/// * `sector_id` - the sector-id of the sealed sector.
+/// * `ticket` - the ticket used to seal the sector.
+/// * `comm_d` - the commitment to the sector's data.
+/// * `porep_seed` - the porep-id of the sector's porep config.
+pub fn derive_replica_id(
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    comm_d: Commitment,
+    porep_seed: &[u8; 32],
+) -> DefaultTreeDomain {
+    generate_replica_id::<DefaultTreeHasher, _>(
+        &prover_id,
+        sector_id.into(),
+        &ticket,
+        comm_d,
+        porep_seed,
+    )
+}
+
+/// Computes the node indices challenged in one partition of a seal proof.
+///
+/// These are the same challenges `verify_seal` checks, so they can be used to replay or audit a
+/// proof independently.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `replica_id` - the replica-id of the sealed sector.
+/// * `seed` - the seed used to derive the porep challenges.
+/// * `partition` - the (zero-based) partition of the proof to compute the challenges for.
+pub fn compute_challenges<D: Domain>(
+    porep_config: &PoRepConfig,
+    replica_id: &D,
+    seed: &Ticket,
+    partition: usize,
+) -> Result<Vec<usize>> {
+    let partitions = usize::from(PoRepProofPartitions::from(*porep_config));
+    ensure!(
+        partition < partitions,
+        "partition {} out of range, there are {} partitions",
+        partition,
+        partitions
+    );
+
+    let setup_params = porep_config.setup_params()?;
+
+    Ok(setup_params
+        .layer_challenges
+        .derive(setup_params.nodes, replica_id, seed, partition as u8))
+}
+
+/// Returns the partition proving the challenge at `challenge_index`, counting the challenges of
+/// all partitions of a seal proof in order, and the index of the challenge within the ones
+/// `compute_challenges` returns for that partition.
+pub fn challenge_partition(
+    porep_config: &PoRepConfig,
+    challenge_index: usize,
+) -> Result<(usize, usize)> {
+    let partitions = usize::from(PoRepProofPartitions::from(*porep_config));
+    let per_partition = porep_config
+        .setup_params()?
+        .layer_challenges
+        .challenges_count_all();
+    ensure!(
+        challenge_index < partitions * per_partition,
+        "challenge {} out of range, there are {} challenges",
+        challenge_index,
+        partitions * per_partition
+    );
+
+    Ok((
+        challenge_index / per_partition,
+        challenge_index % per_partition,
+    ))
+}
+
 /// Verifies the output of some previously-run seal operation.
 ///
 /// # Arguments
@@ -578,6 +1350,135 @@ pub fn verify_seal<Tree: 'static + MerkleTreeTrait>(
     info!("verify_seal:start: {:?}", sector_id);
     ensure!(comm_d_in != [0; 32], "Invalid all zero commitment (comm_d)");
     ensure!(comm_r_in != [0; 32], "Invalid all zero commitment (comm_r)");
+    // Rejected before the verifying key is loaded, which may have to generate parameters.
+    ensure_proof_len(porep_config, proof_vec)?;
+
+    let verifying_key = get_stacked_verifying_key::<Tree>(porep_config)?;
+    info!(
+        "got verifying key ({}) while verifying seal",
+        u64::from(PaddedBytesAmount::from(porep_config))
+    );
+
+    let result = verify_seal_with_vk::<Tree>(
+        porep_config,
+        &verifying_key,
+        comm_r_in,
+        comm_d_in,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        proof_vec,
+    );
+
+    info!("verify_seal:finish: {:?}", sector_id);
+    result
+}
+
+/// Verifies the output of some previously-run seal operation with a verifying key given as
+/// bytes, serialized like the `.vk` files in the parameter cache.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `vk_bytes` - the serialized verifying key for this sector's porep circuit.
+/// * `comm_r_in` - commitment to the sector's replica (`comm_r`).
+/// * `comm_d_in` - commitment to the sector's data (`comm_d`).
+/// * `prover_id` - the prover-id that sealed this sector.
+/// * `sector_id` - this sector's sector-id.
+/// * `ticket` - the ticket that was used to generate this sector's replica-id.
+/// * `seed` - the seed used to derive the porep challenges.
+/// * `proof_vec` - the porep circuit proof serialized into a vector of bytes.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_seal_from_vk_bytes<Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    vk_bytes: &[u8],
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof_vec: &[u8],
+) -> Result<bool> {
+    let verifying_key = verifying_key_from_bytes(vk_bytes)?;
+
+    verify_seal_with_vk::<Tree>(
+        porep_config,
+        &verifying_key,
+        comm_r_in,
+        comm_d_in,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        proof_vec,
+    )
+}
+
+/// Like `verify_seal`, but verifies with the verifying key published under parameter version
+/// `version` instead of the current `VERSION`.
+///
+/// This lets verifiers which only have the keys of an older version verify proofs while the
+/// network upgrades, as long as the circuits are unchanged. Fails for versions not listed in
+/// `CIRCUIT_COMPATIBLE_VERSIONS`, and if the key of `version` is not in the parameter cache.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_seal_with_param_version<Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    version: usize,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof_vec: &[u8],
+) -> Result<bool> {
+    let verifying_key = get_stacked_verifying_key_for_version::<Tree>(porep_config, version)?;
+
+    verify_seal_with_vk::<Tree>(
+        porep_config,
+        &verifying_key,
+        comm_r_in,
+        comm_d_in,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        proof_vec,
+    )
+}
+
+/// Verifies the output of some previously-run seal operation with an already loaded verifying
+/// key. Unlike `verify_seal`, this never looks up or generates any parameters.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `verifying_key` - the prepared verifying key for this sector's porep circuit.
+/// * `comm_r_in` - commitment to the sector's replica (`comm_r`).
+/// * `comm_d_in` - commitment to the sector's data (`comm_d`).
+/// * `prover_id` - the prover-id that sealed this sector.
+/// * `sector_id` - this sector's sector-id.
+/// * `ticket` - the ticket that was used to generate this sector's replica-id.
+/// * `seed` - the seed used to derive the porep challenges.
+/// * `proof_vec` - the porep circuit proof serialized into a vector of bytes.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_seal_with_vk<Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    verifying_key: &PreparedVerifyingKey<Bls12>,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof_vec: &[u8],
+) -> Result<bool> {
+    info!("verify_seal_with_vk:start: {:?}", sector_id);
+    ensure!(comm_d_in != [0; 32], "Invalid all zero commitment (comm_d)");
+    ensure!(comm_r_in != [0; 32], "Invalid all zero commitment (comm_r)");
+    ensure_proof_len(porep_config, proof_vec)?;
 
     let comm_r: <Tree::Hasher as Hasher>::Domain = as_safe_commitment(&comm_r_in, "comm_r")?;
     let comm_d: DefaultPieceDomain = as_safe_commitment(&comm_d_in, "comm_d")?;
@@ -591,12 +1492,7 @@ pub fn verify_seal<Tree: 'static + MerkleTreeTrait>(
     );
 
     let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-            porep_config.porep_id,
-            porep_config.api_version,
-        )?,
+        vanilla_params: porep_config.setup_params()?,
         partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
         priority: false,
     };
@@ -615,38 +1511,45 @@ pub fn verify_seal<Tree: 'static + MerkleTreeTrait>(
         };
 
     let result = {
-        let sector_bytes = PaddedBytesAmount::from(porep_config);
-        let verifying_key = get_stacked_verifying_key::<Tree>(porep_config)?;
-
-        info!(
-            "got verifying key ({}) while verifying seal",
-            u64::from(sector_bytes)
-        );
-
         let proof = MultiProof::new_from_reader(
             Some(usize::from(PoRepProofPartitions::from(porep_config))),
             proof_vec,
-            &verifying_key,
-        )?;
+            verifying_key,
+        )
+        .context(Error::InvalidProof)?;
 
         StackedCompound::verify(
             &compound_public_params,
             &public_inputs,
             &proof,
             &ChallengeRequirements {
-                minimum_challenges: *POREP_MINIMUM_CHALLENGES
-                    .read()
-                    .expect("POREP_MINIMUM_CHALLENGES poisoned")
-                    .get(&u64::from(SectorSize::from(porep_config)))
-                    .expect("unknown sector size") as usize,
+                minimum_challenges: porep_minimum_challenges(u64::from(SectorSize::from(
+                    porep_config,
+                )))? as usize,
             },
         )
     };
 
-    info!("verify_seal:finish: {:?}", sector_id);
+    info!("verify_seal_with_vk:finish: {:?}", sector_id);
     result
 }
 
+/// Fails with `Error::MalformedProof` unless `proof_vec` holds a proof for every partition of
+/// `porep_config`, so a truncated proof isn't reported as a deserialization failure.
+fn ensure_proof_len(porep_config: PoRepConfig, proof_vec: &[u8]) -> Result<()> {
+    let expected_len =
+        SINGLE_PARTITION_PROOF_LEN * usize::from(PoRepProofPartitions::from(porep_config));
+    if proof_vec.len() != expected_len {
+        return Err(Error::MalformedProof {
+            expected_len,
+            actual_len: proof_vec.len(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
 /// Verifies a batch of outputs of some previously-run seal operations.
 ///
 /// # Arguments
@@ -703,12 +1606,7 @@ pub fn verify_batch_seal<Tree: 'static + MerkleTreeTrait>(
     );
 
     let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-            porep_config.porep_id,
-            porep_config.api_version,
-        )?,
+        vanilla_params: porep_config.setup_params()?,
         partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
         priority: false,
     };
@@ -742,11 +1640,14 @@ pub fn verify_batch_seal<Tree: 'static + MerkleTreeTrait>(
             seed: seeds[i],
             k: None,
         });
-        proofs.push(MultiProof::new_from_reader(
-            Some(usize::from(PoRepProofPartitions::from(porep_config))),
-            proof_vecs[i],
-            &verifying_key,
-        )?);
+        proofs.push(
+            MultiProof::new_from_reader(
+                Some(usize::from(PoRepProofPartitions::from(porep_config))),
+                proof_vecs[i],
+                &verifying_key,
+            )
+            .context(Error::InvalidProof)?,
+        );
     }
 
     let result = StackedCompound::<Tree, DefaultPieceHasher>::batch_verify(
@@ -754,11 +1655,8 @@ pub fn verify_batch_seal<Tree: 'static + MerkleTreeTrait>(
         &public_inputs,
         &proofs,
         &ChallengeRequirements {
-            minimum_challenges: *POREP_MINIMUM_CHALLENGES
-                .read()
-                .expect("POREP_MINIMUM_CHALLENGES poisoned")
-                .get(&u64::from(SectorSize::from(porep_config)))
-                .expect("unknown sector size") as usize,
+            minimum_challenges: porep_minimum_challenges(u64::from(SectorSize::from(porep_config)))?
+                as usize,
         },
     )
     .map_err(Into::into);
@@ -766,3 +1664,86 @@ pub fn verify_batch_seal<Tree: 'static + MerkleTreeTrait>(
     info!("verify_batch_seal:finish");
     result
 }
+
+/// Verifies the seal proofs of many sectors, which may be of different sizes and porep
+/// configs, and returns whether each of them is valid.
+///
+/// The verifying key for each distinct porep config is loaded once, and the proofs are
+/// verified in parallel. Proofs that can't be decoded are reported as invalid.
+///
+/// # Arguments
+///
+/// * `items` - the porep config of each sector, together with its seal inputs and proof.
+pub fn verify_seals(items: &[(PoRepConfig, SealVerifyInput)]) -> Result<Vec<bool>> {
+    info!("verify_seals:start: {} proofs", items.len());
+
+    let mut groups: Vec<(PoRepConfig, Vec<usize>)> = Vec::new();
+    for (i, (porep_config, _)) in items.iter().enumerate() {
+        match groups.iter_mut().find(|(config, _)| config == porep_config) {
+            Some((_, indices)) => indices.push(i),
+            None => groups.push((*porep_config, vec![i])),
+        }
+    }
+
+    let mut results = vec![false; items.len()];
+    for (porep_config, indices) in groups {
+        let inputs = indices.iter().map(|&i| &items[i].1).collect();
+        let verified = with_sector_shape(
+            u64::from(porep_config.sector_size),
+            VerifySeals {
+                porep_config,
+                inputs,
+            },
+        )??;
+
+        for (i, verified) in indices.into_iter().zip(verified) {
+            results[i] = verified;
+        }
+    }
+
+    info!("verify_seals:finish");
+    Ok(results)
+}
+
+struct VerifySeals<'a> {
+    porep_config: PoRepConfig,
+    inputs: Vec<&'a SealVerifyInput>,
+}
+
+impl ShapeFn for VerifySeals<'_> {
+    type Output = Result<Vec<bool>>;
+
+    fn call<Tree: 'static + MerkleTreeTrait>(self) -> Self::Output {
+        let porep_config = self.porep_config;
+        let verifying_key = get_stacked_verifying_key::<Tree>(porep_config)?;
+
+        self.inputs
+            .par_iter()
+            .map(|input| {
+                let result = verify_seal_with_vk::<Tree>(
+                    porep_config,
+                    &verifying_key,
+                    input.comm_r,
+                    input.comm_d,
+                    input.prover_id,
+                    input.sector_id,
+                    input.ticket,
+                    input.seed,
+                    &input.proof,
+                );
+
+                match result {
+                    Err(err)
+                        if matches!(
+                            err.downcast_ref::<Error>(),
+                            Some(Error::InvalidProof) | Some(Error::MalformedProof { .. })
+                        ) =>
+                    {
+                        Ok(false)
+                    }
+                    result => result,
+                }
+            })
+            .collect()
+    }
+}
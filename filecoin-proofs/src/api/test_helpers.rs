@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use log::info;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use sha2::{Digest, Sha256};
+use storage_proofs_core::{api_version::ApiVersion, merkle::MerkleTreeTrait, sector::SectorId};
+
+use crate::{
+    api::{fauxrep_aux, window_post::generate_window_post_inner, zero_comm_d},
+    constants::{with_sector_shape, ShapeFn, SECTOR_SIZE_32_GIB},
+    error::Error,
+    types::{
+        ChallengeSeed, CommD, CommR, Commitment, PoRepConfig, PoStConfig, PrivateReplicaInfo,
+        ProverId, SnarkProof,
+    },
+};
+
+/// Generates a fake sealed sector of `sector_size` bytes for tests, like `fauxrep` but
+/// deterministically: the same `seed` always gives the same replica and commitments.
+///
+/// The replica is written to `sealed` in a new temporary directory, with the trees and
+/// persistent aux needed to prove it in `cache` next to it. The caller removes the directory
+/// when done. The sector is empty, so its comm_d is `zero_comm_d(sector_size)`.
+///
+/// Only the sector sizes smaller than 32 GiB are supported.
+pub fn generate_test_sealed_sector(sector_size: u64, seed: u64) -> Result<(PathBuf, CommR, CommD)> {
+    if sector_size >= SECTOR_SIZE_32_GIB {
+        return Err(Error::UnsupportedSectorSize(sector_size).into());
+    }
+    let porep_config = PoRepConfig::for_sector_size(sector_size, [0; 32], ApiVersion::V1_1_0)?;
+
+    let dir = tempfile::Builder::new()
+        .prefix("test-sealed-sector")
+        .tempdir()?
+        .into_path();
+    let cache_path = dir.join("cache");
+    let sealed_path = dir.join("sealed");
+    fs::create_dir(&cache_path)
+        .with_context(|| format!("could not create cache_path={:?}", cache_path))?;
+
+    let comm_r = with_sector_shape(
+        sector_size,
+        TestSealedSector {
+            porep_config,
+            seed,
+            cache_path: &cache_path,
+            sealed_path: &sealed_path,
+        },
+    )??;
+    let comm_d = zero_comm_d(sector_size)?;
+
+    Ok((
+        sealed_path,
+        CommR::try_from(comm_r)?,
+        CommD::try_from(comm_d)?,
+    ))
+}
+
+struct TestSealedSector<'a> {
+    porep_config: PoRepConfig,
+    seed: u64,
+    cache_path: &'a Path,
+    sealed_path: &'a Path,
+}
+
+impl ShapeFn for TestSealedSector<'_> {
+    type Output = Result<Commitment>;
+
+    fn call<Tree: 'static + MerkleTreeTrait>(self) -> Self::Output {
+        let mut rng = XorShiftRng::seed_from_u64(self.seed);
+        fauxrep_aux::<_, _, _, Tree>(
+            &mut rng,
+            self.porep_config,
+            self.cache_path,
+            self.sealed_path,
+        )
+    }
+}
+
+/// Generates a Window proof-of-spacetime which only depends on the inputs, for test vectors
+/// and conformance tests against the spec. Only available with the `test-helpers` feature.
+///
+/// The challenges are derived from `randomness` exactly like in `generate_window_post`, so
+/// the proof verifies with `verify_window_post`. Unlike there, the randomness of the SNARK
+/// proof is derived from `randomness` too, which makes the proof reproducible but **not**
+/// zero-knowledge. Never use this for proofs submitted on chain.
+pub fn generate_window_post_with_randomness<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo<Tree>>,
+    prover_id: ProverId,
+) -> Result<SnarkProof> {
+    info!("generate_window_post_with_randomness:start");
+
+    let mut seed = [0u8; 16];
+    seed.copy_from_slice(
+        &Sha256::new()
+            .chain(b"filecoin-proofs window post snark randomness")
+            .chain(randomness)
+            .finalize()[..16],
+    );
+    let mut rng = XorShiftRng::from_seed(seed);
+
+    let proof = generate_window_post_inner(post_config, randomness, replicas, prover_id, &mut rng)?;
+
+    info!("generate_window_post_with_randomness:finish");
+
+    Ok(proof)
+}
@@ -0,0 +1,163 @@
+use std::convert::TryFrom;
+
+use anyhow::{ensure, Context, Result};
+use bellperson::bls::Fr;
+use ff::Field;
+use filecoin_hashers::{Domain, HashFunction, Hasher};
+use merkletree::hash::Algorithm;
+use rayon::prelude::{IndexedParallelIterator, ParallelIterator, ParallelSlice};
+use storage_proofs_core::{
+    merkle::{get_base_tree_count, MerkleTreeTrait},
+    util::NODE_SIZE,
+};
+use typenum::Unsigned;
+
+use crate::{
+    api::{as_safe_commitment, commitment_from_fr, util::read_nodes},
+    constants::{DefaultTreeDomain, DefaultTreeHasher},
+    types::{CommR, Commitment},
+};
+
+/// Encodes `new_data`, the padded data of an empty sector update, into `old_replica`, the
+/// replica of a committed capacity sector, which becomes the updated replica.
+///
+/// Every node is encoded as `old + data * rho`, where `rho` is the hash of `key` and the node
+/// index, so the same inputs always give the same replica. Returns comm_r of the updated
+/// replica: the hash of the sector's `comm_c` and the root of the new tree_r_last of shape
+/// `Tree`. If a node of `new_data` or `old_replica` is not a field element, an error is
+/// returned and `old_replica` is left unchanged.
+pub fn encode_into<Tree: MerkleTreeTrait<Hasher = DefaultTreeHasher>>(
+    new_data: &[u8],
+    old_replica: &mut [u8],
+    comm_c: Commitment,
+    key: Commitment,
+) -> Result<CommR> {
+    ensure_sector_len::<Tree>(new_data.len(), old_replica.len())?;
+    let comm_c: DefaultTreeDomain = as_safe_commitment(&comm_c, "comm_c")?;
+    let key: DefaultTreeDomain = as_safe_commitment(&key, "key")?;
+
+    // Encode into a separate buffer first, so that a failure leaves the replica untouched.
+    let encoded = old_replica
+        .par_chunks(NODE_SIZE)
+        .zip(new_data.par_chunks(NODE_SIZE))
+        .enumerate()
+        .map(|(i, (replica_node, data_node))| {
+            let mut encoded: Fr = DefaultTreeDomain::try_from_bytes(data_node)?.into();
+            encoded.mul_assign(&rho(key, i)?);
+            encoded.add_assign(&DefaultTreeDomain::try_from_bytes(replica_node)?.into());
+
+            Ok(DefaultTreeDomain::from(encoded).into_bytes())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    old_replica.copy_from_slice(&encoded.concat());
+
+    let comm_r_last = tree_root::<Tree>(old_replica)?;
+    let comm_r = <DefaultTreeHasher as Hasher>::Function::hash2(&comm_c, &comm_r_last);
+
+    CommR::try_from(commitment_from_fr(comm_r.into()))
+}
+
+/// Recovers the data encoded with `encode_into` from `new_replica`, given the replica of the
+/// committed capacity sector it was encoded into and the same `key`.
+pub fn decode_from<Tree: MerkleTreeTrait<Hasher = DefaultTreeHasher>>(
+    new_replica: &[u8],
+    old_replica: &[u8],
+    key: Commitment,
+) -> Result<Vec<u8>> {
+    ensure_sector_len::<Tree>(new_replica.len(), old_replica.len())?;
+    let key: DefaultTreeDomain = as_safe_commitment(&key, "key")?;
+
+    let nodes = new_replica
+        .par_chunks(NODE_SIZE)
+        .zip(old_replica.par_chunks(NODE_SIZE))
+        .enumerate()
+        .map(|(i, (new_node, old_node))| {
+            let mut data: Fr = DefaultTreeDomain::try_from_bytes(new_node)?.into();
+            data.sub_assign(&DefaultTreeDomain::try_from_bytes(old_node)?.into());
+            let rho_inverse = rho(key, i)?
+                .inverse()
+                .with_context(|| format!("key stream of node {} is zero", i))?;
+            data.mul_assign(&rho_inverse);
+
+            Ok(DefaultTreeDomain::from(data).into_bytes())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(nodes.concat())
+}
+
+/// Returns the key stream element of node `node`, the hash of `key` and the node index.
+fn rho(key: DefaultTreeDomain, node: usize) -> Result<Fr> {
+    let mut index = [0u8; NODE_SIZE];
+    index[..8].copy_from_slice(&(node as u64).to_le_bytes());
+    let index = DefaultTreeDomain::try_from_bytes(&index)?;
+
+    Ok(<DefaultTreeHasher as Hasher>::Function::hash2(&key, &index).into())
+}
+
+fn ensure_sector_len<Tree: MerkleTreeTrait>(data_len: usize, replica_len: usize) -> Result<()> {
+    ensure!(
+        data_len == replica_len,
+        "data of {} bytes doesn't match the replica of {} bytes",
+        data_len,
+        replica_len
+    );
+    let nodes = replica_len / NODE_SIZE;
+    let base_tree_count = get_base_tree_count::<Tree>();
+    ensure!(
+        replica_len % NODE_SIZE == 0
+            && nodes % base_tree_count == 0
+            && (nodes / base_tree_count).is_power_of_two(),
+        "replica of {} bytes doesn't have the tree shape",
+        replica_len
+    );
+
+    Ok(())
+}
+
+/// Returns the root of the tree of shape `Tree` over the nodes of `replica`.
+fn tree_root<Tree: MerkleTreeTrait<Hasher = DefaultTreeHasher>>(
+    replica: &[u8],
+) -> Result<DefaultTreeDomain> {
+    let arity = Tree::Arity::to_usize();
+    let base_tree_len = replica.len() / get_base_tree_count::<Tree>();
+    let base_roots = replica
+        .chunks(base_tree_len)
+        .map(|base_tree| {
+            let mut row = read_nodes(base_tree)?;
+            let mut height = 0;
+            while row.len() > 1 {
+                ensure!(
+                    row.len() % arity == 0,
+                    "base tree doesn't have arity {}",
+                    arity
+                );
+                row = row
+                    .par_chunks(arity)
+                    .map(|nodes| hash_nodes(nodes, height))
+                    .collect();
+                height += 1;
+            }
+
+            Ok(row[0])
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let sub_tree_arity = Tree::SubTreeArity::to_usize();
+    if sub_tree_arity == 0 {
+        return Ok(base_roots[0]);
+    }
+    let sub_roots: Vec<_> = base_roots
+        .chunks(sub_tree_arity)
+        .map(|roots| hash_nodes(roots, 0))
+        .collect();
+    if Tree::TopTreeArity::to_usize() == 0 {
+        return Ok(sub_roots[0]);
+    }
+
+    Ok(hash_nodes(&sub_roots, 0))
+}
+
+fn hash_nodes(nodes: &[DefaultTreeDomain], height: usize) -> DefaultTreeDomain {
+    <DefaultTreeHasher as Hasher>::Function::default().multi_node(nodes, height)
+}
@@ -1,14 +1,40 @@
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
 use std::mem::size_of;
+use std::path::Path;
 
-use anyhow::{Context, Result};
+use anyhow::{ensure, Context, Result};
 use bellperson::bls::Fr;
-use filecoin_hashers::{Domain, Hasher};
+use bincode::deserialize;
+use filecoin_hashers::{Domain, HashFunction, Hasher};
 use fr32::{bytes_into_fr, fr_into_bytes};
-use merkletree::merkle::{get_merkle_tree_leafs, get_merkle_tree_len};
-use storage_proofs_core::merkle::{get_base_tree_count, MerkleTreeTrait};
-use typenum::Unsigned;
+use memmap::MmapOptions;
+use merkletree::{
+    hash::Algorithm,
+    merkle::{get_merkle_tree_leafs, get_merkle_tree_len},
+    store::StoreConfig,
+};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator, ParallelSlice};
+use storage_proofs_core::{
+    cache_key::CacheKey,
+    merkle::{
+        create_lc_tree, get_base_tree_count, split_config_and_replica, LCTree, MerkleProofTrait,
+        MerkleTreeTrait,
+    },
+    util::NODE_SIZE,
+};
+use storage_proofs_porep::stacked::{StackedBucketGraph, TemporaryAux};
+use typenum::{Unsigned, U0};
 
-use crate::types::{Commitment, SectorSize};
+use crate::{
+    api::read_cache_file,
+    constants::{
+        layers, DefaultPieceHasher, DefaultTreeDomain, DefaultTreeHasher, DRG_DEGREE, EXP_DEGREE,
+    },
+    error::Error,
+    types::{CommC, CommR, Commitment, PoRepConfig, SectorSize, Ticket},
+};
 
 pub fn as_safe_commitment<H: Domain, T: AsRef<str>>(
     comm: &[u8; 32],
@@ -19,6 +45,25 @@ pub fn as_safe_commitment<H: Domain, T: AsRef<str>>(
         .with_context(|| format!("Invalid commitment ({})", commitment_name.as_ref(),))
 }
 
+/// Checks that `ticket` and `seed` are the little-endian bytes of field elements, as the
+/// randomness drawn from the chain is once its top bits are masked off.
+///
+/// Sealing accepts any bytes, as the ticket and seed are only hashed with SHA-256, so neither
+/// `seal_pre_commit_phase1` nor `seal_commit_phase1` checks this. It lets callers which mask
+/// the randomness catch values that weren't.
+pub fn validate_seal_randomness(ticket: &Ticket, seed: &Ticket) -> Result<()> {
+    bytes_into_fr(ticket).with_context(|| {
+        format!(
+            "ticket {} is not a valid field element",
+            hex::encode(ticket)
+        )
+    })?;
+    bytes_into_fr(seed)
+        .with_context(|| format!("seed {} is not a valid field element", hex::encode(seed)))?;
+
+    Ok(())
+}
+
 pub fn commitment_from_fr(fr: Fr) -> Commitment {
     let mut commitment = [0; 32];
     for (i, b) in fr_into_bytes(&fr).iter().enumerate() {
@@ -27,6 +72,32 @@ pub fn commitment_from_fr(fr: Fr) -> Commitment {
     commitment
 }
 
+/// Returns true if `comm_r` is the hash of `comm_c` and `comm_r_last`, as computed when sealing.
+///
+/// Returns false if any of the commitments is not a valid field element.
+pub fn verify_comm_r(comm_r: Commitment, comm_c: Commitment, comm_r_last: Commitment) -> bool {
+    let comm_c = as_safe_commitment::<DefaultTreeDomain, _>(&comm_c, "comm_c");
+    let comm_r_last = as_safe_commitment::<DefaultTreeDomain, _>(&comm_r_last, "comm_r_last");
+    let (comm_c, comm_r_last) = match (comm_c, comm_r_last) {
+        (Ok(comm_c), Ok(comm_r_last)) => (comm_c, comm_r_last),
+        _ => return false,
+    };
+
+    let expected = <DefaultTreeHasher as Hasher>::Function::hash2(&comm_c, &comm_r_last);
+    commitment_from_fr(expected.into()) == comm_r
+}
+
+/// Returns comm_r, the hash of `comm_c` and `comm_r_last` as computed when sealing.
+///
+/// Fails if `comm_r_last` is not a valid field element.
+pub fn create_comm_r(comm_c: CommC, comm_r_last: Commitment) -> Result<CommR> {
+    let comm_c = as_safe_commitment::<DefaultTreeDomain, _>(comm_c.as_bytes(), "comm_c")?;
+    let comm_r_last = as_safe_commitment::<DefaultTreeDomain, _>(&comm_r_last, "comm_r_last")?;
+
+    let comm_r = <DefaultTreeHasher as Hasher>::Function::hash2(&comm_c, &comm_r_last);
+    CommR::try_from(commitment_from_fr(comm_r.into()))
+}
+
 pub fn get_base_tree_size<Tree: MerkleTreeTrait>(sector_size: SectorSize) -> Result<usize> {
     let base_tree_leaves = u64::from(sector_size) as usize
         / size_of::<<Tree::Hasher as Hasher>::Domain>()
@@ -38,3 +109,289 @@ pub fn get_base_tree_size<Tree: MerkleTreeTrait>(sector_size: SectorSize) -> Res
 pub fn get_base_tree_leafs<Tree: MerkleTreeTrait>(base_tree_size: usize) -> Result<usize> {
     get_merkle_tree_leafs(base_tree_size, Tree::Arity::to_usize())
 }
+
+/// Returns the inclusion path of the leaf at index `leaf` in `tree`.
+///
+/// Every element holds the sibling hashes at one level of the tree, from the leaves up,
+/// together with the position of the path node among them. For trees of higher arity a
+/// level has more than one sibling, so the siblings are returned as a vector.
+pub fn gen_inclusion_proof<Tree: MerkleTreeTrait>(
+    tree: &Tree,
+    leaf: usize,
+) -> Result<Vec<(Vec<<Tree::Hasher as Hasher>::Domain>, usize)>> {
+    ensure!(
+        leaf < tree.leaves(),
+        "leaf {} out of range for a tree with {} leaves",
+        leaf,
+        tree.leaves()
+    );
+    let proof = tree
+        .gen_proof(leaf)
+        .with_context(|| format!("failed to generate inclusion proof for leaf {}", leaf))?;
+
+    Ok(proof.path())
+}
+
+/// Returns true if `path`, as returned by `gen_inclusion_proof`, proves that `leaf` is
+/// the node at index `leaf_index` of the tree with the given `root`.
+pub fn verify_inclusion_proof<H: Hasher>(
+    root: H::Domain,
+    leaf: H::Domain,
+    leaf_index: usize,
+    path: &[(Vec<H::Domain>, usize)],
+) -> bool {
+    let mut hasher = H::Function::default();
+    let mut calculated_root = leaf;
+    let mut index = 0;
+    let mut level_size = 1;
+
+    for (height, (hashes, position)) in path.iter().enumerate() {
+        if *position > hashes.len() {
+            return false;
+        }
+
+        let mut nodes = hashes.clone();
+        nodes.insert(*position, calculated_root);
+        hasher.reset();
+        calculated_root = hasher.multi_node(&nodes, height);
+
+        index += position * level_size;
+        level_size *= nodes.len();
+    }
+
+    calculated_root == root && index == leaf_index
+}
+
+/// Returns the parents the label of `node` in `layer` is derived from, for sectors sealed with
+/// `porep_config`: the `DRG_DEGREE` base parents in the same layer, followed by the
+/// `EXP_DEGREE` expander parents in the previous layer. The first layer has no expander
+/// parents.
+///
+/// Layers are numbered from 1, like the layer labels in the cache directory.
+pub fn parents_for_node(
+    porep_config: &PoRepConfig,
+    layer: usize,
+    node: usize,
+) -> Result<Vec<usize>> {
+    let sector_size = u64::from(porep_config.sector_size);
+    let layers = layers(sector_size)?;
+    ensure!(
+        layer >= 1 && layer <= layers,
+        "layer {} out of range, sector has {} layers",
+        layer,
+        layers
+    );
+    let nodes = sector_size as usize / NODE_SIZE;
+    ensure!(
+        node < nodes,
+        "node {} out of range, sector has {} nodes",
+        node,
+        nodes
+    );
+
+    let graph = StackedBucketGraph::<DefaultTreeHasher>::new_stacked(
+        nodes,
+        DRG_DEGREE,
+        EXP_DEGREE,
+        porep_config.porep_id,
+        porep_config.api_version,
+    )?;
+
+    let mut parents = vec![0u32; DRG_DEGREE];
+    graph.base_parents(node, &mut parents)?;
+    if layer > 1 {
+        let mut expanded_parents = vec![0u32; EXP_DEGREE];
+        graph.expanded_parents(node, &mut expanded_parents)?;
+        parents.extend(expanded_parents);
+    }
+
+    Ok(parents.into_iter().map(|parent| parent as usize).collect())
+}
+
+/// Reads the leaves of the base tree stored in `tree_file`, for a sector of `sector_size` bytes
+/// with the tree shape `Tree`, in the order they are hashed.
+///
+/// Only tree files which store every row start with the leaves. Level cache trees, like
+/// tree_r_last, don't store their lowest rows, so their leaves are read from the replica with
+/// `extract_lc_leaves` instead.
+pub fn extract_leaves<Tree: MerkleTreeTrait<Hasher = DefaultTreeHasher>>(
+    tree_file: &Path,
+    sector_size: u64,
+) -> Result<Vec<DefaultTreeDomain>> {
+    let base_tree_leafs = base_tree_leafs::<Tree>(sector_size);
+    let tree = fs::read(tree_file).with_context(|| format!("could not read {:?}", tree_file))?;
+    let stored_leafs = stored_leafs::<Tree>(tree_file, &tree, base_tree_leafs)?;
+    ensure!(
+        stored_leafs == base_tree_leafs,
+        "tree file {:?} is a level cache tree without its leaves, use extract_lc_leaves",
+        tree_file
+    );
+
+    read_nodes(&tree[..base_tree_leafs * NODE_SIZE])
+}
+
+/// Reads the leaves of the level cache tree stored in `tree_file` from `replica`. The tree is
+/// base tree `base_tree` of a sector of `sector_size` bytes with the tree shape `Tree`.
+///
+/// The rows discarded from the tree file are recomputed from the leaves and checked against
+/// the lowest row it stores, so a replica which doesn't match the tree is rejected.
+pub fn extract_lc_leaves<Tree: MerkleTreeTrait<Hasher = DefaultTreeHasher>>(
+    tree_file: &Path,
+    replica: &Path,
+    base_tree: usize,
+    sector_size: u64,
+) -> Result<Vec<DefaultTreeDomain>> {
+    let base_tree_count = get_base_tree_count::<Tree>();
+    ensure!(
+        base_tree < base_tree_count,
+        "base tree {} out of range, sector has {} base trees",
+        base_tree,
+        base_tree_count
+    );
+    let base_tree_leafs = base_tree_leafs::<Tree>(sector_size);
+    let tree = fs::read(tree_file).with_context(|| format!("could not read {:?}", tree_file))?;
+    let stored_leafs = stored_leafs::<Tree>(tree_file, &tree, base_tree_leafs)?;
+
+    let mut data = vec![0u8; base_tree_leafs * NODE_SIZE];
+    let mut file =
+        File::open(replica).with_context(|| format!("could not open replica {:?}", replica))?;
+    file.seek(SeekFrom::Start((base_tree * data.len()) as u64))?;
+    file.read_exact(&mut data).with_context(|| {
+        format!(
+            "replica {:?} is too short for base tree {}",
+            replica, base_tree
+        )
+    })?;
+    let leaves = read_nodes(&data)?;
+
+    let arity = Tree::Arity::to_usize();
+    let mut row = leaves.clone();
+    let mut height = 0;
+    while row.len() > stored_leafs {
+        row = row
+            .chunks(arity)
+            .map(|nodes| {
+                <DefaultTreeHasher as Hasher>::Function::default().multi_node(nodes, height)
+            })
+            .collect();
+        height += 1;
+    }
+    ensure!(
+        row == read_nodes(&tree[..stored_leafs * NODE_SIZE])?,
+        "replica {:?} doesn't match tree file {:?}",
+        replica,
+        tree_file
+    );
+
+    Ok(leaves)
+}
+
+/// Rebuilds tree_r_last of the replica at `replica`, a sector of `sector_size` bytes with the
+/// tree shape `Tree`, and returns comm_r_last.
+///
+/// tree_r_last only depends on the replica, so a lost tree can be recovered without sealing
+/// the sector again. Its base trees are written to `cache_dir`, replacing any existing ones,
+/// discarding as many rows as the `tree_r_last_config` recorded in the sector's t_aux, so the
+/// rebuilt tree matches the one `seal_commit_phase1` expects to read.
+pub fn rebuild_tree_r_last<Tree: MerkleTreeTrait>(
+    replica: &Path,
+    cache_dir: &Path,
+    sector_size: u64,
+) -> Result<Commitment> {
+    let base_tree_count = get_base_tree_count::<Tree>();
+    let base_tree_leafs = base_tree_leafs::<Tree>(sector_size);
+    let base_tree_len = get_merkle_tree_len(base_tree_leafs, Tree::Arity::to_usize())?;
+
+    let file =
+        File::open(replica).with_context(|| format!("could not open replica {:?}", replica))?;
+    let data = unsafe { MmapOptions::new().map(&file) }
+        .with_context(|| format!("could not mmap replica {:?}", replica))?;
+    ensure!(
+        data.len() as u64 == sector_size,
+        Error::ReplicaSizeMismatch {
+            expected: sector_size,
+            actual: data.len() as u64,
+        }
+    );
+    ensure!(
+        data.par_chunks(NODE_SIZE)
+            .all(|node| bytes_into_fr(node).is_ok()),
+        "replica {:?} holds nodes which are not field elements",
+        replica
+    );
+
+    let t_aux: TemporaryAux<Tree, DefaultPieceHasher> =
+        deserialize(&read_cache_file(cache_dir, CacheKey::TAux)?)?;
+
+    let mut config = StoreConfig::new(
+        cache_dir,
+        CacheKey::CommRLastTree.to_string(),
+        t_aux.tree_r_last_config.rows_to_discard,
+    );
+    config.size = Some(base_tree_len);
+    let (configs, replica_config) = split_config_and_replica(
+        config,
+        replica.to_path_buf(),
+        base_tree_leafs,
+        base_tree_count,
+    )?;
+
+    for (config, leaves) in configs.iter().zip(data.chunks(base_tree_leafs * NODE_SIZE)) {
+        let path = StoreConfig::data_path(&config.path, &config.id);
+        if path.exists() {
+            fs::remove_file(&path).with_context(|| format!("could not remove {:?}", path))?;
+        }
+
+        let leaves = leaves
+            .par_chunks(NODE_SIZE)
+            .map(<Tree::Hasher as Hasher>::Domain::try_from_bytes)
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("could not read the leaves of {:?}", path))?;
+        LCTree::<Tree::Hasher, Tree::Arity, U0, U0>::from_par_iter_with_config(
+            leaves.into_par_iter(),
+            config.clone(),
+        )
+        .with_context(|| format!("could not build tree_r_last {:?}", path))?;
+    }
+
+    let tree_r_last = create_lc_tree::<
+        LCTree<Tree::Hasher, Tree::Arity, Tree::SubTreeArity, Tree::TopTreeArity>,
+    >(base_tree_len, &configs, &replica_config)?;
+
+    Ok(commitment_from_fr(tree_r_last.root().into()))
+}
+
+fn base_tree_leafs<Tree: MerkleTreeTrait>(sector_size: u64) -> usize {
+    sector_size as usize / NODE_SIZE / get_base_tree_count::<Tree>()
+}
+
+/// Returns the number of nodes in the lowest row stored in the tree file `tree`, which is
+/// smaller than `base_tree_leafs` for level cache trees.
+fn stored_leafs<Tree: MerkleTreeTrait>(
+    tree_file: &Path,
+    tree: &[u8],
+    base_tree_leafs: usize,
+) -> Result<usize> {
+    ensure!(
+        tree.len() % NODE_SIZE == 0,
+        "tree file {:?} has {} bytes, not a whole number of nodes",
+        tree_file,
+        tree.len()
+    );
+    let stored_leafs = get_merkle_tree_leafs(tree.len() / NODE_SIZE, Tree::Arity::to_usize())
+        .with_context(|| format!("{:?} is not a tree file", tree_file))?;
+    ensure!(
+        stored_leafs <= base_tree_leafs && base_tree_leafs % stored_leafs == 0,
+        "tree file {:?} doesn't belong to a sector with {} leaves per base tree",
+        tree_file,
+        base_tree_leafs
+    );
+
+    Ok(stored_leafs)
+}
+
+pub(crate) fn read_nodes(data: &[u8]) -> Result<Vec<DefaultTreeDomain>> {
+    data.chunks(NODE_SIZE)
+        .map(DefaultTreeDomain::try_from_bytes)
+        .collect()
+}
@@ -1,12 +1,22 @@
 use std::collections::BTreeMap;
 
 use anyhow::{ensure, Context, Result};
+use bellperson::{
+    bls::{Bls12, Fr},
+    groth16::{verify_proofs_batch, PreparedVerifyingKey},
+};
 use filecoin_hashers::Hasher;
-use log::info;
+use log::{info, warn};
+use rand::{rngs::OsRng, RngCore};
+use rayon::ThreadPoolBuilder;
+use serde::{Deserialize, Serialize};
 use storage_proofs_core::{
     compound_proof::{self, CompoundProof},
+    enter_span,
+    error::Error as CoreError,
     merkle::MerkleTreeTrait,
     multi_proof::MultiProof,
+    proof::ProofScheme,
     sector::SectorId,
 };
 use storage_proofs_post::fallback::{
@@ -16,10 +26,11 @@ use storage_proofs_post::fallback::{
 use crate::{
     api::{as_safe_commitment, get_partitions_for_window_post, partition_vanilla_proofs},
     caches::{get_post_params, get_post_verifying_key},
+    error::Error,
     parameters::window_post_setup_params,
     types::{
-        ChallengeSeed, FallbackPoStSectorProof, PoStConfig, PrivateReplicaInfo, ProverId,
-        PublicReplicaInfo, SnarkProof,
+        ChallengeSeed, CommR, Commitment, FallbackPoStSectorProof, PoStConfig, PrivateReplicaInfo,
+        ProverId, PublicReplicaInfo, SnarkProof,
     },
     PoStType,
 };
@@ -32,6 +43,10 @@ pub fn generate_window_post_with_vanilla<Tree: 'static + MerkleTreeTrait>(
     vanilla_proofs: Vec<FallbackPoStSectorProof<Tree>>,
 ) -> Result<SnarkProof> {
     info!("generate_window_post_with_vanilla:start");
+    let _span = enter_span!(
+        "generate_window_post_with_vanilla",
+        sector_size = u64::from(post_config.sector_size)
+    );
     ensure!(
         post_config.typ == PoStType::Window,
         "invalid post config type"
@@ -100,41 +115,75 @@ pub fn generate_window_post<Tree: 'static + MerkleTreeTrait>(
     prover_id: ProverId,
 ) -> Result<SnarkProof> {
     info!("generate_window_post:start");
-    ensure!(
-        post_config.typ == PoStType::Window,
-        "invalid post config type"
-    );
 
-    let randomness_safe = as_safe_commitment(randomness, "randomness")?;
-    let prover_id_safe = as_safe_commitment(&prover_id, "prover_id")?;
+    let proof =
+        generate_window_post_inner(post_config, randomness, replicas, prover_id, &mut OsRng)?;
 
-    let vanilla_params = window_post_setup_params(&post_config);
-    let partitions = get_partitions_for_window_post(replicas.len(), &post_config);
+    info!("generate_window_post:finish");
 
-    let sector_count = vanilla_params.sector_count;
-    let setup_params = compound_proof::SetupParams {
-        vanilla_params,
-        partitions,
-        priority: post_config.priority,
-    };
+    Ok(proof)
+}
 
-    let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
-        FallbackPoStCompound::setup(&setup_params)?;
-    let groth_params = get_post_params::<Tree>(&post_config)?;
+/// Like `generate_window_post`, but runs the rayon work of proving, such as generating the
+/// vanilla proofs and synthesizing the partitions, in a pool of `thread_budget` threads.
+///
+/// Only the rayon work is scoped to the pool. bellperson runs its multiexp and FFT workers in
+/// its own pool, which the budget doesn't cap.
+pub fn generate_window_post_with_thread_budget<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo<Tree>>,
+    prover_id: ProverId,
+    thread_budget: usize,
+) -> Result<SnarkProof> {
+    info!("generate_window_post_with_thread_budget:start");
+    let proof = install_with_thread_budget(thread_budget, || {
+        generate_window_post_inner(post_config, randomness, replicas, prover_id, &mut OsRng)
+    })??;
+
+    info!("generate_window_post_with_thread_budget:finish");
+
+    Ok(proof)
+}
+
+/// Runs `op` in a new rayon pool of `thread_budget` threads.
+fn install_with_thread_budget<T: Send>(
+    thread_budget: usize,
+    op: impl FnOnce() -> T + Send,
+) -> Result<T> {
+    ensure!(thread_budget > 0, "thread budget must be at least 1");
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(thread_budget)
+        .build()
+        .context("failed to build thread pool")?;
+
+    Ok(pool.install(op))
+}
+
+pub(crate) fn generate_window_post_inner<Tree: 'static + MerkleTreeTrait, R: RngCore>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo<Tree>>,
+    prover_id: ProverId,
+    rng: &mut R,
+) -> Result<SnarkProof> {
+    ensure!(
+        post_config.typ == PoStType::Window,
+        "invalid post config type"
+    );
 
     let trees: Vec<_> = replicas
         .iter()
         .map(|(sector_id, replica)| {
             replica
-                .merkle_tree(post_config.sector_size)
-                .with_context(|| {
-                    format!("generate_window_post: merkle_tree failed: {:?}", sector_id)
-                })
+                .mmap_tree(post_config.sector_size)
+                .with_context(|| format!("generate_window_post: mmap_tree failed: {:?}", sector_id))
         })
         .collect::<Result<_>>()?;
 
-    let mut pub_sectors = Vec::with_capacity(sector_count);
-    let mut priv_sectors = Vec::with_capacity(sector_count);
+    let mut pub_sectors = Vec::with_capacity(replicas.len());
+    let mut priv_sectors = Vec::with_capacity(replicas.len());
 
     for ((sector_id, replica), tree) in replicas.iter().zip(trees.iter()) {
         let comm_r = replica.safe_comm_r().with_context(|| {
@@ -154,6 +203,126 @@ pub fn generate_window_post<Tree: 'static + MerkleTreeTrait>(
         });
     }
 
+    prove_window_post(
+        post_config,
+        randomness,
+        prover_id,
+        pub_sectors,
+        &priv_sectors,
+        rng,
+    )
+}
+
+/// Generates a Window proof-of-spacetime over all sectors in `replicas` which can be proven.
+///
+/// Sectors whose replica or merkle tree is missing or corrupt are skipped instead of failing
+/// the whole proof. Returns the proof together with the ids of the skipped sectors, the proof
+/// verifies against the remaining sectors only.
+pub fn generate_window_post_with_faults<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo<Tree>>,
+    prover_id: ProverId,
+) -> Result<(SnarkProof, Vec<SectorId>)> {
+    info!("generate_window_post_with_faults:start");
+    ensure!(
+        post_config.typ == PoStType::Window,
+        "invalid post config type"
+    );
+
+    let mut faults = Vec::new();
+    let mut sectors = Vec::with_capacity(replicas.len());
+    for (sector_id, replica) in replicas {
+        match replica.mmap_tree(post_config.sector_size) {
+            Ok(tree) => sectors.push((*sector_id, replica, tree)),
+            Err(err) => {
+                warn!("faulty sector: {:?} ({:?})", sector_id, err);
+                faults.push(*sector_id);
+            }
+        }
+    }
+
+    loop {
+        ensure!(!sectors.is_empty(), CoreError::FaultySectors(faults));
+
+        let mut pub_sectors = Vec::with_capacity(sectors.len());
+        let mut priv_sectors = Vec::with_capacity(sectors.len());
+
+        for (sector_id, replica, tree) in &sectors {
+            let comm_r = replica.safe_comm_r().with_context(|| {
+                format!(
+                    "generate_window_post_with_faults: safe_comm_r failed: {:?}",
+                    sector_id
+                )
+            })?;
+
+            pub_sectors.push(PublicSector {
+                id: *sector_id,
+                comm_r,
+            });
+            priv_sectors.push(PrivateSector {
+                tree,
+                comm_c: replica.safe_comm_c(),
+                comm_r_last: replica.safe_comm_r_last(),
+            });
+        }
+
+        // Sectors with a readable tree may still fail to produce valid inclusion proofs,
+        // those are dropped and the proof is retried over the rest.
+        let new_faults = match prove_window_post(
+            post_config,
+            randomness,
+            prover_id,
+            pub_sectors,
+            &priv_sectors,
+            &mut OsRng,
+        ) {
+            Ok(proof) => {
+                faults.sort();
+                info!("generate_window_post_with_faults:finish");
+
+                return Ok((proof, faults));
+            }
+            Err(err) => match err.downcast_ref::<CoreError>() {
+                Some(CoreError::FaultySectors(new_faults)) => new_faults.clone(),
+                _ => return Err(err),
+            },
+        };
+
+        sectors.retain(|(sector_id, _, _)| !new_faults.contains(sector_id));
+        faults.extend(new_faults);
+    }
+}
+
+fn prove_window_post<Tree: 'static + MerkleTreeTrait, R: RngCore>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    prover_id: ProverId,
+    pub_sectors: Vec<PublicSector<<Tree::Hasher as Hasher>::Domain>>,
+    priv_sectors: &[PrivateSector<'_, Tree>],
+    rng: &mut R,
+) -> Result<SnarkProof> {
+    let _span = enter_span!(
+        "generate_window_post",
+        sector_size = u64::from(post_config.sector_size),
+        sector_count = pub_sectors.len()
+    );
+    let randomness_safe = as_safe_commitment(randomness, "randomness")?;
+    let prover_id_safe = as_safe_commitment(&prover_id, "prover_id")?;
+
+    let vanilla_params = window_post_setup_params(&post_config);
+    let partitions = get_partitions_for_window_post(pub_sectors.len(), &post_config);
+
+    let setup_params = compound_proof::SetupParams {
+        vanilla_params,
+        partitions,
+        priority: post_config.priority,
+    };
+
+    let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
+        FallbackPoStCompound::setup(&setup_params)?;
+    let groth_params = get_post_params::<Tree>(&post_config)?;
+
     let pub_inputs = fallback::PublicInputs {
         randomness: randomness_safe,
         prover_id: prover_id_safe,
@@ -162,12 +331,16 @@ pub fn generate_window_post<Tree: 'static + MerkleTreeTrait>(
     };
 
     let priv_inputs = fallback::PrivateInputs::<Tree> {
-        sectors: &priv_sectors,
+        sectors: priv_sectors,
     };
 
-    let proof = FallbackPoStCompound::prove(&pub_params, &pub_inputs, &priv_inputs, &groth_params)?;
-
-    info!("generate_window_post:finish");
+    let proof = FallbackPoStCompound::prove_with_rng(
+        &pub_params,
+        &pub_inputs,
+        &priv_inputs,
+        &groth_params,
+        rng,
+    )?;
 
     proof.to_vec()
 }
@@ -182,16 +355,127 @@ pub fn verify_window_post<Tree: 'static + MerkleTreeTrait>(
 ) -> Result<bool> {
     info!("verify_window_post:start");
 
+    let verifying_key = get_post_verifying_key::<Tree>(&post_config)?;
+    let result = verify_window_post_with_vk::<Tree>(
+        post_config,
+        &verifying_key,
+        randomness,
+        replicas,
+        prover_id,
+        proof,
+    );
+
+    info!("verify_window_post:finish");
+    result
+}
+
+/// Verifies a window proof-of-spacetime with an already loaded verifying key. Unlike
+/// `verify_window_post`, this never looks up or generates any parameters.
+pub fn verify_window_post_with_vk<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    verifying_key: &PreparedVerifyingKey<Bls12>,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PublicReplicaInfo>,
+    prover_id: ProverId,
+    proof: &[u8],
+) -> Result<bool> {
+    info!("verify_window_post_with_vk:start");
+
     ensure!(
         post_config.typ == PoStType::Window,
         "invalid post config type"
     );
 
-    let randomness_safe = as_safe_commitment(randomness, "randomness")?;
-    let prover_id_safe = as_safe_commitment(&prover_id, "prover_id")?;
+    let pub_inputs = window_post_vanilla_public_inputs::<Tree>(randomness, replicas, prover_id)?;
+    let is_valid =
+        verify_window_post_inputs::<Tree>(post_config, verifying_key, &pub_inputs, proof)?;
+
+    info!("verify_window_post_with_vk:finish");
+
+    Ok(is_valid)
+}
+
+/// Verifies a window proof-of-spacetime over the sectors `sector_ids`, asking `resolver` for
+/// the comm_r of each of them instead of taking a map of all replicas.
+///
+/// The result is the same as that of `verify_window_post` with the replicas `resolver`
+/// returns. Fails if a sector id is repeated, or if `resolver` fails for any of them.
+pub fn verify_window_post_with_resolver<Tree, F>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    sector_ids: &[SectorId],
+    prover_id: ProverId,
+    proof: &[u8],
+    resolver: F,
+) -> Result<bool>
+where
+    Tree: 'static + MerkleTreeTrait,
+    F: Fn(SectorId) -> Result<CommR>,
+{
+    info!("verify_window_post_with_resolver:start");
+
+    ensure!(
+        post_config.typ == PoStType::Window,
+        "invalid post config type"
+    );
 
+    // The sectors are proven in the order of their ids, as the replicas of a `BTreeMap` are.
+    let mut sector_ids = sector_ids.to_vec();
+    sector_ids.sort_unstable();
+    let sector_count = sector_ids.len();
+    sector_ids.dedup();
+    ensure!(
+        sector_ids.len() == sector_count,
+        "verify_window_post_with_resolver: duplicate sector ids"
+    );
+
+    let pub_sectors = sector_ids
+        .into_iter()
+        .map(|sector_id| {
+            let comm_r = resolver(sector_id).with_context(|| {
+                format!(
+                    "verify_window_post_with_resolver: resolver failed: {:?}",
+                    sector_id
+                )
+            })?;
+            let comm_r = PublicReplicaInfo::new(comm_r.into())?
+                .safe_comm_r()
+                .with_context(|| {
+                    format!(
+                        "verify_window_post_with_resolver: safe_comm_r failed: {:?}",
+                        sector_id
+                    )
+                })?;
+            Ok(PublicSector {
+                id: sector_id,
+                comm_r,
+            })
+        })
+        .collect::<Result<_>>()?;
+    let pub_inputs = fallback::PublicInputs {
+        randomness: as_safe_commitment(randomness, "randomness")?,
+        prover_id: as_safe_commitment(&prover_id, "prover_id")?,
+        sectors: pub_sectors,
+        k: None,
+    };
+
+    let verifying_key = get_post_verifying_key::<Tree>(&post_config)?;
+    let is_valid =
+        verify_window_post_inputs::<Tree>(post_config, &verifying_key, &pub_inputs, proof)?;
+
+    info!("verify_window_post_with_resolver:finish");
+
+    Ok(is_valid)
+}
+
+fn verify_window_post_inputs<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    verifying_key: &PreparedVerifyingKey<Bls12>,
+    pub_inputs: &fallback::PublicInputs<<Tree::Hasher as Hasher>::Domain>,
+    proof: &[u8],
+) -> Result<bool> {
     let vanilla_params = window_post_setup_params(&post_config);
-    let partitions = get_partitions_for_window_post(replicas.len(), &post_config);
+    let partitions = get_partitions_for_window_post(pub_inputs.sectors.len(), &post_config);
 
     let setup_params = compound_proof::SetupParams {
         vanilla_params,
@@ -201,6 +485,182 @@ pub fn verify_window_post<Tree: 'static + MerkleTreeTrait>(
     let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
         FallbackPoStCompound::setup(&setup_params)?;
 
+    let is_valid = {
+        let multi_proof = MultiProof::new_from_reader(partitions, proof, verifying_key)
+            .context(Error::InvalidProof)?;
+
+        FallbackPoStCompound::verify(
+            &pub_params,
+            pub_inputs,
+            &multi_proof,
+            &fallback::ChallengeRequirements {
+                minimum_challenge_count: post_config.challenge_count * post_config.sector_count,
+            },
+        )?
+    };
+
+    Ok(is_valid)
+}
+
+/// Window proofs-of-spacetime bundled up to be verified together, see
+/// `verify_window_post_bundle`.
+///
+/// Nothing is aggregated: the bundle holds every proof as is and grows with the number of
+/// proofs. Only the verification is batched.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WindowPoStProofBundle {
+    pub proofs: Vec<SnarkProof>,
+}
+
+/// The public inputs of a single window proof-of-spacetime in a `WindowPoStProofBundle`.
+#[derive(Clone, Debug)]
+pub struct WindowPoStPublicInputs<'a> {
+    pub randomness: ChallengeSeed,
+    pub replicas: &'a BTreeMap<SectorId, PublicReplicaInfo>,
+    pub prover_id: ProverId,
+}
+
+/// Bundles window proofs-of-spacetime, so that they can be verified in a single batch.
+pub fn bundle_window_post_proofs(proofs: &[SnarkProof]) -> Result<WindowPoStProofBundle> {
+    ensure!(!proofs.is_empty(), "cannot bundle zero proofs");
+
+    Ok(WindowPoStProofBundle {
+        proofs: proofs.to_vec(),
+    })
+}
+
+/// Verifies all window proofs-of-spacetime in `bundle` with a single Groth16 batch
+/// verification. `public_inputs` must be in the same order as the bundled proofs.
+///
+/// Returns `false` if any of the proofs is invalid.
+pub fn verify_window_post_bundle<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    bundle: &WindowPoStProofBundle,
+    public_inputs: &[WindowPoStPublicInputs<'_>],
+) -> Result<bool> {
+    info!("verify_window_post_bundle:start");
+
+    ensure!(
+        post_config.typ == PoStType::Window,
+        "invalid post config type"
+    );
+    ensure!(!bundle.proofs.is_empty(), "cannot verify an empty bundle");
+    ensure!(
+        bundle.proofs.len() == public_inputs.len(),
+        "number of proofs and public inputs don't match"
+    );
+
+    let verifying_key = get_post_verifying_key::<Tree>(&post_config)?;
+    let requirements = fallback::ChallengeRequirements {
+        minimum_challenge_count: post_config.challenge_count * post_config.sector_count,
+    };
+
+    let mut circuit_proofs = Vec::new();
+    let mut circuit_inputs = Vec::new();
+    for (proof, inputs) in bundle.proofs.iter().zip(public_inputs) {
+        let partitions = get_partitions_for_window_post(inputs.replicas.len(), &post_config);
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: window_post_setup_params(&post_config),
+            partitions,
+            priority: false,
+        };
+        let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
+            FallbackPoStCompound::setup(&setup_params)?;
+        let pub_inputs = window_post_vanilla_public_inputs::<Tree>(
+            &inputs.randomness,
+            inputs.replicas,
+            inputs.prover_id,
+        )?;
+
+        let multi_proof = MultiProof::new_from_reader(partitions, proof, &verifying_key)
+            .context(Error::InvalidProof)?;
+        if !FallbackPoSt::<Tree>::satisfies_requirements(
+            &pub_params.vanilla_params,
+            &requirements,
+            multi_proof.len(),
+        ) {
+            return Ok(false);
+        }
+
+        for k in 0..multi_proof.len() {
+            circuit_inputs.push(FallbackPoStCompound::<Tree>::generate_public_inputs(
+                &pub_inputs,
+                &pub_params.vanilla_params,
+                Some(k),
+            )?);
+        }
+        circuit_proofs.extend(multi_proof.circuit_proofs);
+    }
+
+    let circuit_proofs: Vec<_> = circuit_proofs.iter().collect();
+    let is_valid =
+        verify_proofs_batch(&verifying_key, &mut OsRng, &circuit_proofs, &circuit_inputs)?;
+
+    info!("verify_window_post_bundle:finish");
+
+    Ok(is_valid)
+}
+
+/// Returns the circuit public inputs of a window proof-of-spacetime over the sectors
+/// `sector_ids` with the replica commitments `comm_rs`, one set per partition.
+///
+/// These are the inputs `verify_window_post` checks the proof against, so they can be
+/// transmitted along with the proof and compared to the ones derived by the verifier.
+pub fn window_post_public_inputs<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    sector_ids: &[SectorId],
+    comm_rs: &[Commitment],
+    prover_id: ProverId,
+) -> Result<Vec<Vec<Fr>>> {
+    ensure!(
+        post_config.typ == PoStType::Window,
+        "invalid post config type"
+    );
+    ensure!(
+        sector_ids.len() == comm_rs.len(),
+        "number of sector ids and comm_rs don't match"
+    );
+
+    let mut replicas = BTreeMap::new();
+    for (sector_id, comm_r) in sector_ids.iter().zip(comm_rs) {
+        let replica = PublicReplicaInfo::new(*comm_r)?;
+        ensure!(
+            replicas.insert(*sector_id, replica).is_none(),
+            "duplicate sector id {:?}",
+            sector_id
+        );
+    }
+
+    let partitions = get_partitions_for_window_post(replicas.len(), &post_config);
+    let setup_params = compound_proof::SetupParams {
+        vanilla_params: window_post_setup_params(&post_config),
+        partitions,
+        priority: false,
+    };
+    let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
+        FallbackPoStCompound::setup(&setup_params)?;
+    let pub_inputs = window_post_vanilla_public_inputs::<Tree>(randomness, &replicas, prover_id)?;
+
+    (0..FallbackPoStCompound::<Tree>::partition_count(&pub_params))
+        .map(|k| {
+            FallbackPoStCompound::<Tree>::generate_public_inputs(
+                &pub_inputs,
+                &pub_params.vanilla_params,
+                Some(k),
+            )
+        })
+        .collect()
+}
+
+fn window_post_vanilla_public_inputs<Tree: 'static + MerkleTreeTrait>(
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PublicReplicaInfo>,
+    prover_id: ProverId,
+) -> Result<fallback::PublicInputs<<Tree::Hasher as Hasher>::Domain>> {
+    let randomness_safe = as_safe_commitment(randomness, "randomness")?;
+    let prover_id_safe = as_safe_commitment(&prover_id, "prover_id")?;
+
     let pub_sectors: Vec<_> = replicas
         .iter()
         .map(|(sector_id, replica)| {
@@ -214,31 +674,33 @@ pub fn verify_window_post<Tree: 'static + MerkleTreeTrait>(
         })
         .collect::<Result<_>>()?;
 
-    let pub_inputs = fallback::PublicInputs {
+    Ok(fallback::PublicInputs {
         randomness: randomness_safe,
         prover_id: prover_id_safe,
         sectors: pub_sectors,
         k: None,
-    };
+    })
+}
 
-    let is_valid = {
-        let verifying_key = get_post_verifying_key::<Tree>(&post_config)?;
-        let multi_proof = MultiProof::new_from_reader(partitions, proof, &verifying_key)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        FallbackPoStCompound::verify(
-            &pub_params,
-            &pub_inputs,
-            &multi_proof,
-            &fallback::ChallengeRequirements {
-                minimum_challenge_count: post_config.challenge_count * post_config.sector_count,
-            },
-        )?
-    };
-    if !is_valid {
-        return Ok(false);
-    }
+    use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 
-    info!("verify_window_post:finish");
+    #[test]
+    fn test_install_with_thread_budget() -> Result<()> {
+        for thread_budget in &[1, 3] {
+            let thread_counts: Vec<usize> = install_with_thread_budget(*thread_budget, || {
+                (0..64)
+                    .into_par_iter()
+                    .map(|_| rayon::current_num_threads())
+                    .collect()
+            })?;
+            assert!(thread_counts.iter().all(|count| count == thread_budget));
+        }
+        assert!(install_with_thread_budget(0, || ()).is_err());
 
-    Ok(true)
+        Ok(())
+    }
 }
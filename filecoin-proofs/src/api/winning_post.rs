@@ -1,8 +1,13 @@
 use anyhow::{ensure, Context, Result};
+use bellperson::{
+    bls::{Bls12, Fr},
+    groth16::PreparedVerifyingKey,
+};
 use filecoin_hashers::Hasher;
 use log::info;
 use storage_proofs_core::{
     compound_proof::{self, CompoundProof},
+    enter_span,
     merkle::MerkleTreeTrait,
     multi_proof::MultiProof,
     sector::SectorId,
@@ -15,6 +20,8 @@ use storage_proofs_post::fallback::{
 use crate::{
     api::{as_safe_commitment, partition_vanilla_proofs},
     caches::{get_post_params, get_post_verifying_key},
+    constants::{DefaultTreeDomain, WINNING_POST_SECTOR_COUNT},
+    error::Error,
     parameters::winning_post_setup_params,
     types::{
         ChallengeSeed, Commitment, FallbackPoStSectorProof, PoStConfig, PrivateReplicaInfo,
@@ -31,6 +38,10 @@ pub fn generate_winning_post_with_vanilla<Tree: 'static + MerkleTreeTrait>(
     vanilla_proofs: Vec<FallbackPoStSectorProof<Tree>>,
 ) -> Result<SnarkProof> {
     info!("generate_winning_post_with_vanilla:start");
+    let _span = enter_span!(
+        "generate_winning_post_with_vanilla",
+        sector_size = u64::from(post_config.sector_size)
+    );
     ensure!(
         post_config.typ == PoStType::Winning,
         "invalid post config type"
@@ -102,6 +113,10 @@ pub fn generate_winning_post<Tree: 'static + MerkleTreeTrait>(
     prover_id: ProverId,
 ) -> Result<SnarkProof> {
     info!("generate_winning_post:start");
+    let _span = enter_span!(
+        "generate_winning_post",
+        sector_size = u64::from(post_config.sector_size)
+    );
     ensure!(
         post_config.typ == PoStType::Winning,
         "invalid post config type"
@@ -132,11 +147,9 @@ pub fn generate_winning_post<Tree: 'static + MerkleTreeTrait>(
     let trees = replicas
         .iter()
         .map(|(sector_id, replica)| {
-            replica
-                .merkle_tree(post_config.sector_size)
-                .with_context(|| {
-                    format!("generate_winning_post: merkle_tree failed: {:?}", sector_id)
-                })
+            replica.mmap_tree(post_config.sector_size).with_context(|| {
+                format!("generate_winning_post: mmap_tree failed: {:?}", sector_id)
+            })
         })
         .collect::<Result<Vec<_>>>()?;
 
@@ -183,6 +196,25 @@ pub fn generate_winning_post<Tree: 'static + MerkleTreeTrait>(
     Ok(proof)
 }
 
+/// Generates a Winning proof-of-spacetime without blocking the async runtime.
+///
+/// The proof is generated by `generate_winning_post` on tokio's blocking thread pool. Dropping
+/// the returned future stops waiting for the proof, the proving itself runs to completion in the
+/// background and its result is discarded.
+#[cfg(feature = "async")]
+pub async fn generate_winning_post_async<Tree: 'static + MerkleTreeTrait>(
+    post_config: PoStConfig,
+    randomness: ChallengeSeed,
+    replicas: Vec<(SectorId, PrivateReplicaInfo<Tree>)>,
+    prover_id: ProverId,
+) -> Result<SnarkProof> {
+    tokio::task::spawn_blocking(move || {
+        generate_winning_post::<Tree>(&post_config, &randomness, &replicas, prover_id)
+    })
+    .await
+    .context("winning post task failed")?
+}
+
 /// Given some randomness and the length of available sectors, generates the challenged sector.
 ///
 /// The returned values are indices in the range of `0..sector_set_size`, requiring the caller
@@ -217,6 +249,31 @@ pub fn generate_winning_post_sector_challenge<Tree: MerkleTreeTrait>(
     result
 }
 
+/// Returns the sector of `sector_ids` challenged by `randomness` for a Winning PoSt of
+/// `prover_id`, which is the single replica to pass to `generate_winning_post`.
+///
+/// The challenge is the same as `generate_winning_post_sector_challenge` with
+/// `WINNING_POST_SECTOR_COUNT` sectors, resolved to the sector id at the challenged index.
+/// `sector_ids` must be ordered as the sector set the challenge is verified against.
+pub fn select_winning_sector(
+    randomness: &ChallengeSeed,
+    prover_id: ProverId,
+    sector_ids: &[SectorId],
+) -> Result<SectorId> {
+    ensure!(!sector_ids.is_empty(), "empty sector set is invalid");
+
+    let prover_id_safe: DefaultTreeDomain = as_safe_commitment(&prover_id, "prover_id")?;
+    let randomness_safe: DefaultTreeDomain = as_safe_commitment(randomness, "randomness")?;
+    let challenges = generate_sector_challenges(
+        randomness_safe,
+        WINNING_POST_SECTOR_COUNT,
+        sector_ids.len() as u64,
+        prover_id_safe,
+    )?;
+
+    Ok(sector_ids[challenges[0] as usize])
+}
+
 /// Verifies a winning proof-of-spacetime.
 ///
 /// The provided `replicas` must be the same ones as passed to `generate_winning_post`, and be based on
@@ -231,6 +288,32 @@ pub fn verify_winning_post<Tree: 'static + MerkleTreeTrait>(
 ) -> Result<bool> {
     info!("verify_winning_post:start");
 
+    let verifying_key = get_post_verifying_key::<Tree>(&post_config)?;
+    let result = verify_winning_post_with_vk::<Tree>(
+        post_config,
+        &verifying_key,
+        randomness,
+        replicas,
+        prover_id,
+        proof,
+    );
+
+    info!("verify_winning_post:finish");
+    result
+}
+
+/// Verifies a winning proof-of-spacetime with an already loaded verifying key. Unlike
+/// `verify_winning_post`, this never looks up or generates any parameters.
+pub fn verify_winning_post_with_vk<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    verifying_key: &PreparedVerifyingKey<Bls12>,
+    randomness: &ChallengeSeed,
+    replicas: &[(SectorId, PublicReplicaInfo)],
+    prover_id: ProverId,
+    proof: &[u8],
+) -> Result<bool> {
+    info!("verify_winning_post_with_vk:start");
+
     ensure!(
         post_config.typ == PoStType::Winning,
         "invalid post config type"
@@ -240,11 +323,6 @@ pub fn verify_winning_post<Tree: 'static + MerkleTreeTrait>(
         "invalid amount of replicas provided"
     );
 
-    let randomness_safe: <Tree::Hasher as Hasher>::Domain =
-        as_safe_commitment(randomness, "randomness")?;
-    let prover_id_safe: <Tree::Hasher as Hasher>::Domain =
-        as_safe_commitment(&prover_id, "prover_id")?;
-
     let vanilla_params = winning_post_setup_params(&post_config)?;
     let param_sector_count = vanilla_params.sector_count;
 
@@ -255,31 +333,16 @@ pub fn verify_winning_post<Tree: 'static + MerkleTreeTrait>(
     };
     let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
         FallbackPoStCompound::setup(&setup_params)?;
-
-    let mut pub_sectors = Vec::with_capacity(param_sector_count);
-    for _ in 0..param_sector_count {
-        for (sector_id, replica) in replicas.iter() {
-            let comm_r = replica.safe_comm_r().with_context(|| {
-                format!("verify_winning_post: safe_comm_r failed: {:?}", sector_id)
-            })?;
-            pub_sectors.push(PublicSector {
-                id: *sector_id,
-                comm_r,
-            });
-        }
-    }
-
-    let pub_inputs = fallback::PublicInputs {
-        randomness: randomness_safe,
-        prover_id: prover_id_safe,
-        sectors: pub_sectors,
-        k: None,
-    };
+    let pub_inputs = winning_post_vanilla_public_inputs::<Tree>(
+        param_sector_count,
+        randomness,
+        replicas,
+        prover_id,
+    )?;
 
     let is_valid = {
-        let verifying_key = get_post_verifying_key::<Tree>(&post_config)?;
-
-        let single_proof = MultiProof::new_from_reader(None, proof, &verifying_key)?;
+        let single_proof =
+            MultiProof::new_from_reader(None, proof, verifying_key).context(Error::InvalidProof)?;
         if single_proof.len() != 1 {
             return Ok(false);
         }
@@ -298,7 +361,83 @@ pub fn verify_winning_post<Tree: 'static + MerkleTreeTrait>(
         return Ok(false);
     }
 
-    info!("verify_winning_post:finish");
+    info!("verify_winning_post_with_vk:finish");
 
     Ok(true)
 }
+
+/// Returns the circuit public inputs of a winning proof-of-spacetime over the sectors
+/// `sector_ids` with the replica commitments `comm_rs`.
+///
+/// These are the inputs `verify_winning_post` checks the proof against, so they can be
+/// transmitted along with the proof and compared to the ones derived by the verifier.
+pub fn winning_post_public_inputs<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    sector_ids: &[SectorId],
+    comm_rs: &[Commitment],
+    prover_id: ProverId,
+) -> Result<Vec<Fr>> {
+    ensure!(
+        post_config.typ == PoStType::Winning,
+        "invalid post config type"
+    );
+    ensure!(
+        post_config.sector_count == sector_ids.len(),
+        "invalid amount of sector ids provided"
+    );
+    ensure!(
+        sector_ids.len() == comm_rs.len(),
+        "number of sector ids and comm_rs don't match"
+    );
+
+    let replicas = sector_ids
+        .iter()
+        .zip(comm_rs)
+        .map(|(sector_id, comm_r)| Ok((*sector_id, PublicReplicaInfo::new(*comm_r)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let vanilla_params = winning_post_setup_params(&post_config)?;
+    let pub_inputs = winning_post_vanilla_public_inputs::<Tree>(
+        vanilla_params.sector_count,
+        randomness,
+        &replicas,
+        prover_id,
+    )?;
+
+    FallbackPoStCompound::<Tree>::generate_public_inputs(&pub_inputs, &vanilla_params, Some(0))
+}
+
+/// Winning PoSt challenges each sector `sector_count` times, so every replica is repeated
+/// that many times.
+fn winning_post_vanilla_public_inputs<Tree: 'static + MerkleTreeTrait>(
+    sector_count: usize,
+    randomness: &ChallengeSeed,
+    replicas: &[(SectorId, PublicReplicaInfo)],
+    prover_id: ProverId,
+) -> Result<fallback::PublicInputs<<Tree::Hasher as Hasher>::Domain>> {
+    let randomness_safe: <Tree::Hasher as Hasher>::Domain =
+        as_safe_commitment(randomness, "randomness")?;
+    let prover_id_safe: <Tree::Hasher as Hasher>::Domain =
+        as_safe_commitment(&prover_id, "prover_id")?;
+
+    let mut pub_sectors = Vec::with_capacity(sector_count * replicas.len());
+    for _ in 0..sector_count {
+        for (sector_id, replica) in replicas.iter() {
+            let comm_r = replica.safe_comm_r().with_context(|| {
+                format!("verify_winning_post: safe_comm_r failed: {:?}", sector_id)
+            })?;
+            pub_sectors.push(PublicSector {
+                id: *sector_id,
+                comm_r,
+            });
+        }
+    }
+
+    Ok(fallback::PublicInputs {
+        randomness: randomness_safe,
+        prover_id: prover_id_safe,
+        sectors: pub_sectors,
+        k: None,
+    })
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use bellperson::{
     bls::Bls12,
     groth16::{self, prepare_verifying_key},
@@ -9,12 +9,19 @@ use bellperson::{
 use lazy_static::lazy_static;
 use log::info;
 use rand::rngs::OsRng;
-use storage_proofs_core::{compound_proof::CompoundProof, merkle::MerkleTreeTrait};
+use storage_proofs_core::{
+    compound_proof::CompoundProof,
+    merkle::MerkleTreeTrait,
+    parameter_cache::{
+        is_circuit_compatible_version, parameter_cache_verifying_key_path_for_version, VERSION,
+    },
+};
 use storage_proofs_porep::stacked::{StackedCompound, StackedDrg};
 use storage_proofs_post::fallback::{FallbackPoSt, FallbackPoStCircuit, FallbackPoStCompound};
 
 use crate::{
     constants::DefaultPieceHasher,
+    param::read_verifying_key,
     parameters::{public_params, window_post_public_params, winning_post_public_params},
     types::{PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, PoStConfig, PoStType},
 };
@@ -82,6 +89,47 @@ where
     cache_lookup(&*VERIFYING_KEY_MEMORY_CACHE, vk_identifier, generator)
 }
 
+/// Returns true if PoRep parameters or verifying keys for `sector_size` have
+/// been loaded into the memory cache by this process.
+pub fn stacked_params_loaded(sector_size: u64) -> bool {
+    let identifier = format!("STACKED[{}]", sector_size);
+    let vk_identifier = format!("{}-verifying-key", &identifier);
+
+    GROTH_PARAM_MEMORY_CACHE
+        .lock()
+        .expect("poisoned cache")
+        .contains_key(&identifier)
+        || VERIFYING_KEY_MEMORY_CACHE
+            .lock()
+            .expect("poisoned cache")
+            .contains_key(&vk_identifier)
+}
+
+/// Drops all Groth parameters held in the memory cache, so the memory can be reclaimed.
+///
+/// Proofs already running keep the parameters they use alive, later proofs load them again.
+pub fn clear_parameter_cache() {
+    GROTH_PARAM_MEMORY_CACHE
+        .lock()
+        .expect("poisoned cache")
+        .clear();
+}
+
+/// Drops the Groth parameters for `sector_size` from the memory cache, like
+/// `clear_parameter_cache` does for all sector sizes.
+pub fn clear_parameter_cache_for_sector_size(sector_size: u64) {
+    evict_sector_size(&*GROTH_PARAM_MEMORY_CACHE, sector_size);
+}
+
+fn evict_sector_size<G>(cache_ref: &Mutex<Cache<G>>, sector_size: u64) {
+    let suffix = format!("[{}]", sector_size);
+
+    cache_ref
+        .lock()
+        .expect("poisoned cache")
+        .retain(|identifier, _| !identifier.ends_with(&suffix));
+}
+
 pub fn get_stacked_params<Tree: 'static + MerkleTreeTrait>(
     porep_config: PoRepConfig,
 ) -> Result<Arc<Bls12GrothParams>> {
@@ -181,6 +229,35 @@ pub fn get_stacked_verifying_key<Tree: 'static + MerkleTreeTrait>(
     )
 }
 
+/// Like `get_stacked_verifying_key`, but reads the verifying key published under parameter
+/// version `version` from the parameter cache.
+///
+/// `version` must share the current circuits, see `CIRCUIT_COMPATIBLE_VERSIONS`. The key is
+/// never generated, as parameters are only generated for the current version.
+pub fn get_stacked_verifying_key_for_version<Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    version: usize,
+) -> Result<Arc<Bls12PreparedVerifyingKey>> {
+    ensure!(
+        is_circuit_compatible_version(version),
+        "parameter version {} does not share the circuits of version {}",
+        version,
+        VERSION
+    );
+
+    let cache_id = porep_config.get_cache_identifier::<Tree>()?;
+    let path = parameter_cache_verifying_key_path_for_version(version, &cache_id);
+
+    lookup_verifying_key(
+        format!(
+            "STACKED[{}]-v{}",
+            usize::from(PaddedBytesAmount::from(porep_config)),
+            version
+        ),
+        || read_verifying_key(&path),
+    )
+}
+
 pub fn get_post_verifying_key<Tree: 'static + MerkleTreeTrait>(
     post_config: &PoStConfig,
 ) -> Result<Arc<Bls12PreparedVerifyingKey>> {
@@ -225,3 +302,39 @@ pub fn get_post_verifying_key<Tree: 'static + MerkleTreeTrait>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    #[test]
+    fn test_evict_sector_size() -> Result<()> {
+        let cache: Mutex<Cache<u64>> = Default::default();
+        let loads = Cell::new(0);
+        let lookup = |identifier: &str| {
+            cache_lookup(&cache, identifier.to_string(), || {
+                loads.set(loads.get() + 1);
+                Ok(0)
+            })
+        };
+
+        lookup("WINNING_POST[2048]")?;
+        lookup("Window_POST[2048]")?;
+        lookup("WINNING_POST[4096]")?;
+        lookup("WINNING_POST[2048]")?;
+        assert_eq!(loads.get(), 3);
+
+        evict_sector_size(&cache, 2048);
+        assert_eq!(cache.lock().expect("poisoned cache").len(), 1);
+
+        // The evicted parameters are loaded again, the others are still cached.
+        lookup("WINNING_POST[2048]")?;
+        assert_eq!(loads.get(), 4);
+        lookup("WINNING_POST[4096]")?;
+        assert_eq!(loads.get(), 4);
+
+        Ok(())
+    }
+}
@@ -45,23 +45,31 @@ impl<R: Read> CommitmentReader<R> {
 
         let CommitmentReader { current_tree, .. } = self;
 
-        let mut current_row = current_tree;
+        Ok(reduce_piece_tree(current_tree))
+    }
+}
 
-        while current_row.len() > 1 {
-            let next_row = current_row
-                .par_chunks(2)
-                .map(|chunk| piece_hash(chunk[0].as_ref(), chunk[1].as_ref()))
-                .collect::<Vec<_>>();
+/// Hashes the leaves of a piece tree pairwise until only the root remains.
+/// The number of leaves must be a power of 2.
+pub(crate) fn reduce_piece_tree(
+    leaves: Vec<<DefaultPieceHasher as Hasher>::Domain>,
+) -> <DefaultPieceHasher as Hasher>::Domain {
+    let mut current_row = leaves;
 
-            current_row = next_row;
-        }
-        debug_assert_eq!(current_row.len(), 1);
+    while current_row.len() > 1 {
+        let next_row = current_row
+            .par_chunks(2)
+            .map(|chunk| piece_hash(chunk[0].as_ref(), chunk[1].as_ref()))
+            .collect::<Vec<_>>();
 
-        Ok(current_row
-            .into_iter()
-            .next()
-            .expect("should have been caught by debug build: len==1"))
+        current_row = next_row;
     }
+    debug_assert_eq!(current_row.len(), 1);
+
+    current_row
+        .into_iter()
+        .next()
+        .expect("should have been caught by debug build: len==1")
 }
 
 impl<R: Read> Read for CommitmentReader<R> {
@@ -0,0 +1,112 @@
+use std::io::{self, Read, Write};
+
+use anyhow::{ensure, Context, Result};
+use filecoin_hashers::{HashFunction, Hasher};
+use fr32::Fr32Reader;
+
+use crate::{
+    commitment_reader::reduce_piece_tree,
+    constants::{DefaultPieceHasher, MINIMUM_RESERVED_BYTES_FOR_PIECE_IN_FULLY_ALIGNED_SECTOR},
+    types::{PaddedBytesAmount, PieceInfo, UnpaddedBytesAmount},
+};
+
+/// Number of unpadded bytes preprocessed at once, a whole number of 127 byte blocks.
+const CHUNK_SIZE: usize = 127 * 1024;
+
+/// Preprocesses the unpadded bytes written to it, writes the result to `target` and
+/// calculates the piece commitment of the data along the way.
+/// The total amount of data written must be a valid piece size.
+pub struct CommitmentWriter<W> {
+    target: W,
+    buffer: Vec<u8>,
+    padded: Vec<u8>,
+    unpadded_written: u64,
+    padded_written: u64,
+    current_tree: Vec<<DefaultPieceHasher as Hasher>::Domain>,
+}
+
+impl<W: Write> CommitmentWriter<W> {
+    pub fn new(target: W) -> Self {
+        CommitmentWriter {
+            target,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            padded: Vec::with_capacity(CHUNK_SIZE / 127 * 128),
+            unpadded_written: 0,
+            padded_written: 0,
+            current_tree: Vec::new(),
+        }
+    }
+
+    /// Preprocesses and writes out all buffered bytes.
+    fn process_buffer(&mut self) -> io::Result<()> {
+        self.padded.clear();
+        Fr32Reader::new(&self.buffer[..]).read_to_end(&mut self.padded)?;
+        self.target.write_all(&self.padded)?;
+
+        // WARNING: keep in sync with DefaultPieceHasher and its .node impl
+        // Every 127 byte block is padded to 128 bytes, so this never leaves a partial pair.
+        for pair in self.padded.chunks_exact(64) {
+            self.current_tree
+                .push(<DefaultPieceHasher as Hasher>::Function::hash(pair));
+        }
+
+        self.unpadded_written += self.buffer.len() as u64;
+        self.padded_written += self.padded.len() as u64;
+        self.buffer.clear();
+
+        Ok(())
+    }
+
+    /// Writes out the remaining data and returns the number of padded bytes written to the
+    /// target, together with the piece info of the data.
+    pub fn finish(mut self) -> Result<(PaddedBytesAmount, PieceInfo)> {
+        ensure!(
+            (self.unpadded_written + self.buffer.len() as u64) % 127 == 0,
+            "piece data must be a multiple of 127 bytes"
+        );
+        if !self.buffer.is_empty() {
+            self.process_buffer()
+                .context("failed to write and preprocess bytes")?;
+        }
+        self.target.flush()?;
+
+        let piece_size = UnpaddedBytesAmount(self.unpadded_written);
+        ensure!(
+            u64::from(piece_size) >= MINIMUM_RESERVED_BYTES_FOR_PIECE_IN_FULLY_ALIGNED_SECTOR,
+            "Piece must be at least {} bytes",
+            MINIMUM_RESERVED_BYTES_FOR_PIECE_IN_FULLY_ALIGNED_SECTOR
+        );
+        ensure!(
+            self.padded_written.is_power_of_two(),
+            "padded piece size {} is not a power of 2",
+            self.padded_written
+        );
+
+        let commitment = reduce_piece_tree(self.current_tree);
+        let mut comm = [0u8; 32];
+        comm.copy_from_slice(commitment.as_ref());
+
+        Ok((
+            PaddedBytesAmount(self.padded_written),
+            PieceInfo::new(comm, piece_size)?,
+        ))
+    }
+}
+
+impl<W: Write> Write for CommitmentWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = std::cmp::min(CHUNK_SIZE - self.buffer.len(), buf.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+        if self.buffer.len() == CHUNK_SIZE {
+            self.process_buffer()?;
+        }
+
+        Ok(n)
+    }
+
+    /// Flushes the target. Bytes which don't fill a whole chunk yet stay buffered until more
+    /// data is written, or `finish` is called.
+    fn flush(&mut self) -> io::Result<()> {
+        self.target.flush()
+    }
+}
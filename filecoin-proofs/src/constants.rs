@@ -1,19 +1,30 @@
+use std::cmp::min;
 use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::Range;
 use std::sync::RwLock;
 
 pub use storage_proofs_core::drgraph::BASE_DEGREE as DRG_DEGREE;
 pub use storage_proofs_porep::stacked::EXP_DEGREE;
 
-use filecoin_hashers::{poseidon::PoseidonHasher, sha256::Sha256Hasher, Hasher};
+use anyhow::{anyhow, ensure};
+use filecoin_hashers::{
+    blake2s::Blake2sHasher, poseidon::PoseidonHasher, sha256::Sha256Hasher, Hasher, PoseidonArity,
+};
 use lazy_static::lazy_static;
+use merkletree::merkle::{get_merkle_tree_cache_size, get_merkle_tree_row_count};
 use storage_proofs_core::{
-    merkle::{BinaryMerkleTree, LCTree, OctLCMerkleTree, OctMerkleTree},
-    util::NODE_SIZE,
+    merkle::{BinaryMerkleTree, LCTree, MerkleTreeTrait, OctLCMerkleTree, OctMerkleTree},
+    util::{default_rows_to_discard, NODE_SIZE},
     MAX_LEGACY_POREP_REGISTERED_PROOF_ID,
 };
-use typenum::{U0, U2, U8};
+use typenum::{Unsigned, U0, U2, U4, U8};
 
-use crate::types::UnpaddedBytesAmount;
+use crate::{
+    caches::stacked_params_loaded,
+    error::{Error, Result},
+    types::{PaddedBytesAmount, PoStType, UnpaddedBytesAmount},
+};
 
 pub const SECTOR_SIZE_2_KIB: u64 = 1 << 11;
 pub const SECTOR_SIZE_4_KIB: u64 = 1 << 12;
@@ -21,6 +32,7 @@ pub const SECTOR_SIZE_16_KIB: u64 = 1 << 14;
 pub const SECTOR_SIZE_32_KIB: u64 = 1 << 15;
 pub const SECTOR_SIZE_8_MIB: u64 = 1 << 23;
 pub const SECTOR_SIZE_16_MIB: u64 = 1 << 24;
+pub const SECTOR_SIZE_256_MIB: u64 = 1 << 28;
 pub const SECTOR_SIZE_512_MIB: u64 = 1 << 29;
 pub const SECTOR_SIZE_1_GIB: u64 = 1 << 30;
 pub const SECTOR_SIZE_32_GIB: u64 = 1 << 35;
@@ -33,7 +45,7 @@ pub const WINDOW_POST_CHALLENGE_COUNT: usize = 10;
 
 pub const MAX_LEGACY_REGISTERED_SEAL_PROOF_ID: u64 = MAX_LEGACY_POREP_REGISTERED_PROOF_ID;
 
-/// Sector sizes for which parameters have been published.
+/// Sector sizes whose parameters are published in parameters.json.
 pub const PUBLISHED_SECTOR_SIZES: [u64; 10] = [
     SECTOR_SIZE_2_KIB,
     SECTOR_SIZE_4_KIB,
@@ -47,6 +59,22 @@ pub const PUBLISHED_SECTOR_SIZES: [u64; 10] = [
     SECTOR_SIZE_64_GIB,
 ];
 
+/// Sector sizes supported by the proofs, which paramcache generates parameters for. These are
+/// the `PUBLISHED_SECTOR_SIZES` and 256 MiB, whose parameters aren't published.
+pub const SUPPORTED_SECTOR_SIZES: [u64; 11] = [
+    SECTOR_SIZE_2_KIB,
+    SECTOR_SIZE_4_KIB,
+    SECTOR_SIZE_16_KIB,
+    SECTOR_SIZE_32_KIB,
+    SECTOR_SIZE_8_MIB,
+    SECTOR_SIZE_16_MIB,
+    SECTOR_SIZE_256_MIB,
+    SECTOR_SIZE_512_MIB,
+    SECTOR_SIZE_1_GIB,
+    SECTOR_SIZE_32_GIB,
+    SECTOR_SIZE_64_GIB,
+];
+
 lazy_static! {
     pub static ref POREP_MINIMUM_CHALLENGES: RwLock<HashMap<u64, u64>> = RwLock::new(
         [
@@ -56,6 +84,7 @@ lazy_static! {
             (SECTOR_SIZE_32_KIB, 2),
             (SECTOR_SIZE_8_MIB, 2),
             (SECTOR_SIZE_16_MIB, 2),
+            (SECTOR_SIZE_256_MIB, 2),
             (SECTOR_SIZE_512_MIB, 2),
             (SECTOR_SIZE_1_GIB, 2),
             (SECTOR_SIZE_32_GIB, 176),
@@ -73,6 +102,7 @@ lazy_static! {
             (SECTOR_SIZE_32_KIB, 1),
             (SECTOR_SIZE_8_MIB, 1),
             (SECTOR_SIZE_16_MIB, 1),
+            (SECTOR_SIZE_256_MIB, 1),
             (SECTOR_SIZE_512_MIB, 1),
             (SECTOR_SIZE_1_GIB, 1),
             (SECTOR_SIZE_32_GIB, 10),
@@ -90,6 +120,7 @@ lazy_static! {
             (SECTOR_SIZE_32_KIB, 2),
             (SECTOR_SIZE_8_MIB, 2),
             (SECTOR_SIZE_16_MIB, 2),
+            (SECTOR_SIZE_256_MIB, 2),
             (SECTOR_SIZE_512_MIB, 2),
             (SECTOR_SIZE_1_GIB, 2),
             (SECTOR_SIZE_32_GIB, 11),
@@ -110,6 +141,7 @@ lazy_static! {
             (SECTOR_SIZE_32_KIB, 2),
             (SECTOR_SIZE_8_MIB, 2),
             (SECTOR_SIZE_16_MIB, 2),
+            (SECTOR_SIZE_256_MIB, 2),
             (SECTOR_SIZE_512_MIB, 2),
             (SECTOR_SIZE_1_GIB, 2),
             (SECTOR_SIZE_32_GIB, 2349), // this gives 125,279,217 constraints, fitting in a single partition
@@ -119,6 +151,231 @@ lazy_static! {
         .copied()
         .collect()
     );
+    // Only holds the sector sizes whose tree_r_last rows to discard have been overridden.
+    pub static ref ROWS_TO_DISCARD: RwLock<HashMap<u64, usize>> = RwLock::new(HashMap::new());
+}
+
+/// Returns the number of PoRep partitions used for `sector_size`.
+pub fn porep_partitions(sector_size: u64) -> Result<u8> {
+    lookup_constant(&POREP_PARTITIONS, "POREP_PARTITIONS", sector_size)
+}
+
+/// Returns the number of layers used for `sector_size`.
+pub fn layers(sector_size: u64) -> Result<usize> {
+    lookup_constant(&LAYERS, "LAYERS", sector_size)
+}
+
+/// Returns the number of challenges for each sector proven by a PoSt of type `post_type`.
+pub fn post_challenge_count(post_type: PoStType) -> usize {
+    match post_type {
+        PoStType::Winning => WINNING_POST_CHALLENGE_COUNT,
+        PoStType::Window => WINDOW_POST_CHALLENGE_COUNT,
+    }
+}
+
+/// Returns the number of sectors proven in a single Window PoSt partition for `sector_size`.
+pub fn window_post_sector_count(sector_size: u64) -> Result<usize> {
+    lookup_constant(
+        &WINDOW_POST_SECTOR_COUNT,
+        "WINDOW_POST_SECTOR_COUNT",
+        sector_size,
+    )
+}
+
+/// Returns the length in bytes of a Window PoSt proof of `sector_count` sectors of
+/// `sector_size`, one partition proof for every `window_post_sector_count` sectors.
+pub fn window_post_proof_len(sector_size: u64, sector_count: usize) -> Result<usize> {
+    ensure!(sector_count > 0, "no sectors to prove");
+    let partitions = window_post_partitions(sector_size, sector_count)?.len();
+
+    Ok(partitions * SINGLE_PARTITION_PROOF_LEN)
+}
+
+/// Returns the range of sector indexes proven by each partition of a Window PoSt of
+/// `sector_count` sectors of `sector_size`.
+///
+/// The sectors are indexed in the order of their ids, as they are proven. Every partition
+/// proves `window_post_sector_count` sectors, except for the last one, which may prove fewer.
+pub fn window_post_partitions(sector_size: u64, sector_count: usize) -> Result<Vec<Range<usize>>> {
+    let partition_sectors = window_post_sector_count(sector_size)?;
+
+    Ok((0..sector_count)
+        .step_by(partition_sectors)
+        .map(|start| start..min(start + partition_sectors, sector_count))
+        .collect())
+}
+
+/// Returns the minimum number of PoRep challenges used for `sector_size`.
+pub fn porep_minimum_challenges(sector_size: u64) -> Result<u64> {
+    lookup_constant(
+        &POREP_MINIMUM_CHALLENGES,
+        "POREP_MINIMUM_CHALLENGES",
+        sector_size,
+    )
+}
+
+fn lookup_constant<T: Copy>(
+    constant: &RwLock<HashMap<u64, T>>,
+    name: &str,
+    sector_size: u64,
+) -> Result<T> {
+    let map = constant.read().map_err(|_| anyhow!("{} poisoned", name))?;
+
+    map.get(&sector_size)
+        .copied()
+        .ok_or_else(|| Error::UnsupportedSectorSize(sector_size).into())
+}
+
+/// Overrides the number of PoRep partitions used for `sector_size`.
+///
+/// Fails if the sector size is unknown, `partitions` is zero, or parameters
+/// for the sector size have already been loaded by this process.
+pub fn set_porep_partitions(sector_size: u64, partitions: u8) -> Result<()> {
+    let mut map = POREP_PARTITIONS.write().expect("POREP_PARTITIONS poisoned");
+    ensure_overridable(
+        "porep partitions",
+        sector_size,
+        map.contains_key(&sector_size),
+        u64::from(partitions),
+        1,
+    )?;
+    map.insert(sector_size, partitions);
+
+    Ok(())
+}
+
+/// Overrides the minimum number of PoRep challenges used for `sector_size`.
+///
+/// Fails if the sector size is unknown, `challenges` is zero, or parameters
+/// for the sector size have already been loaded by this process.
+pub fn set_porep_minimum_challenges(sector_size: u64, challenges: u64) -> Result<()> {
+    let mut map = POREP_MINIMUM_CHALLENGES
+        .write()
+        .expect("POREP_MINIMUM_CHALLENGES poisoned");
+    ensure_overridable(
+        "porep minimum challenges",
+        sector_size,
+        map.contains_key(&sector_size),
+        challenges,
+        1,
+    )?;
+    map.insert(sector_size, challenges);
+
+    Ok(())
+}
+
+/// Overrides the number of layers used for `sector_size`.
+///
+/// Fails if the sector size is unknown, `layers` is less than two, or
+/// parameters for the sector size have already been loaded by this process.
+pub fn set_layers(sector_size: u64, layers: usize) -> Result<()> {
+    let mut map = LAYERS.write().expect("LAYERS poisoned");
+    ensure_overridable(
+        "layers",
+        sector_size,
+        map.contains_key(&sector_size),
+        layers as u64,
+        2,
+    )?;
+    map.insert(sector_size, layers);
+
+    Ok(())
+}
+
+/// Returns the number of rows discarded from the cached tree_r_last of `sector_size` sectors.
+///
+/// This is the value set with `set_rows_to_discard`, or the default for the sector shape.
+pub fn rows_to_discard(sector_size: u64) -> Result<usize> {
+    let (base_tree_leafs, arity) = tree_r_last_base_tree(sector_size)?;
+    let map = ROWS_TO_DISCARD
+        .read()
+        .map_err(|_| anyhow!("ROWS_TO_DISCARD poisoned"))?;
+
+    Ok(map
+        .get(&sector_size)
+        .copied()
+        .unwrap_or_else(|| default_rows_to_discard(base_tree_leafs, arity)))
+}
+
+/// Overrides the number of rows discarded from the cached tree_r_last of `sector_size` sectors.
+///
+/// Discarding more rows saves memory and disk space, but the discarded rows have to be rebuilt
+/// when generating proofs. A sector must be proven with the value it was sealed with. Fails if
+/// the sector size is unknown or `rows` exceeds the rows between the base tree leaves and root.
+pub fn set_rows_to_discard(sector_size: u64, rows: usize) -> Result<()> {
+    let (base_tree_leafs, arity) = tree_r_last_base_tree(sector_size)?;
+    // The leaves and the root are always kept.
+    let max_rows = get_merkle_tree_row_count(base_tree_leafs, arity).saturating_sub(2);
+    if rows > max_rows {
+        return Err(Error::InvalidSectorConstant {
+            name: "rows to discard",
+            sector_size,
+            value: rows as u64,
+        }
+        .into());
+    }
+    ROWS_TO_DISCARD
+        .write()
+        .expect("ROWS_TO_DISCARD poisoned")
+        .insert(sector_size, rows);
+
+    Ok(())
+}
+
+/// Returns the fewest rows to discard from the cached tree_r_last of `sector_size` sectors for
+/// the cached rows of all its base trees to fit in `memory_budget_bytes`.
+///
+/// Every row kept saves rebuilding it when proving, so this is the value to pass to
+/// `set_rows_to_discard` for a given budget. If the budget is too small even when discarding
+/// every row between the leaves and the root, that maximum is returned.
+pub fn recommended_rows_to_discard(sector_size: u64, memory_budget_bytes: u64) -> Result<usize> {
+    let (base_tree_leafs, arity) = tree_r_last_base_tree(sector_size)?;
+    let base_tree_count = sector_size as usize / NODE_SIZE / base_tree_leafs;
+    let max_rows = get_merkle_tree_row_count(base_tree_leafs, arity).saturating_sub(2);
+
+    for rows in 0..max_rows {
+        let cache_size = get_merkle_tree_cache_size(base_tree_leafs, arity, rows)?;
+        if (cache_size * NODE_SIZE * base_tree_count) as u64 <= memory_budget_bytes {
+            return Ok(rows);
+        }
+    }
+
+    Ok(max_rows)
+}
+
+/// Returns the number of leaves and the arity of a single tree_r_last base tree.
+fn tree_r_last_base_tree(sector_size: u64) -> Result<(usize, usize)> {
+    let (base, sub, top) = sector_shape_arities(sector_size)?;
+    let base_tree_count = sub.max(1) * top.max(1);
+
+    Ok((sector_size as usize / NODE_SIZE / base_tree_count, base))
+}
+
+fn ensure_overridable(
+    name: &'static str,
+    sector_size: u64,
+    known: bool,
+    value: u64,
+    minimum: u64,
+) -> Result<()> {
+    if !known {
+        return Err(Error::UnsupportedSectorSize(sector_size).into());
+    }
+    if value < minimum {
+        return Err(Error::InvalidSectorConstant {
+            name,
+            sector_size,
+            value,
+        }
+        .into());
+    }
+    // Changing the circuit shape after parameters were loaded would make
+    // proofs disagree with the cached parameters.
+    if stacked_params_loaded(sector_size) {
+        return Err(Error::ParametersAlreadyLoaded(sector_size).into());
+    }
+
+    Ok(())
 }
 
 /// The size of a single snark proof.
@@ -133,6 +390,20 @@ pub const MINIMUM_RESERVED_BYTES_FOR_PIECE_IN_FULLY_ALIGNED_SECTOR: u64 =
 /// The minimum size a single piece must have before padding.
 pub const MIN_PIECE_SIZE: UnpaddedBytesAmount = UnpaddedBytesAmount(127);
 
+/// Returns the smallest unpadded piece which can be added to a sector of `sector_size` bytes.
+pub fn minimum_piece_size(sector_size: u64) -> UnpaddedBytesAmount {
+    let sector = UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size));
+    std::cmp::min(MIN_PIECE_SIZE, sector)
+}
+
+/// Returns the number of minimum sized pieces which fill a sector of `sector_size` bytes.
+pub fn maximum_pieces_per_sector(sector_size: u64) -> u64 {
+    let padded_piece_size = PaddedBytesAmount::from(minimum_piece_size(sector_size));
+    sector_size
+        .checked_div(u64::from(padded_piece_size))
+        .unwrap_or(0)
+}
+
 /// The hasher used for creating comm_d.
 pub type DefaultPieceHasher = Sha256Hasher;
 pub type DefaultPieceDomain = <DefaultPieceHasher as Hasher>::Domain;
@@ -148,6 +419,7 @@ pub type DefaultOctLCTree = OctLCMerkleTree<DefaultTreeHasher>;
 // Generic shapes
 pub type SectorShapeBase = LCTree<DefaultTreeHasher, U8, U0, U0>;
 pub type SectorShapeSub2 = LCTree<DefaultTreeHasher, U8, U2, U0>;
+pub type SectorShapeSub4 = LCTree<DefaultTreeHasher, U8, U4, U0>;
 pub type SectorShapeSub8 = LCTree<DefaultTreeHasher, U8, U8, U0>;
 pub type SectorShapeTop2 = LCTree<DefaultTreeHasher, U8, U8, U2>;
 
@@ -160,12 +432,54 @@ pub type SectorShape4KiB = SectorShapeSub2;
 pub type SectorShape16MiB = SectorShapeSub2;
 pub type SectorShape1GiB = SectorShapeSub2;
 
+pub type SectorShape256MiB = SectorShapeSub4;
+
 pub type SectorShape16KiB = SectorShapeSub8;
 pub type SectorShape32GiB = SectorShapeSub8;
 
 pub type SectorShape32KiB = SectorShapeTop2;
 pub type SectorShape64GiB = SectorShapeTop2;
 
+/// Returns the type hint of the `DefaultTreeHasher` tree with the arities `Base`, `Sub` and
+/// `Top`, for trying shapes other than the `SectorShape*` aliases. Check the arities against
+/// the sector size with `validate_tree_shape` before building such a tree.
+pub fn lc_tree_type<Base, Sub, Top>() -> PhantomData<LCTree<DefaultTreeHasher, Base, Sub, Top>>
+where
+    Base: 'static + PoseidonArity,
+    Sub: 'static + PoseidonArity,
+    Top: 'static + PoseidonArity,
+{
+    PhantomData
+}
+
+/// A Blake2s based alternative to `DefaultTreeHasher`, for comparing proving costs.
+pub type Blake2sTreeHasher = Blake2sHasher;
+
+// Generic shapes using `Blake2sTreeHasher`
+pub type SectorShapeBlake2sBase = LCTree<Blake2sTreeHasher, U8, U0, U0>;
+pub type SectorShapeBlake2sSub2 = LCTree<Blake2sTreeHasher, U8, U2, U0>;
+pub type SectorShapeBlake2sSub4 = LCTree<Blake2sTreeHasher, U8, U4, U0>;
+pub type SectorShapeBlake2sSub8 = LCTree<Blake2sTreeHasher, U8, U8, U0>;
+pub type SectorShapeBlake2sTop2 = LCTree<Blake2sTreeHasher, U8, U8, U2>;
+
+/// Returns true if `sector_size` is one of the supported sector sizes.
+pub const fn is_supported_sector_size(sector_size: u64) -> bool {
+    matches!(
+        sector_size,
+        SECTOR_SIZE_2_KIB
+            | SECTOR_SIZE_4_KIB
+            | SECTOR_SIZE_16_KIB
+            | SECTOR_SIZE_32_KIB
+            | SECTOR_SIZE_8_MIB
+            | SECTOR_SIZE_16_MIB
+            | SECTOR_SIZE_256_MIB
+            | SECTOR_SIZE_512_MIB
+            | SECTOR_SIZE_1_GIB
+            | SECTOR_SIZE_32_GIB
+            | SECTOR_SIZE_64_GIB
+    )
+}
+
 pub fn is_sector_shape_base(sector_size: u64) -> bool {
     matches!(
         sector_size,
@@ -180,6 +494,10 @@ pub fn is_sector_shape_sub2(sector_size: u64) -> bool {
     )
 }
 
+pub fn is_sector_shape_sub4(sector_size: u64) -> bool {
+    matches!(sector_size, SECTOR_SIZE_256_MIB)
+}
+
 pub fn is_sector_shape_sub8(sector_size: u64) -> bool {
     matches!(sector_size, SECTOR_SIZE_16_KIB | SECTOR_SIZE_32_GIB)
 }
@@ -188,6 +506,193 @@ pub fn is_sector_shape_top2(sector_size: u64) -> bool {
     matches!(sector_size, SECTOR_SIZE_32_KIB | SECTOR_SIZE_64_GIB)
 }
 
+/// An operation which is generic over the sector shape, see [`with_sector_shape`].
+pub trait ShapeFn {
+    type Output;
+
+    fn call<Tree: 'static + MerkleTreeTrait>(self) -> Self::Output;
+}
+
+/// Calls `f` with the sector shape matching the provided sector size.
+///
+/// This is the runtime equivalent of `with_shape!`, which allows the operation to carry state.
+/// Returns an error if provided with an unknown sector size.
+pub fn with_sector_shape<F: ShapeFn>(sector_size: u64, f: F) -> Result<F::Output> {
+    let output = match sector_size {
+        SECTOR_SIZE_2_KIB => f.call::<SectorShape2KiB>(),
+        SECTOR_SIZE_4_KIB => f.call::<SectorShape4KiB>(),
+        SECTOR_SIZE_16_KIB => f.call::<SectorShape16KiB>(),
+        SECTOR_SIZE_32_KIB => f.call::<SectorShape32KiB>(),
+        SECTOR_SIZE_8_MIB => f.call::<SectorShape8MiB>(),
+        SECTOR_SIZE_16_MIB => f.call::<SectorShape16MiB>(),
+        SECTOR_SIZE_256_MIB => f.call::<SectorShape256MiB>(),
+        SECTOR_SIZE_512_MIB => f.call::<SectorShape512MiB>(),
+        SECTOR_SIZE_1_GIB => f.call::<SectorShape1GiB>(),
+        SECTOR_SIZE_32_GIB => f.call::<SectorShape32GiB>(),
+        SECTOR_SIZE_64_GIB => f.call::<SectorShape64GiB>(),
+        _ => return Err(Error::UnsupportedSectorSize(sector_size).into()),
+    };
+
+    Ok(output)
+}
+
+/// Calls `f` with the `Blake2sTreeHasher` sector shape matching the provided sector size.
+///
+/// The shapes have the same arities as the ones used by `with_sector_shape`, only the tree
+/// hasher differs. Returns an error if provided with an unknown sector size.
+pub fn with_sector_shape_blake2s<F: ShapeFn>(sector_size: u64, f: F) -> Result<F::Output> {
+    let output = match sector_size {
+        SECTOR_SIZE_2_KIB | SECTOR_SIZE_8_MIB | SECTOR_SIZE_512_MIB => {
+            f.call::<SectorShapeBlake2sBase>()
+        }
+        SECTOR_SIZE_4_KIB | SECTOR_SIZE_16_MIB | SECTOR_SIZE_1_GIB => {
+            f.call::<SectorShapeBlake2sSub2>()
+        }
+        SECTOR_SIZE_256_MIB => f.call::<SectorShapeBlake2sSub4>(),
+        SECTOR_SIZE_16_KIB | SECTOR_SIZE_32_GIB => f.call::<SectorShapeBlake2sSub8>(),
+        SECTOR_SIZE_32_KIB | SECTOR_SIZE_64_GIB => f.call::<SectorShapeBlake2sTop2>(),
+        _ => return Err(Error::UnsupportedSectorSize(sector_size).into()),
+    };
+
+    Ok(output)
+}
+
+/// Returns all supported sector sizes in ascending order.
+pub fn supported_sector_sizes() -> impl Iterator<Item = u64> {
+    SUPPORTED_SECTOR_SIZES.iter().copied()
+}
+
+/// Returns all supported sector sizes in ascending order, together with the
+/// `(base, sub, top)` arities of their sector shape.
+pub fn supported_sector_shapes() -> impl Iterator<Item = (u64, (usize, usize, usize))> {
+    supported_sector_sizes().map(|sector_size| {
+        let arities =
+            sector_shape_arities(sector_size).expect("supported sector size without a shape");
+        (sector_size, arities)
+    })
+}
+
+/// Returns the `(base, sub, top)` arities of the sector shape used for `sector_size`.
+pub(crate) fn sector_shape_arities(sector_size: u64) -> Result<(usize, usize, usize)> {
+    with_sector_shape(sector_size, ShapeArities)
+}
+
+/// Checks that the sector shape of every supported sector size matches its canonical shape.
+///
+/// Returns an error naming the first sector size whose shape does not match.
+pub fn validate_sector_shapes() -> Result<()> {
+    for (sector_size, actual) in supported_sector_shapes() {
+        let expected = canonical_shape(sector_size);
+        if actual != expected {
+            return Err(Error::InvalidSectorShape {
+                sector_size,
+                actual,
+                expected,
+            }
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the `(base, sub, top)` arities form a valid tree over a sector of `sector_size`
+/// bytes: the base arity is 2, 4 or 8, the sub and top arities are 0, 2, 4 or 8 with a top
+/// tree only above a sub tree, and the nodes of the sector split evenly into full base trees.
+///
+/// The shape doesn't have to be the canonical one, which the returned error reports as the
+/// expected shape.
+pub fn validate_tree_shape(sector_size: u64, shape: (usize, usize, usize)) -> Result<()> {
+    if sector_size.count_ones() != 1 || sector_size < NODE_SIZE as u64 {
+        return Err(Error::UnsupportedSectorSize(sector_size).into());
+    }
+
+    let (base, sub, top) = shape;
+    let is_arity = |arity: usize| arity == 2 || arity == 4 || arity == 8;
+    let base_tree_count = match (sub, top) {
+        (0, 0) => Some(1),
+        (sub, 0) if is_arity(sub) => Some(sub),
+        (sub, top) if is_arity(sub) && is_arity(top) => Some(sub * top),
+        _ => None,
+    };
+
+    let nodes = (sector_size / NODE_SIZE as u64) as usize;
+    let valid = match base_tree_count {
+        Some(count) if is_arity(base) && nodes % count == 0 => {
+            let base_tree_leafs = nodes / count;
+            base_tree_leafs >= base
+                && base_tree_leafs.is_power_of_two()
+                && base_tree_leafs.trailing_zeros() % base.trailing_zeros() == 0
+        }
+        _ => false,
+    };
+
+    if !valid {
+        return Err(Error::InvalidSectorShape {
+            sector_size,
+            actual: shape,
+            expected: canonical_shape(sector_size),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Computes the `(base, sub, top)` arities a sector of `sector_size` bytes should use.
+///
+/// The base trees are oct trees holding at most 2^27 nodes each. Whatever does not fit into a
+/// single base tree is split over an oct sub tree and, if needed, a remainder top tree.
+fn canonical_shape(sector_size: u64) -> (usize, usize, usize) {
+    // 16 KiB and 32 KiB sectors exercise the 32 GiB and 64 GiB shapes at test scale.
+    let sector_size = match sector_size {
+        SECTOR_SIZE_16_KIB => SECTOR_SIZE_32_GIB,
+        SECTOR_SIZE_32_KIB => SECTOR_SIZE_64_GIB,
+        _ => sector_size,
+    };
+    debug_assert_eq!(sector_size.count_ones(), 1);
+    let log_nodes = sector_size.trailing_zeros() - (NODE_SIZE as u32).trailing_zeros();
+
+    let max_tree_log = 3; // Largest allowable arity.
+
+    let log_max_base = 27; // 4 GiB worth of nodes
+    let log_base = max_tree_log;
+    let log_in_base = u32::min(log_max_base, (log_nodes / log_base) * log_base);
+
+    let log_upper = log_nodes - log_in_base; // Nodes in sub and top combined.
+    let log_rem = log_upper % max_tree_log; // Remainder after filling optimal trees.
+
+    let (log_sub, log_top) = if log_upper == 0 {
+        (None, None)
+    } else if log_rem == 0 {
+        (Some(max_tree_log), None)
+    } else if log_upper > max_tree_log {
+        (Some(max_tree_log), Some(log_rem))
+    } else {
+        (Some(log_rem), None)
+    };
+
+    let base = 1 << log_base;
+    let sub = log_sub.map_or(0, |l| 1 << l);
+    let top = log_top.map_or(0, |l| 1 << l);
+
+    (base, sub, top)
+}
+
+struct ShapeArities;
+
+impl ShapeFn for ShapeArities {
+    type Output = (usize, usize, usize);
+
+    fn call<Tree: 'static + MerkleTreeTrait>(self) -> Self::Output {
+        (
+            Tree::Arity::to_usize(),
+            Tree::SubTreeArity::to_usize(),
+            Tree::TopTreeArity::to_usize(),
+        )
+    }
+}
+
 /// Calls a function with the type hint of the sector shape matching the provided sector.
 /// Panics if provided with an unknown sector size.
 #[macro_export]
@@ -215,6 +720,9 @@ macro_rules! with_shape {
             _xx if $size == $crate::constants::SECTOR_SIZE_16_MIB => {
               $f::<$crate::constants::SectorShape16MiB>($($args),*)
             },
+            _x if $size == $crate::constants::SECTOR_SIZE_256_MIB => {
+              $f::<$crate::constants::SectorShape256MiB>($($args),*)
+            },
             _x if $size == $crate::constants::SECTOR_SIZE_512_MIB => {
               $f::<$crate::constants::SectorShape512MiB>($($args),*)
             },
@@ -235,6 +743,26 @@ macro_rules! with_shape {
     };
 }
 
+/// Calls a function with the type hint of the sector shape matching the provided sector.
+/// Evaluates to `Err(Error::UnsupportedSectorSize)` if provided with an unknown sector size.
+#[macro_export]
+macro_rules! try_with_shape {
+    ($size:expr, $f:ident) => {
+        try_with_shape!($size, $f,)
+    };
+    ($size:expr, $f:ident, $($args:expr,)*) => {{
+        let size: u64 = $size;
+        if $crate::constants::is_supported_sector_size(size) {
+            Ok($crate::with_shape!(size, $f, $($args,)*))
+        } else {
+            Err($crate::error::Error::UnsupportedSectorSize(size))
+        }
+    }};
+    ($size:expr, $f:ident, $($args:expr),*) => {
+        try_with_shape!($size, $f, $($args,)*)
+    };
+}
+
 pub const TEST_SEED: [u8; 16] = [
     0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc, 0xe5,
 ];
@@ -0,0 +1,28 @@
+//! Conversions between `DefaultTreeDomain`, `Fr` and the 32 byte little-endian encoding of
+//! field elements, as used for commitments.
+//!
+//! Bytes and domain elements are only converted if they hold a canonical field element, a value
+//! at or above the modulus is an error instead of being reduced.
+
+use anyhow::{Context, Result};
+use bellperson::bls::Fr;
+
+pub use fr32::{bytes_into_fr, fr_into_bytes};
+
+use crate::constants::DefaultTreeDomain;
+
+/// Returns the field element held by `domain`.
+pub fn domain_into_fr(domain: DefaultTreeDomain) -> Result<Fr> {
+    bytes_into_fr(AsRef::<[u8]>::as_ref(&domain))
+        .context("domain element is not a canonical field element")
+}
+
+/// Returns the domain element holding `fr`.
+pub fn fr_into_domain(fr: Fr) -> DefaultTreeDomain {
+    fr.into()
+}
+
+/// Returns the domain element of the little-endian field element in `bytes`.
+pub fn bytes_into_domain(bytes: &[u8]) -> Result<DefaultTreeDomain> {
+    bytes_into_fr(bytes).map(fr_into_domain)
+}
@@ -0,0 +1,180 @@
+use std::io::{self, Write};
+
+use anyhow::{ensure, Result};
+use storage_proofs_core::util::NODE_SIZE;
+
+use crate::{
+    pieces::piece_hash,
+    types::{Commitment, SectorSize},
+};
+
+/// Builds the data tree of a sector from its preprocessed bytes as they arrive, and
+/// calculates its comm_d.
+///
+/// Only the roots of the complete subtrees built so far are kept, so adding more data only
+/// hashes the new nodes, and finalizing only hashes the right edge of the tree. Space not
+/// filled with data is committed as zeros, the same way the staged sector is padded.
+#[derive(Debug, Clone)]
+pub struct IncrementalDataTreeBuilder {
+    sector_size: u64,
+    leaves: u64,
+    buffer: Vec<u8>,
+    /// Heights and roots of the complete subtrees, from left to right.
+    subtrees: Vec<(u32, Commitment)>,
+}
+
+impl IncrementalDataTreeBuilder {
+    pub fn new(sector_size: SectorSize) -> Result<Self> {
+        let sector_size = u64::from(sector_size);
+        ensure!(
+            sector_size.is_power_of_two() && sector_size >= 2 * NODE_SIZE as u64,
+            "invalid sector size {}",
+            sector_size
+        );
+
+        Ok(IncrementalDataTreeBuilder {
+            sector_size,
+            leaves: 0,
+            buffer: Vec::with_capacity(NODE_SIZE),
+            subtrees: Vec::new(),
+        })
+    }
+
+    /// Adds the preprocessed (fr32 padded) bytes in `data` to the tree. Data doesn't need
+    /// to be split at node boundaries, but in total no more than a sector can be added.
+    pub fn add_leaves(&mut self, mut data: &[u8]) -> Result<()> {
+        self.ensure_fits(data.len())?;
+
+        if !self.buffer.is_empty() {
+            let n = std::cmp::min(NODE_SIZE - self.buffer.len(), data.len());
+            self.buffer.extend_from_slice(&data[..n]);
+            data = &data[n..];
+
+            if self.buffer.len() == NODE_SIZE {
+                let mut leaf = [0u8; NODE_SIZE];
+                leaf.copy_from_slice(&self.buffer);
+                self.buffer.clear();
+                self.push_subtree(0, leaf);
+            }
+        }
+
+        let mut chunks = data.chunks_exact(NODE_SIZE);
+        for chunk in &mut chunks {
+            let mut leaf = [0u8; NODE_SIZE];
+            leaf.copy_from_slice(chunk);
+            self.push_subtree(0, leaf);
+        }
+        self.buffer.extend_from_slice(chunks.remainder());
+
+        Ok(())
+    }
+
+    /// Checks that `len` more bytes can be added without exceeding the sector.
+    fn ensure_fits(&self, len: usize) -> Result<()> {
+        let added = self.leaves * NODE_SIZE as u64 + (self.buffer.len() + len) as u64;
+        ensure!(
+            added <= self.sector_size,
+            "data of {} bytes does not fit into a sector of {} bytes",
+            added,
+            self.sector_size
+        );
+
+        Ok(())
+    }
+
+    /// Pads the remaining space of the sector with zeros and returns the comm_d.
+    pub fn finalize(mut self) -> Result<Commitment> {
+        ensure!(
+            self.buffer.is_empty(),
+            "data must be a multiple of {} bytes",
+            NODE_SIZE
+        );
+
+        let total_leaves = self.sector_size / NODE_SIZE as u64;
+        let mut zero_roots = vec![[0u8; NODE_SIZE]];
+        for height in 0..total_leaves.trailing_zeros() as usize {
+            let root = piece_hash(&zero_roots[height], &zero_roots[height]);
+            let mut commitment = [0u8; NODE_SIZE];
+            commitment.copy_from_slice(root.as_ref());
+            zero_roots.push(commitment);
+        }
+
+        // Fill the space with the largest zero subtrees that line up with the data.
+        while self.leaves < total_leaves {
+            let height = if self.leaves == 0 {
+                total_leaves.trailing_zeros()
+            } else {
+                self.leaves.trailing_zeros()
+            };
+            self.push_subtree(height, zero_roots[height as usize]);
+        }
+        debug_assert_eq!(self.subtrees.len(), 1);
+
+        Ok(self.subtrees[0].1)
+    }
+
+    /// Adds a complete subtree of the given height, merging it with its left neighbour
+    /// for as long as both are of the same height.
+    fn push_subtree(&mut self, mut height: u32, mut root: Commitment) {
+        self.leaves += 1 << height;
+
+        while let Some((left_height, left_root)) = self.subtrees.last() {
+            if *left_height != height {
+                break;
+            }
+
+            let parent = piece_hash(left_root, &root);
+            root.copy_from_slice(parent.as_ref());
+            height += 1;
+            self.subtrees.pop();
+        }
+        self.subtrees.push((height, root));
+    }
+}
+
+/// Writes the preprocessed bytes of a sector to `target` and calculates its comm_d along the
+/// way, for staging sectors too large to build the data tree in memory.
+///
+/// The data is fed to an `IncrementalDataTreeBuilder`, so only the roots of the complete
+/// subtrees are kept and memory stays logarithmic in the sector size.
+#[derive(Debug)]
+pub struct StreamingCommDBuilder<W> {
+    target: W,
+    tree: IncrementalDataTreeBuilder,
+}
+
+impl<W: Write> StreamingCommDBuilder<W> {
+    pub fn new(target: W, sector_size: SectorSize) -> Result<Self> {
+        Ok(StreamingCommDBuilder {
+            target,
+            tree: IncrementalDataTreeBuilder::new(sector_size)?,
+        })
+    }
+
+    /// Flushes the target and returns it together with the comm_d of the data written, with
+    /// the remaining space of the sector committed as zeros.
+    pub fn finalize(mut self) -> Result<(W, Commitment)> {
+        self.target.flush()?;
+        let comm_d = self.tree.finalize()?;
+
+        Ok((self.target, comm_d))
+    }
+}
+
+impl<W: Write> Write for StreamingCommDBuilder<W> {
+    /// Fails without writing anything if the data doesn't fit into the sector anymore. The
+    /// data is only added to the tree once the target accepted it, so a failed write doesn't
+    /// leave the comm_d ahead of the target.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let invalid_input = |err| io::Error::new(io::ErrorKind::InvalidInput, err);
+        self.tree.ensure_fits(buf.len()).map_err(invalid_input)?;
+        self.target.write_all(buf)?;
+        self.tree.add_leaves(buf).map_err(invalid_input)?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.target.flush()
+    }
+}
@@ -1,30 +1,67 @@
-use crate::FCP_LOG;
-use failure::{Backtrace, Error};
-use slog::*;
+use std::path::PathBuf;
 
-pub type Result<T> = ::std::result::Result<T, Error>;
+pub use anyhow::Result;
 
-pub trait ExpectWithBacktrace<T> {
-    fn expects(self, msg: &str) -> T;
-}
-
-impl<T, E: std::fmt::Debug> ExpectWithBacktrace<T> for ::std::result::Result<T, E> {
-    fn expects(self, msg: &str) -> T {
-        if let Err(ref err) = self {
-            let err = format!("{:?}", err);
-            let backtrace = format!("{:?}", Backtrace::new());
-            error!(FCP_LOG, "expected Result to be Ok"; "error" => err, "backtrace" => backtrace);
-        }
-        self.expect(msg)
-    }
-}
-
-impl<T> ExpectWithBacktrace<T> for Option<T> {
-    fn expects(self, msg: &str) -> T {
-        if self.is_none() {
-            let backtrace = format!("{:?}", Backtrace::new());
-            error!(FCP_LOG, "expected Option to be Some"; "backtrace" => backtrace);
-        }
-        self.expect(msg)
-    }
+/// Custom error types
+///
+/// The API returns `anyhow::Error`s, which carry one of these where the cause matters to
+/// the caller. Use `downcast_ref::<Error>()` to match on them.
+///
+/// Only the failures listed here are typed: unsupported sector sizes and shapes, missing or
+/// corrupt cache files and replicas, missing parameters, invalid or malformed proofs, invalid
+/// pieces and I/O errors. Invalid arguments, like a PoSt config of the wrong type or mismatched
+/// input lengths, are still reported as plain messages.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unsupported sector size: {0}")]
+    UnsupportedSectorSize(u64),
+    #[error("invalid {name} for sector size {sector_size}: {value}")]
+    InvalidSectorConstant {
+        name: &'static str,
+        sector_size: u64,
+        value: u64,
+    },
+    #[error("parameters for sector size {0} are already loaded")]
+    ParametersAlreadyLoaded(u64),
+    #[error("invalid shape for sector size {sector_size}: have {actual:?} but need {expected:?}")]
+    InvalidSectorShape {
+        sector_size: u64,
+        actual: (usize, usize, usize),
+        expected: (usize, usize, usize),
+    },
+    #[error("cache file {path:?} is corrupt: expected {expected} bytes, found {actual}")]
+    CacheFileCorrupt {
+        path: PathBuf,
+        expected: u64,
+        actual: u64,
+    },
+    #[error("cache file {0:?} is missing")]
+    MissingCacheFile(PathBuf),
+    #[error("no parameter data for cache id {0}")]
+    ParameterNotFound(String),
+    #[error("invalid proof")]
+    InvalidProof,
+    #[error("malformed proof: expected {expected_len} bytes, found {actual_len}")]
+    MalformedProof {
+        expected_len: usize,
+        actual_len: usize,
+    },
+    #[error(
+        "piece {index} has padded size {size}, which is not a power of two of at least 128 bytes"
+    )]
+    InvalidPieceSize { index: usize, size: u64 },
+    #[error("padded piece size {0} is not a power of two of at least 128 bytes")]
+    InvalidPaddedPieceSize(u64),
+    #[error("piece {index} ends at byte {end}, past the end of the {sector_size} byte sector")]
+    PieceExceedsSector {
+        index: usize,
+        end: u64,
+        sector_size: u64,
+    },
+    #[error("replica has {actual} bytes, expected {expected}")]
+    ReplicaSizeMismatch { expected: u64, actual: u64 },
+    #[error("label layer {layer} does not match its checksum")]
+    LayerChecksumMismatch { layer: usize },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
 }
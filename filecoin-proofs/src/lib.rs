@@ -4,6 +4,8 @@
 #![warn(clippy::unnecessary_wraps)]
 
 pub mod constants;
+pub mod conversions;
+pub mod error;
 pub mod param;
 pub mod parameters;
 pub mod pieces;
@@ -12,8 +14,13 @@ pub mod types;
 mod api;
 mod caches;
 mod commitment_reader;
+mod commitment_writer;
+mod data_tree_builder;
 
 pub use api::*;
+pub use caches::{clear_parameter_cache, clear_parameter_cache_for_sector_size};
 pub use commitment_reader::*;
+pub use commitment_writer::*;
 pub use constants::*;
+pub use data_tree_builder::*;
 pub use types::*;
@@ -1,13 +1,36 @@
 use std::collections::BTreeMap;
 use std::ffi::OsStr;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
 use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
+use anyhow::{ensure, Context, Result};
+use bellperson::{
+    bls::Bls12,
+    groth16::{prepare_verifying_key, PreparedVerifyingKey, VerifyingKey},
+};
 use blake2b_simd::State as Blake2b;
-use storage_proofs_core::parameter_cache::{
-    parameter_cache_dir, CacheEntryMetadata, PARAMETER_METADATA_EXT,
+use log::info;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use storage_proofs_core::{
+    api_version::ApiVersion,
+    merkle::MerkleTreeTrait,
+    parameter_cache::{
+        get_parameter_data, parameter_cache_dir, parameter_id, verifying_key_id,
+        CacheEntryMetadata, PARAMETER_METADATA_EXT,
+    },
+};
+
+use crate::{
+    constants::{
+        layers, minimum_piece_size, porep_minimum_challenges, porep_partitions,
+        sector_shape_arities, window_post_sector_count, with_sector_shape, ShapeFn,
+        WINDOW_POST_CHALLENGE_COUNT, WINNING_POST_CHALLENGE_COUNT, WINNING_POST_SECTOR_COUNT,
+    },
+    error::Error,
+    types::{PoRepConfig, PoStConfig, SectorSize},
+    PoStType,
 };
 
 // Produces an absolute path to a file within the cache
@@ -19,15 +42,88 @@ pub fn get_full_path_for_file_within_cache(filename: &str) -> PathBuf {
 
 // Produces a BLAKE2b checksum for a file within the cache
 pub fn get_digest_for_file_within_cache(filename: &str) -> Result<String> {
-    let path = get_full_path_for_file_within_cache(filename);
-    let mut file = File::open(&path).with_context(|| format!("could not open path={:?}", path))?;
+    get_digest_for_file(&get_full_path_for_file_within_cache(filename))
+}
+
+// Produces a BLAKE2b checksum for a file, truncated like the digests in parameters.json
+pub fn get_digest_for_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)
+        .map_err(Error::Io)
+        .with_context(|| format!("could not open path={:?}", path))?;
     let mut hasher = Blake2b::new();
 
-    io::copy(&mut file, &mut hasher)?;
+    io::copy(&mut file, &mut hasher).map_err(Error::Io)?;
 
     Ok(hasher.finalize().to_hex()[..32].into())
 }
 
+/// Checks whether the groth parameters file at `path` matches the digest recorded for
+/// `cache_id` in parameters.json.
+pub fn verify_parameter_file(cache_id: &str, path: &Path) -> Result<bool> {
+    let data = get_parameter_data(cache_id)
+        .ok_or_else(|| Error::ParameterNotFound(cache_id.to_string()))?;
+
+    Ok(get_digest_for_file(path)? == data.digest)
+}
+
+/// Reads and prepares the verifying key stored at `path`, e.g. a `.vk` file from the
+/// parameter cache, for use with `verify_seal_with_vk` and friends.
+pub fn read_verifying_key(path: &Path) -> Result<PreparedVerifyingKey<Bls12>> {
+    let bytes = fs::read(path)
+        .map_err(Error::Io)
+        .with_context(|| format!("could not open path={:?}", path))?;
+
+    verifying_key_from_bytes(&bytes)
+        .with_context(|| format!("could not read verifying key from path={:?}", path))
+}
+
+/// Decodes and prepares a verifying key serialized like the `.vk` files in the parameter
+/// cache, e.g. one embedded in a binary. Fails if `bytes` is not exactly one verifying key.
+pub fn verifying_key_from_bytes(bytes: &[u8]) -> Result<PreparedVerifyingKey<Bls12>> {
+    let mut reader = bytes;
+    let vk = VerifyingKey::<Bls12>::read(&mut reader).context("malformed verifying key")?;
+    ensure!(
+        reader.is_empty(),
+        "malformed verifying key: {} trailing bytes",
+        reader.len()
+    );
+
+    Ok(prepare_verifying_key(&vk))
+}
+
+/// Returns the SHA-256 digest of the verifying key for seal proofs with `porep_config`, as
+/// stored in the parameter cache.
+///
+/// A proof only verifies with the key of the circuit it was generated for, so comparing the
+/// fingerprints of prover and verifier reveals a stale parameter cache.
+pub fn verifying_key_fingerprint(porep_config: &PoRepConfig) -> Result<[u8; 32]> {
+    let path = with_sector_shape(
+        u64::from(porep_config.sector_size),
+        VerifyingKeyPath {
+            porep_config: *porep_config,
+        },
+    )??;
+    let bytes = fs::read(&path)
+        .map_err(Error::Io)
+        .with_context(|| format!("could not read verifying key path={:?}", path))?;
+
+    let mut fingerprint = [0u8; 32];
+    fingerprint.copy_from_slice(&Sha256::digest(&bytes));
+    Ok(fingerprint)
+}
+
+struct VerifyingKeyPath {
+    porep_config: PoRepConfig,
+}
+
+impl ShapeFn for VerifyingKeyPath {
+    type Output = Result<PathBuf>;
+
+    fn call<Tree: 'static + MerkleTreeTrait>(self) -> Self::Output {
+        self.porep_config.get_cache_verifying_key_path::<Tree>()
+    }
+}
+
 // Predicate which matches the provided extension against the given filename
 pub fn has_extension<S: AsRef<str>, P: AsRef<Path>>(filename: P, ext: S) -> bool {
     filename
@@ -72,3 +168,164 @@ pub fn filename_to_parameter_id<'a, P: AsRef<Path> + 'a>(filename: P) -> Option<
         .and_then(OsStr::to_str)
         .map(ToString::to_string)
 }
+
+/// Returns the names of the Groth parameter and verifying key files needed to seal and
+/// prove sectors of the given sizes which are absent from `cache_dir`, like
+/// `v28-<cache id>.params`.
+///
+/// Fails if any of the sector sizes is not supported.
+pub fn missing_parameters(cache_dir: &Path, sector_sizes: &[u64]) -> Result<Vec<String>> {
+    let mut missing = Vec::new();
+
+    for &sector_size in sector_sizes {
+        let cache_ids = with_sector_shape(sector_size, CacheIds { sector_size })??;
+
+        for cache_id in cache_ids {
+            for filename in &[parameter_id(&cache_id), verifying_key_id(&cache_id)] {
+                if !cache_dir.join(filename).exists() && !missing.contains(filename) {
+                    missing.push(filename.clone());
+                }
+            }
+        }
+    }
+
+    Ok(missing)
+}
+
+/// Returns the ids of the published Groth parameter files of the PoRep, Winning PoSt and
+/// Window PoSt circuits of `sector_size`, like `v28-<cache id>.params`.
+///
+/// Fails with `Error::ParameterNotFound` if parameters.json has no parameters for one of the
+/// circuits, as for 256 MiB sectors, whose parameters have to be generated with paramcache.
+/// Fails as well if the sector size is not supported.
+pub fn published_parameter_ids(sector_size: u64) -> Result<Vec<String>> {
+    let cache_ids = with_sector_shape(sector_size, CacheIds { sector_size })??;
+
+    cache_ids
+        .iter()
+        .map(|cache_id| {
+            ensure!(
+                get_parameter_data(cache_id).is_some(),
+                Error::ParameterNotFound(cache_id.clone())
+            );
+            Ok(parameter_id(cache_id))
+        })
+        .collect()
+}
+
+/// Reads the Groth parameter and verifying key files needed for the given sector sizes from
+/// the parameter cache, so that the OS has them in its page cache before the first proof.
+/// Meant to be called while a node warms up; see `prefetch_parameters_in`.
+pub fn prefetch_parameters(sector_sizes: &[u64]) -> Result<()> {
+    prefetch_parameters_in(&parameter_cache_dir(), sector_sizes)
+}
+
+/// Like `prefetch_parameters`, for the parameter files in `cache_dir`.
+///
+/// Fails with `Error::MissingCacheFile` before reading anything if any of the files is absent,
+/// and if any of the sector sizes is not supported.
+pub fn prefetch_parameters_in(cache_dir: &Path, sector_sizes: &[u64]) -> Result<()> {
+    let mut paths = Vec::new();
+    for &sector_size in sector_sizes {
+        let cache_ids = with_sector_shape(sector_size, CacheIds { sector_size })??;
+
+        for cache_id in cache_ids {
+            for filename in &[parameter_id(&cache_id), verifying_key_id(&cache_id)] {
+                let path = cache_dir.join(filename);
+                ensure!(path.exists(), Error::MissingCacheFile(path));
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+    }
+
+    for path in paths {
+        info!("prefetching {:?}", path);
+        let mut file = File::open(&path).with_context(|| format!("could not open {:?}", path))?;
+        io::copy(&mut file, &mut io::sink())
+            .with_context(|| format!("could not read {:?}", path))?;
+    }
+
+    Ok(())
+}
+
+/// Returns the constants used to seal and prove sectors of `sector_size` bytes as JSON, for
+/// tools and bindings which can't call the getters of this crate:
+///
+/// ```json
+/// {
+///   "sector_size": 34359738368,
+///   "partitions": 10,
+///   "layers": 11,
+///   "porep_minimum_challenges": 176,
+///   "winning_post_challenge_count": 66,
+///   "winning_post_sector_count": 1,
+///   "window_post_challenge_count": 10,
+///   "window_post_sector_count": 2349,
+///   "arities": [8, 8, 0],
+///   "minimum_piece_size": 127,
+///   "cache_ids": { "porep": "...", "winning_post": "...", "window_post": "..." }
+/// }
+/// ```
+///
+/// Fails if the sector size is not supported.
+pub fn sector_size_manifest(sector_size: u64) -> Result<Value> {
+    let partitions = porep_partitions(sector_size)?;
+    let cache_ids = with_sector_shape(sector_size, CacheIds { sector_size })??;
+
+    Ok(json!({
+        "sector_size": sector_size,
+        "partitions": partitions,
+        "layers": layers(sector_size)?,
+        "porep_minimum_challenges": porep_minimum_challenges(sector_size)?,
+        "winning_post_challenge_count": WINNING_POST_CHALLENGE_COUNT,
+        "winning_post_sector_count": WINNING_POST_SECTOR_COUNT,
+        "window_post_challenge_count": WINDOW_POST_CHALLENGE_COUNT,
+        "window_post_sector_count": window_post_sector_count(sector_size)?,
+        "arities": sector_shape_arities(sector_size)?,
+        "minimum_piece_size": u64::from(minimum_piece_size(sector_size)),
+        "cache_ids": {
+            "porep": cache_ids[0],
+            "winning_post": cache_ids[1],
+            "window_post": cache_ids[2],
+        },
+    }))
+}
+
+/// Collects the cache ids of the PoRep, Winning PoSt and Window PoSt circuits of a
+/// sector size.
+struct CacheIds {
+    sector_size: u64,
+}
+
+impl ShapeFn for CacheIds {
+    type Output = Result<Vec<String>>;
+
+    fn call<Tree: 'static + MerkleTreeTrait>(self) -> Self::Output {
+        let api_version = ApiVersion::V1_1_0;
+        let porep_config = PoRepConfig::for_sector_size(self.sector_size, [0; 32], api_version)?;
+        let winning_post_config = PoStConfig {
+            sector_size: SectorSize(self.sector_size),
+            challenge_count: WINNING_POST_CHALLENGE_COUNT,
+            sector_count: WINNING_POST_SECTOR_COUNT,
+            typ: PoStType::Winning,
+            priority: false,
+            api_version,
+        };
+        let window_post_config = PoStConfig {
+            sector_size: SectorSize(self.sector_size),
+            challenge_count: WINDOW_POST_CHALLENGE_COUNT,
+            sector_count: window_post_sector_count(self.sector_size)?,
+            typ: PoStType::Window,
+            priority: false,
+            api_version,
+        };
+
+        Ok(vec![
+            porep_config.get_cache_identifier::<Tree>()?,
+            winning_post_config.get_cache_identifier::<Tree>()?,
+            window_post_config.get_cache_identifier::<Tree>()?,
+        ])
+    }
+}
@@ -4,7 +4,7 @@ use storage_proofs_porep::stacked::{self, LayerChallenges, StackedDrg};
 use storage_proofs_post::fallback::{self, FallbackPoSt};
 
 use crate::{
-    constants::{DefaultPieceHasher, DRG_DEGREE, EXP_DEGREE, LAYERS, POREP_MINIMUM_CHALLENGES},
+    constants::{layers, porep_minimum_challenges, DefaultPieceHasher, DRG_DEGREE, EXP_DEGREE},
     types::{MerkleTreeTrait, PaddedBytesAmount, PoStConfig},
 };
 
@@ -82,16 +82,8 @@ pub fn setup_params(
 ) -> Result<stacked::SetupParams> {
     let layer_challenges = select_challenges(
         partitions,
-        *POREP_MINIMUM_CHALLENGES
-            .read()
-            .expect("POREP_MINIMUM_CHALLENGES poisoned")
-            .get(&u64::from(sector_bytes))
-            .expect("unknown sector size") as usize,
-        *LAYERS
-            .read()
-            .expect("LAYERS poisoned")
-            .get(&u64::from(sector_bytes))
-            .expect("unknown sector size"),
+        porep_minimum_challenges(u64::from(sector_bytes))? as usize,
+        layers(u64::from(sector_bytes))?,
     )?;
     let sector_bytes = u64::from(sector_bytes);
 
@@ -1,7 +1,9 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::io::Read;
 use std::io::{self, Cursor};
 use std::iter::Iterator;
+use std::marker::PhantomData;
 use std::sync::Mutex;
 
 use anyhow::{ensure, Context, Result};
@@ -14,8 +16,10 @@ use crate::constants::{
     DefaultPieceHasher,
     MINIMUM_RESERVED_BYTES_FOR_PIECE_IN_FULLY_ALIGNED_SECTOR as MINIMUM_PIECE_SIZE,
 };
+use crate::error::Error;
 use crate::types::{
-    Commitment, PaddedBytesAmount, PieceInfo, SectorSize, UnpaddedByteIndex, UnpaddedBytesAmount,
+    CommD, Commitment, PaddedBytesAmount, PieceInfo, SectorSize, UnpaddedByteIndex,
+    UnpaddedBytesAmount,
 };
 
 /// Verify that the provided `piece_infos` and `comm_d` match.
@@ -29,6 +33,55 @@ pub fn verify_pieces(
     Ok(&comm_d_calculated == comm_d)
 }
 
+/// Checks that a piece of `unpadded` bytes has a valid padded size, a power of two of at least
+/// the minimum piece size. Returns `Error::InvalidPaddedPieceSize` otherwise.
+pub fn validate_piece_size(unpadded: UnpaddedBytesAmount) -> Result<()> {
+    let size = u64::from(PaddedBytesAmount::from(unpadded));
+    if !is_valid_piece_size(size) {
+        return Err(Error::InvalidPaddedPieceSize(size).into());
+    }
+
+    Ok(())
+}
+
+fn is_valid_piece_size(padded_size: u64) -> bool {
+    let minimum_size = u64::from(PaddedBytesAmount::from(UnpaddedBytesAmount(
+        MINIMUM_PIECE_SIZE,
+    )));
+
+    padded_size.is_power_of_two() && padded_size >= minimum_size
+}
+
+/// Checks that `piece_infos` can be placed into a sector of `sector_size` bytes.
+///
+/// Pieces carry no offsets, they are placed in order, each at the first offset after the
+/// previous piece which is aligned to its padded size. This is where `add_piece` writes them
+/// and how `compute_comm_d` commits to them. So every padded size must be a power of two of
+/// at least the minimum piece size, and the last piece must end within the sector.
+pub fn validate_piece_placement(sector_size: u64, piece_infos: &[PieceInfo]) -> Result<()> {
+    let mut offset = 0;
+    for (index, piece_info) in piece_infos.iter().enumerate() {
+        let size = u64::from(PaddedBytesAmount::from(piece_info.size));
+        if !is_valid_piece_size(size) {
+            return Err(Error::InvalidPieceSize { index, size }.into());
+        }
+
+        let start = (offset + size - 1) / size * size;
+        let end = start + size;
+        if end > sector_size {
+            return Err(Error::PieceExceedsSector {
+                index,
+                end,
+                sector_size,
+            }
+            .into());
+        }
+        offset = end;
+    }
+
+    Ok(())
+}
+
 lazy_static! {
     static ref COMMITMENTS: Mutex<HashMap<SectorSize, Commitment>> = Mutex::new(HashMap::new());
 }
@@ -79,11 +132,32 @@ fn empty_comm_d(sector_size: SectorSize) -> Commitment {
 }
 
 pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Result<Commitment> {
-    info!("verifying {} pieces", piece_infos.len());
     if piece_infos.is_empty() {
+        info!("verifying 0 pieces");
         return Ok(empty_comm_d(sector_size));
     }
 
+    compute_comm_d_with_hasher::<DefaultPieceHasher>(sector_size, piece_infos)
+}
+
+/// Computes comm_d like `compute_comm_d`, using `H` instead of `DefaultPieceHasher`
+/// to hash the piece commitments together.
+pub fn compute_comm_d_with_hasher<H: Hasher>(
+    sector_size: SectorSize,
+    piece_infos: &[PieceInfo],
+) -> Result<Commitment> {
+    info!("verifying {} pieces", piece_infos.len());
+    ensure!(
+        H::Domain::default().as_ref().len() == NODE_SIZE,
+        "Piece hasher {} must produce {} byte domains",
+        H::name(),
+        NODE_SIZE
+    );
+
+    if piece_infos.is_empty() {
+        return Ok(zero_padding_with_hasher::<H>(sector_size.into())?.commitment);
+    }
+
     let unpadded_sector: UnpaddedBytesAmount = sector_size.into();
 
     ensure!(
@@ -102,7 +176,7 @@ pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Res
         "Piece is larger than sector."
     );
 
-    let mut stack = Stack::new();
+    let mut stack = Stack::<H>::new();
 
     let first = piece_infos.first().unwrap().clone();
     ensure!(
@@ -120,14 +194,14 @@ pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Res
         );
 
         while stack.peek().size < piece_info.size {
-            stack.shift_reduce(zero_padding(stack.peek().size)?)?
+            stack.shift_reduce(zero_padding_with_hasher::<H>(stack.peek().size)?)?
         }
 
         stack.shift_reduce(piece_info.clone())?;
     }
 
     while stack.len() > 1 {
-        stack.shift_reduce(zero_padding(stack.peek().size)?)?;
+        stack.shift_reduce(zero_padding_with_hasher::<H>(stack.peek().size)?)?;
     }
 
     ensure!(stack.len() == 1, "Stack size ({}) must be 1.", stack.len());
@@ -137,13 +211,13 @@ pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Res
     Ok(comm_d_calculated)
 }
 
-/// Stack used for piece reduction.
-struct Stack(Vec<PieceInfo>);
+/// Stack used for piece reduction, joining pieces by hashing them with `H`.
+struct Stack<H>(Vec<PieceInfo>, PhantomData<H>);
 
-impl Stack {
+impl<H: Hasher> Stack<H> {
     /// Creates a new stack.
     pub fn new() -> Self {
-        Stack(Vec::new())
+        Stack(Vec::new(), PhantomData)
     }
 
     /// Pushes a single element onto the stack.
@@ -174,7 +248,7 @@ impl Stack {
         if self.peek().size == self.peek2().size {
             let right = self.pop()?;
             let left = self.pop()?;
-            let joined = join_piece_infos(left, right)?;
+            let joined = join_piece_infos::<H>(left, right)?;
             self.shift(joined);
             return Ok(true);
         }
@@ -198,17 +272,22 @@ impl Stack {
 }
 
 /// Create a padding `PieceInfo` of size `size`.
-fn zero_padding(size: UnpaddedBytesAmount) -> Result<PieceInfo> {
+pub fn zero_padding(size: UnpaddedBytesAmount) -> Result<PieceInfo> {
+    zero_padding_with_hasher::<DefaultPieceHasher>(size)
+}
+
+/// Create a padding `PieceInfo` of size `size`, hashed with `H`.
+fn zero_padding_with_hasher<H: Hasher>(size: UnpaddedBytesAmount) -> Result<PieceInfo> {
     let padded_size: PaddedBytesAmount = size.into();
     let mut commitment = [0u8; 32];
 
     // TODO: cache common piece hashes
     let mut hashed_size = 64;
-    let h1 = piece_hash(&commitment, &commitment);
+    let h1 = piece_hash_with_hasher::<H>(&commitment, &commitment);
     commitment.copy_from_slice(h1.as_ref());
 
     while hashed_size < u64::from(padded_size) {
-        let h = piece_hash(&commitment, &commitment);
+        let h = piece_hash_with_hasher::<H>(&commitment, &commitment);
         commitment.copy_from_slice(h.as_ref());
         hashed_size *= 2;
     }
@@ -222,25 +301,42 @@ fn zero_padding(size: UnpaddedBytesAmount) -> Result<PieceInfo> {
 }
 
 /// Join two equally sized `PieceInfo`s together, by hashing them and adding their sizes.
-fn join_piece_infos(mut left: PieceInfo, right: PieceInfo) -> Result<PieceInfo> {
+fn join_piece_infos<H: Hasher>(mut left: PieceInfo, right: PieceInfo) -> Result<PieceInfo> {
     ensure!(
         left.size == right.size,
         "Piece sizes must be equal (left: {:?}, right: {:?})",
         left.size,
         right.size
     );
-    let h = piece_hash(&left.commitment, &right.commitment);
+    let h = piece_hash_with_hasher::<H>(&left.commitment, &right.commitment);
 
     left.commitment.copy_from_slice(AsRef::<[u8]>::as_ref(&h));
     left.size = left.size + right.size;
     Ok(left)
 }
 
+/// Hashes the roots of two adjacent, equally sized subtrees of a data tree into the root of
+/// the subtree spanning both.
+///
+/// Applied recursively this assembles the comm_d of a sector from the commitments of its
+/// parts, such as pieces built on separate machines, without hashing their data again. Every
+/// level of the data tree is hashed alike, so the parent doesn't depend on its height.
+pub fn combine_subtree_commitments(left: CommD, right: CommD) -> CommD {
+    let mut commitment = [0u8; NODE_SIZE];
+    commitment.copy_from_slice(piece_hash(left.as_bytes(), right.as_bytes()).as_ref());
+
+    CommD::try_from(commitment).expect("piece hashes are valid field elements")
+}
+
 pub(crate) fn piece_hash(a: &[u8], b: &[u8]) -> <DefaultPieceHasher as Hasher>::Domain {
+    piece_hash_with_hasher::<DefaultPieceHasher>(a, b)
+}
+
+fn piece_hash_with_hasher<H: Hasher>(a: &[u8], b: &[u8]) -> H::Domain {
     let mut buf = [0u8; NODE_SIZE * 2];
     buf[..NODE_SIZE].copy_from_slice(a);
     buf[NODE_SIZE..].copy_from_slice(b);
-    <DefaultPieceHasher as Hasher>::Function::hash(&buf)
+    H::Function::hash(&buf)
 }
 
 #[derive(Debug, Clone)]
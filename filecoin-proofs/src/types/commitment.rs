@@ -0,0 +1,306 @@
+use std::convert::{TryFrom, TryInto};
+use std::fmt::{self, Debug, Formatter};
+
+use anyhow::{bail, ensure, Context, Error, Result};
+use fr32::bytes_into_fr;
+
+use crate::types::Commitment;
+
+/// Multicodec of the CID of an unsealed sector or piece, `fil-commitment-unsealed`.
+const FIL_COMMITMENT_UNSEALED: u64 = 0xf101;
+/// Multicodec of the CID of a sealed sector, `fil-commitment-sealed`.
+const FIL_COMMITMENT_SEALED: u64 = 0xf102;
+/// Multihash code of comm_d, `sha2-256-trunc254-padded`.
+const SHA2_256_TRUNC254_PADDED: u64 = 0x1012;
+/// Multihash code of comm_r, `poseidon-bls12_381-a2-fc1`.
+const POSEIDON_BLS12_381_A2_FC1: u64 = 0xb401;
+
+/// Multibase prefix of lowercase base32 without padding, as used for CIDv1 strings.
+const MULTIBASE_BASE32: char = 'b';
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+macro_rules! commitment_type {
+    ($(#[$doc:meta])* $name:ident, $label:expr) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct $name(Commitment);
+
+        impl $name {
+            /// Parses the commitment from its 64 character hex encoding.
+            pub fn from_hex(hex: &str) -> Result<Self> {
+                let bytes = hex::decode(hex)
+                    .with_context(|| format!("invalid {} hex {:?}", $label, hex))?;
+                ensure!(
+                    bytes.len() == 32,
+                    "invalid {} length: {} bytes",
+                    $label,
+                    bytes.len()
+                );
+
+                let mut commitment = [0u8; 32];
+                commitment.copy_from_slice(&bytes);
+                Self::try_from(commitment)
+            }
+
+            pub fn to_hex(&self) -> String {
+                hex::encode(&self.0)
+            }
+
+            pub fn as_bytes(&self) -> &Commitment {
+                &self.0
+            }
+
+            /// Parses the commitment from the little-endian bytes of a field element, the
+            /// byte order used by the proofs. Same as `try_from`.
+            pub fn from_le_bytes(bytes: Commitment) -> Result<Self> {
+                Self::try_from(bytes)
+            }
+
+            /// Parses the commitment from the big-endian bytes of a field element, as shown by
+            /// tools which print field elements as numbers.
+            pub fn from_be_bytes(mut bytes: Commitment) -> Result<Self> {
+                bytes.reverse();
+                Self::try_from(bytes)
+            }
+
+            pub fn to_le_bytes(&self) -> Commitment {
+                self.0
+            }
+
+            pub fn to_be_bytes(&self) -> Commitment {
+                let mut bytes = self.0;
+                bytes.reverse();
+                bytes
+            }
+        }
+
+        impl TryFrom<Commitment> for $name {
+            type Error = Error;
+
+            /// Fails if `commitment` is not a valid field element.
+            fn try_from(commitment: Commitment) -> Result<Self> {
+                bytes_into_fr(&commitment)
+                    .with_context(|| format!("invalid {} {}", $label, hex::encode(&commitment)))?;
+
+                Ok($name(commitment))
+            }
+        }
+
+        impl From<$name> for Commitment {
+            fn from(commitment: $name) -> Self {
+                commitment.0
+            }
+        }
+
+        impl Debug for $name {
+            fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+                fmt.debug_tuple(stringify!($name))
+                    .field(&self.to_hex())
+                    .finish()
+            }
+        }
+    };
+}
+
+commitment_type!(
+    /// The commitment to the replica of a sector, comm_r.
+    CommR,
+    "comm_r"
+);
+commitment_type!(
+    /// The commitment to the unsealed data of a sector or piece, comm_d.
+    CommD,
+    "comm_d"
+);
+commitment_type!(
+    /// The commitment to the column hashes of a replica, comm_c.
+    CommC,
+    "comm_c"
+);
+
+impl CommR {
+    /// Returns the CID of a sealed sector with this comm_r, as used on chain.
+    pub fn to_cid(&self) -> String {
+        commitment_to_cid(FIL_COMMITMENT_SEALED, POSEIDON_BLS12_381_A2_FC1, &self.0)
+    }
+
+    pub fn from_cid(cid: &str) -> Result<Self> {
+        let commitment =
+            commitment_from_cid(FIL_COMMITMENT_SEALED, POSEIDON_BLS12_381_A2_FC1, cid)?;
+        Self::try_from(commitment)
+    }
+}
+
+impl CommD {
+    /// Returns the CID of an unsealed sector or piece with this comm_d, as used on chain.
+    pub fn to_cid(&self) -> String {
+        commitment_to_cid(FIL_COMMITMENT_UNSEALED, SHA2_256_TRUNC254_PADDED, &self.0)
+    }
+
+    pub fn from_cid(cid: &str) -> Result<Self> {
+        let commitment =
+            commitment_from_cid(FIL_COMMITMENT_UNSEALED, SHA2_256_TRUNC254_PADDED, cid)?;
+        Self::try_from(commitment)
+    }
+}
+
+/// A commitment borrowed from a larger buffer, such as aggregated proof or on-chain data.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CommitmentRef<'a>(pub &'a Commitment);
+
+impl<'a> CommitmentRef<'a> {
+    pub fn as_bytes(&self) -> &'a Commitment {
+        self.0
+    }
+}
+
+impl AsRef<[u8]> for CommitmentRef<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl From<CommitmentRef<'_>> for Commitment {
+    fn from(commitment: CommitmentRef<'_>) -> Self {
+        *commitment.0
+    }
+}
+
+impl Debug for CommitmentRef<'_> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        fmt.debug_tuple("CommitmentRef")
+            .field(&hex::encode(self.0))
+            .finish()
+    }
+}
+
+/// Splits `buf` into the consecutive 32 byte commitments it holds, without copying them.
+/// Fails if the length of `buf` is not a multiple of 32.
+pub fn split_commitments(buf: &[u8]) -> Result<impl Iterator<Item = CommitmentRef<'_>>> {
+    ensure!(
+        buf.len() % 32 == 0,
+        "buffer of {} bytes is not a sequence of commitments",
+        buf.len()
+    );
+
+    Ok(buf
+        .chunks_exact(32)
+        .map(|chunk| CommitmentRef(chunk.try_into().expect("chunks are exactly 32 bytes"))))
+}
+
+/// Encodes a commitment as a base32 CIDv1 string, with `codec` as the content type and the
+/// commitment as the digest of a multihash with code `hash`.
+fn commitment_to_cid(codec: u64, hash: u64, commitment: &Commitment) -> String {
+    let mut bytes = Vec::with_capacity(40);
+    write_varint(&mut bytes, 1);
+    write_varint(&mut bytes, codec);
+    write_varint(&mut bytes, hash);
+    write_varint(&mut bytes, commitment.len() as u64);
+    bytes.extend_from_slice(commitment);
+
+    let mut cid = String::with_capacity(1 + (bytes.len() * 8 + 4) / 5);
+    cid.push(MULTIBASE_BASE32);
+    cid.extend(base32_encode(&bytes).into_iter().map(char::from));
+    cid
+}
+
+fn commitment_from_cid(codec: u64, hash: u64, cid: &str) -> Result<Commitment> {
+    ensure!(
+        cid.starts_with(MULTIBASE_BASE32),
+        "CID {} is not base32 encoded",
+        cid
+    );
+    let bytes =
+        base32_decode(&cid[1..]).with_context(|| format!("CID {} is not base32 encoded", cid))?;
+    let mut reader = &bytes[..];
+
+    let version = read_varint(&mut reader)?;
+    ensure!(version == 1, "unsupported CID version {}", version);
+    let actual_codec = read_varint(&mut reader)?;
+    ensure!(
+        actual_codec == codec,
+        "unexpected CID codec {:#x}, expected {:#x}",
+        actual_codec,
+        codec
+    );
+    let actual_hash = read_varint(&mut reader)?;
+    ensure!(
+        actual_hash == hash,
+        "unexpected CID multihash {:#x}, expected {:#x}",
+        actual_hash,
+        hash
+    );
+    let len = read_varint(&mut reader)?;
+    ensure!(
+        len == 32 && reader.len() == 32,
+        "invalid CID digest length {}",
+        len
+    );
+
+    let mut commitment = [0u8; 32];
+    commitment.copy_from_slice(reader);
+    Ok(commitment)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(reader: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = reader.split_first().context("truncated CID")?;
+        *reader = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    bail!("CID varint overflow")
+}
+
+fn base32_encode(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity((bytes.len() * 8 + 4) / 5);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+
+    for &byte in bytes {
+        buffer = (buffer << 8) | u16::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[(buffer >> bits) as usize & 0x1f]);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[(buffer << (5 - bits)) as usize & 0x1f]);
+    }
+
+    out
+}
+
+fn base32_decode(encoded: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+
+    for c in encoded.bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .with_context(|| format!("invalid base32 character {:?}", char::from(c)))?;
+        buffer = (buffer << 5) | value as u16;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+
+    Ok(out)
+}
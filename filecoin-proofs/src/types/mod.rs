@@ -1,31 +1,44 @@
-use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+
+use anyhow::{ensure, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use storage_proofs::hasher::Hasher;
 use storage_proofs::porep::stacked;
+use storage_proofs_core::sector::SectorId;
 
 use crate::constants::*;
 
 mod bytes_amount;
+mod commitment;
 mod piece_info;
 mod porep_config;
 mod porep_proof_partitions;
 mod post_config;
 mod post_proof_partitions;
+mod registered_proof;
+mod seal_proof_record;
 mod sector_class;
 mod sector_size;
 
 pub use self::bytes_amount::*;
+pub use self::commitment::*;
 pub use self::piece_info::*;
 pub use self::porep_config::*;
 pub use self::porep_proof_partitions::*;
 pub use self::post_config::*;
 pub use self::post_proof_partitions::*;
+pub use self::registered_proof::*;
+pub use self::seal_proof_record::*;
 pub use self::sector_class::*;
 pub use self::sector_size::*;
 
+/// A commitment as the little-endian bytes of a field element, the order used by the proofs,
+/// hex encodings and CIDs. Use `CommR::to_be_bytes` and its siblings for big-endian forms.
 pub type Commitment = [u8; 32];
 pub type ChallengeSeed = [u8; 32];
 pub use stacked::PersistentAux;
 pub use stacked::TemporaryAux;
+pub use stacked::{ChallengeDerivation, FilecoinChallengeDerivation, LabelControl, LabelProgress};
 pub type ProverId = [u8; 32];
 pub type Ticket = [u8; 32];
 
@@ -51,23 +64,120 @@ pub struct SealPreCommitOutput {
 
 pub type VanillaSealProof<Tree> = storage_proofs::porep::stacked::Proof<Tree, DefaultPieceHasher>;
 
+/// Version of the binary encoding of `SealCommitPhase1Output`, changed whenever its layout
+/// changes.
+pub const SEAL_COMMIT_PHASE1_OUTPUT_VERSION: u32 = 1;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SealCommitPhase1Output<Tree: MerkleTreeTrait> {
+    #[serde(bound(
+        serialize = "VanillaSealProof<Tree>: Serialize",
+        deserialize = "VanillaSealProof<Tree>: Deserialize<'de>"
+    ))]
     pub vanilla_proofs: Vec<Vec<VanillaSealProof<Tree>>>,
     pub comm_r: Commitment,
     pub comm_d: Commitment,
+    #[serde(bound(
+        serialize = "<Tree::Hasher as Hasher>::Domain: Serialize",
+        deserialize = "<Tree::Hasher as Hasher>::Domain: Deserialize<'de>"
+    ))]
     pub replica_id: <Tree::Hasher as Hasher>::Domain,
     pub seed: Ticket,
     pub ticket: Ticket,
 }
 
+impl<Tree: MerkleTreeTrait> SealCommitPhase1Output<Tree> {
+    /// Encodes the output to be passed to `seal_commit_phase2` on another machine, as
+    /// `SEAL_COMMIT_PHASE1_OUTPUT_VERSION` in four little-endian bytes followed by the bincode
+    /// encoding of the output.
+    pub fn to_bytes(&self) -> Result<Vec<u8>>
+    where
+        Self: Serialize,
+    {
+        let mut bytes = SEAL_COMMIT_PHASE1_OUTPUT_VERSION.to_le_bytes().to_vec();
+        bincode::serialize_into(&mut bytes, self)?;
+
+        Ok(bytes)
+    }
+
+    /// Decodes an output encoded with `to_bytes`. Fails if it was encoded with another version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self>
+    where
+        Self: DeserializeOwned,
+    {
+        ensure!(
+            bytes.len() >= 4,
+            "SealCommitPhase1Output encoding is truncated"
+        );
+        let (version, encoded) = bytes.split_at(4);
+        let version = u32::from_le_bytes(version.try_into()?);
+        ensure!(
+            version == SEAL_COMMIT_PHASE1_OUTPUT_VERSION,
+            "unsupported SealCommitPhase1Output encoding version {}, expected {}",
+            version,
+            SEAL_COMMIT_PHASE1_OUTPUT_VERSION
+        );
+
+        Ok(bincode::deserialize(encoded)?)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SealCommitOutput {
     pub proof: Vec<u8>,
 }
 
+/// The result of sealing a sector with `seal_all`.
+#[derive(Clone, Debug)]
+pub struct SealOutput {
+    pub comm_r: Commitment,
+    pub comm_d: Commitment,
+    pub proof: Vec<u8>,
+}
+
+/// The public inputs and proof of a single sector's seal, as verified by `verify_seals`.
+#[derive(Clone, Debug)]
+pub struct SealVerifyInput {
+    pub comm_r: Commitment,
+    pub comm_d: Commitment,
+    pub prover_id: ProverId,
+    pub sector_id: SectorId,
+    pub ticket: Ticket,
+    pub seed: Ticket,
+    pub proof: Vec<u8>,
+}
+
+/// The alignment of buffers, file offsets and write sizes for direct I/O.
+pub const DIRECT_IO_ALIGNMENT: usize = 4096;
+
+/// How PreCommit Phase 1 writes the unsealed data to the replica file, where it's sealed in
+/// place. The default lets `fs::copy` pick how to copy the data.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReplicaWriteConfig {
+    /// The size of the buffer the data is copied through.
+    pub buffer_size: Option<usize>,
+    /// Bypasses the page cache with `O_DIRECT` on Linux, and syncs the replica to disk after
+    /// writing it elsewhere. Needs a `buffer_size` which is a multiple of `DIRECT_IO_ALIGNMENT`.
+    pub direct_io: bool,
+}
+
 pub use merkletree::store::StoreConfig;
 
+/// The commitments and tree configs of a sealed sector, as recovered from its cache
+/// directory by `inspect_sealed_sector`.
+#[derive(Clone, Debug)]
+pub struct SealedSectorInfo {
+    pub sector_size: u64,
+    pub comm_r: Commitment,
+    /// `None` once tree_d has been removed from the cache directory.
+    pub comm_d: Option<Commitment>,
+    pub comm_c: Commitment,
+    pub comm_r_last: Commitment,
+    pub tree_d_config: StoreConfig,
+    pub tree_c_config: StoreConfig,
+    pub tree_r_last_config: StoreConfig,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SealPreCommitPhase1Output<Tree: MerkleTreeTrait> {
     pub labels: Labels<Tree>,
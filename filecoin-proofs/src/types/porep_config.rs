@@ -9,11 +9,14 @@ use storage_proofs_core::{
         parameter_cache_verifying_key_path, CacheableParameters,
     },
 };
-use storage_proofs_porep::stacked::{StackedCircuit, StackedCompound};
+use storage_proofs_porep::stacked::{
+    self, ChallengeDerivation, FilecoinChallengeDerivation, StackedCircuit, StackedCompound,
+};
 
 use crate::{
-    constants::DefaultPieceHasher,
-    parameters::public_params,
+    constants::{layers, porep_minimum_challenges, porep_partitions, DefaultPieceHasher},
+    error::Error,
+    parameters::{public_params, setup_params},
     types::{PaddedBytesAmount, PoRepProofPartitions, SectorSize, UnpaddedBytesAmount},
 };
 
@@ -23,8 +26,32 @@ pub struct PoRepConfig {
     pub partitions: PoRepProofPartitions,
     pub porep_id: [u8; 32],
     pub api_version: ApiVersion,
+    /// Custom derivation of the PoRep challenges, the Filecoin derivation is used if `None`.
+    /// Sealing and verifying must use the same derivation.
+    pub challenge_derivation: Option<&'static dyn ChallengeDerivation>,
+}
+
+// Challenge derivations are told apart by their id, `None` being the Filecoin derivation.
+impl PartialEq for PoRepConfig {
+    fn eq(&self, other: &Self) -> bool {
+        let derivation_id = |config: &Self| {
+            config
+                .challenge_derivation
+                .map_or(FilecoinChallengeDerivation::ID, |derivation| {
+                    derivation.id()
+                })
+        };
+
+        self.sector_size == other.sector_size
+            && self.partitions == other.partitions
+            && self.porep_id == other.porep_id
+            && self.api_version == other.api_version
+            && derivation_id(self) == derivation_id(other)
+    }
 }
 
+impl Eq for PoRepConfig {}
+
 impl From<PoRepConfig> for PaddedBytesAmount {
     fn from(x: PoRepConfig) -> Self {
         let PoRepConfig { sector_size, .. } = x;
@@ -54,6 +81,73 @@ impl From<PoRepConfig> for SectorSize {
 }
 
 impl PoRepConfig {
+    /// Creates the config for `sector_size`, using the partition count registered in the
+    /// per-sector-size constants.
+    ///
+    /// Fails if the sector size is unknown or its registered constants are inconsistent.
+    pub fn for_sector_size(
+        sector_size: u64,
+        porep_id: [u8; 32],
+        api_version: ApiVersion,
+    ) -> Result<Self> {
+        let partitions = porep_partitions(sector_size)?;
+        if partitions == 0 {
+            return Err(Error::InvalidSectorConstant {
+                name: "porep partitions",
+                sector_size,
+                value: u64::from(partitions),
+            }
+            .into());
+        }
+
+        let config = PoRepConfig {
+            sector_size: SectorSize(sector_size),
+            partitions: PoRepProofPartitions(partitions),
+            porep_id,
+            api_version,
+            challenge_derivation: None,
+        };
+
+        let layers = config.layers()?;
+        if layers < 2 {
+            return Err(Error::InvalidSectorConstant {
+                name: "layers",
+                sector_size,
+                value: layers as u64,
+            }
+            .into());
+        }
+        config.minimum_challenges()?;
+
+        Ok(config)
+    }
+
+    /// Returns the number of layers used when sealing with this config.
+    pub fn layers(&self) -> Result<usize> {
+        layers(u64::from(self.sector_size))
+    }
+
+    /// Returns the minimum number of challenges used when sealing with this config.
+    pub fn minimum_challenges(&self) -> Result<u64> {
+        porep_minimum_challenges(u64::from(self.sector_size))
+    }
+
+    /// Returns the setup params of the PoRep with this config, deriving the challenges with
+    /// `challenge_derivation` if set.
+    pub fn setup_params(&self) -> Result<stacked::SetupParams> {
+        let mut params = setup_params(
+            PaddedBytesAmount::from(*self),
+            usize::from(self.partitions),
+            self.porep_id,
+            self.api_version,
+        )?;
+        if let Some(derivation) = self.challenge_derivation {
+            params.layer_challenges = params.layer_challenges.with_derivation(derivation);
+        }
+
+        Ok(params)
+    }
+
     /// Returns the cache identifier as used by `storage-proofs::paramater_cache`.
     pub fn get_cache_identifier<Tree: 'static + MerkleTreeTrait>(&self) -> Result<String> {
         let params = public_params::<Tree>(
@@ -9,18 +9,18 @@ use bincode::deserialize;
 use filecoin_hashers::Hasher;
 use generic_array::typenum::Unsigned;
 use log::trace;
-use merkletree::store::StoreConfig;
+use merkletree::store::{ReplicaConfig, StoreConfig};
 use storage_proofs_core::{
     cache_key::CacheKey,
     merkle::{
         create_tree, get_base_tree_count, split_config_and_replica, MerkleTreeTrait,
-        MerkleTreeWrapper,
+        MerkleTreeWrapper, MmapLCTree,
     },
-    util::default_rows_to_discard,
 };
 
 use crate::{
-    api::{as_safe_commitment, get_base_tree_leafs, get_base_tree_size},
+    api::{as_safe_commitment, get_base_tree_leafs, get_base_tree_size, validate_replica},
+    constants::rows_to_discard,
     types::{Commitment, PersistentAux, SectorSize},
 };
 
@@ -36,6 +36,8 @@ pub struct PrivateReplicaInfo<Tree: MerkleTreeTrait> {
     aux: PersistentAux<<Tree::Hasher as Hasher>::Domain>,
     /// Contains sector-specific (e.g. merkle trees) assets
     pub cache_dir: PathBuf,
+    /// Contains tree_c and tree_r_last, if they are not in `cache_dir`.
+    pub tree_dir: Option<PathBuf>,
 
     _t: PhantomData<Tree>,
 }
@@ -47,6 +49,7 @@ impl<Tree: MerkleTreeTrait> Clone for PrivateReplicaInfo<Tree> {
             comm_r: self.comm_r,
             aux: self.aux.clone(),
             cache_dir: self.cache_dir.clone(),
+            tree_dir: self.tree_dir.clone(),
             _t: Default::default(),
         }
     }
@@ -58,6 +61,7 @@ impl<Tree: MerkleTreeTrait> PartialEq for PrivateReplicaInfo<Tree> {
             && self.comm_r == other.comm_r
             && self.aux == other.aux
             && self.cache_dir == other.cache_dir
+            && self.tree_dir == other.tree_dir
     }
 }
 
@@ -67,6 +71,7 @@ impl<Tree: MerkleTreeTrait> Hash for PrivateReplicaInfo<Tree> {
         self.comm_r.hash(state);
         self.aux.hash(state);
         self.cache_dir.hash(state);
+        self.tree_dir.hash(state);
     }
 }
 
@@ -86,6 +91,17 @@ impl<Tree: MerkleTreeTrait> PartialOrd for PrivateReplicaInfo<Tree> {
 
 impl<Tree: 'static + MerkleTreeTrait> PrivateReplicaInfo<Tree> {
     pub fn new(replica: PathBuf, comm_r: Commitment, cache_dir: PathBuf) -> Result<Self> {
+        Self::new_with_tree_dir(replica, comm_r, cache_dir, None)
+    }
+
+    /// Like `new`, for a replica whose tree_c and tree_r_last were written to `tree_dir` by
+    /// `seal_pre_commit_phase2` instead of `cache_dir`.
+    pub fn new_with_tree_dir(
+        replica: PathBuf,
+        comm_r: Commitment,
+        cache_dir: PathBuf,
+        tree_dir: Option<PathBuf>,
+    ) -> Result<Self> {
         ensure!(comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
 
         let aux = {
@@ -103,6 +119,7 @@ impl<Tree: 'static + MerkleTreeTrait> PrivateReplicaInfo<Tree> {
             comm_r,
             aux,
             cache_dir,
+            tree_dir,
             _t: Default::default(),
         })
     }
@@ -111,6 +128,11 @@ impl<Tree: 'static + MerkleTreeTrait> PrivateReplicaInfo<Tree> {
         self.cache_dir.as_path()
     }
 
+    /// Returns the directory holding tree_c and tree_r_last.
+    pub fn tree_dir_path(&self) -> &Path {
+        self.tree_dir.as_deref().unwrap_or_else(|| self.cache_dir_path())
+    }
+
     pub fn replica_path(&self) -> &Path {
         self.replica.as_path()
     }
@@ -140,22 +162,42 @@ impl<Tree: 'static + MerkleTreeTrait> PrivateReplicaInfo<Tree> {
             Tree::TopTreeArity,
         >,
     > {
+        let (base_tree_size, configs, replica_config) = self.tree_configs(sector_size)?;
+
+        create_tree::<Tree>(base_tree_size, &configs, Some(&replica_config))
+    }
+
+    /// Maps the files of the merkle tree of this replica, to generate its inclusion proofs
+    /// without reading the files for every proof.
+    pub fn mmap_tree(&self, sector_size: SectorSize) -> Result<MmapLCTree<Tree>> {
+        let (base_tree_size, configs, replica_config) = self.tree_configs(sector_size)?;
+
+        MmapLCTree::new(base_tree_size, &configs, &replica_config)
+    }
+
+    fn tree_configs(
+        &self,
+        sector_size: SectorSize,
+    ) -> Result<(usize, Vec<StoreConfig>, ReplicaConfig)> {
+        validate_replica(&self.replica, u64::from(sector_size))?;
+
         let base_tree_size = get_base_tree_size::<Tree>(sector_size)?;
         let base_tree_leafs = get_base_tree_leafs::<Tree>(base_tree_size)?;
+        let rows_to_discard = rows_to_discard(u64::from(sector_size))?;
         trace!(
             "post: base tree size {}, base tree leafs {}, rows_to_discard {}, arities [{}, {}, {}]",
             base_tree_size,
             base_tree_leafs,
-            default_rows_to_discard(base_tree_leafs, Tree::Arity::to_usize()),
+            rows_to_discard,
             Tree::Arity::to_usize(),
             Tree::SubTreeArity::to_usize(),
             Tree::TopTreeArity::to_usize(),
         );
 
         let mut config = StoreConfig::new(
-            self.cache_dir_path(),
+            self.tree_dir_path(),
             CacheKey::CommRLastTree.to_string(),
-            default_rows_to_discard(base_tree_leafs, Tree::Arity::to_usize()),
+            rows_to_discard,
         );
         config.size = Some(base_tree_size);
 
@@ -167,6 +209,6 @@ impl<Tree: 'static + MerkleTreeTrait> PrivateReplicaInfo<Tree> {
             tree_count,
         )?;
 
-        create_tree::<Tree>(base_tree_size, &configs, Some(&replica_config))
+        Ok((base_tree_size, configs, replica_config))
     }
 }
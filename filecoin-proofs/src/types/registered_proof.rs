@@ -0,0 +1,126 @@
+use std::convert::TryFrom;
+
+use anyhow::{bail, Error, Result};
+
+use crate::{constants::*, error::Error as ProofsError};
+
+/// The seal proofs registered on chain, with the ids they are identified by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum RegisteredSealProof {
+    StackedDrg2KiBV1 = 0,
+    StackedDrg8MiBV1 = 1,
+    StackedDrg512MiBV1 = 2,
+    StackedDrg32GiBV1 = 3,
+    StackedDrg64GiBV1 = 4,
+    StackedDrg2KiBV1_1 = 5,
+    StackedDrg8MiBV1_1 = 6,
+    StackedDrg512MiBV1_1 = 7,
+    StackedDrg32GiBV1_1 = 8,
+    StackedDrg64GiBV1_1 = 9,
+}
+
+/// The PoSt proofs registered on chain, with the ids they are identified by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum RegisteredPoStProof {
+    StackedDrgWinning2KiBV1 = 0,
+    StackedDrgWinning8MiBV1 = 1,
+    StackedDrgWinning512MiBV1 = 2,
+    StackedDrgWinning32GiBV1 = 3,
+    StackedDrgWinning64GiBV1 = 4,
+    StackedDrgWindow2KiBV1 = 5,
+    StackedDrgWindow8MiBV1 = 6,
+    StackedDrgWindow512MiBV1 = 7,
+    StackedDrgWindow32GiBV1 = 8,
+    StackedDrgWindow64GiBV1 = 9,
+}
+
+impl TryFrom<u64> for RegisteredSealProof {
+    type Error = Error;
+
+    fn try_from(id: u64) -> Result<Self> {
+        use RegisteredSealProof::*;
+
+        let proof = match id {
+            0 => StackedDrg2KiBV1,
+            1 => StackedDrg8MiBV1,
+            2 => StackedDrg512MiBV1,
+            3 => StackedDrg32GiBV1,
+            4 => StackedDrg64GiBV1,
+            5 => StackedDrg2KiBV1_1,
+            6 => StackedDrg8MiBV1_1,
+            7 => StackedDrg512MiBV1_1,
+            8 => StackedDrg32GiBV1_1,
+            9 => StackedDrg64GiBV1_1,
+            _ => bail!("unknown registered seal proof {}", id),
+        };
+
+        Ok(proof)
+    }
+}
+
+impl TryFrom<u64> for RegisteredPoStProof {
+    type Error = Error;
+
+    fn try_from(id: u64) -> Result<Self> {
+        use RegisteredPoStProof::*;
+
+        let proof = match id {
+            0 => StackedDrgWinning2KiBV1,
+            1 => StackedDrgWinning8MiBV1,
+            2 => StackedDrgWinning512MiBV1,
+            3 => StackedDrgWinning32GiBV1,
+            4 => StackedDrgWinning64GiBV1,
+            5 => StackedDrgWindow2KiBV1,
+            6 => StackedDrgWindow8MiBV1,
+            7 => StackedDrgWindow512MiBV1,
+            8 => StackedDrgWindow32GiBV1,
+            9 => StackedDrgWindow64GiBV1,
+            _ => bail!("unknown registered post proof {}", id),
+        };
+
+        Ok(proof)
+    }
+}
+
+/// Returns the sector size of the seal proof `proof`.
+///
+/// The size is checked to have a sector shape, so it can be passed on to `with_shape!` and
+/// `with_sector_shape`.
+pub fn sector_size_for_proof(proof: RegisteredSealProof) -> Result<u64> {
+    use RegisteredSealProof::*;
+
+    let sector_size = match proof {
+        StackedDrg2KiBV1 | StackedDrg2KiBV1_1 => SECTOR_SIZE_2_KIB,
+        StackedDrg8MiBV1 | StackedDrg8MiBV1_1 => SECTOR_SIZE_8_MIB,
+        StackedDrg512MiBV1 | StackedDrg512MiBV1_1 => SECTOR_SIZE_512_MIB,
+        StackedDrg32GiBV1 | StackedDrg32GiBV1_1 => SECTOR_SIZE_32_GIB,
+        StackedDrg64GiBV1 | StackedDrg64GiBV1_1 => SECTOR_SIZE_64_GIB,
+    };
+
+    supported_sector_size(sector_size)
+}
+
+/// Returns the sector size of the PoSt proof `proof`, like `sector_size_for_proof`.
+pub fn sector_size_for_post_proof(proof: RegisteredPoStProof) -> Result<u64> {
+    use RegisteredPoStProof::*;
+
+    let sector_size = match proof {
+        StackedDrgWinning2KiBV1 | StackedDrgWindow2KiBV1 => SECTOR_SIZE_2_KIB,
+        StackedDrgWinning8MiBV1 | StackedDrgWindow8MiBV1 => SECTOR_SIZE_8_MIB,
+        StackedDrgWinning512MiBV1 | StackedDrgWindow512MiBV1 => SECTOR_SIZE_512_MIB,
+        StackedDrgWinning32GiBV1 | StackedDrgWindow32GiBV1 => SECTOR_SIZE_32_GIB,
+        StackedDrgWinning64GiBV1 | StackedDrgWindow64GiBV1 => SECTOR_SIZE_64_GIB,
+    };
+
+    supported_sector_size(sector_size)
+}
+
+fn supported_sector_size(sector_size: u64) -> Result<u64> {
+    if !is_supported_sector_size(sector_size) {
+        return Err(ProofsError::UnsupportedSectorSize(sector_size).into());
+    }
+
+    Ok(sector_size)
+}
@@ -0,0 +1,95 @@
+use std::convert::{TryFrom, TryInto};
+
+use anyhow::{ensure, Context, Result};
+use storage_proofs_core::sector::SectorId;
+
+use crate::types::Commitment;
+
+/// Version of the framing written by `pack_seal_proofs`, changed whenever its layout changes.
+pub const SEAL_PROOF_PACK_VERSION: u32 = 1;
+
+/// The seal proof of a sector together with the sector id and comm_r it proves, as bundled by
+/// `pack_seal_proofs`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SealProofRecord {
+    pub sector_id: SectorId,
+    pub comm_r: Commitment,
+    pub proof: Vec<u8>,
+}
+
+/// Packs the seal proofs of many sectors into a single blob, to be stored on disk and read back
+/// with `unpack_seal_proofs`.
+///
+/// The blob starts with `SEAL_PROOF_PACK_VERSION` and the number of records, followed by every
+/// record as its sector id, comm_r, and proof prefixed with its length. Integers are
+/// little-endian, the version and lengths four bytes, sector ids eight.
+///
+/// Fails if there are more records, or a longer proof, than a length of four bytes holds.
+pub fn pack_seal_proofs(items: &[SealProofRecord]) -> Result<Vec<u8>> {
+    let len = |n: usize| u32::try_from(n).context("too many or too large seal proofs to pack");
+
+    let records_len: usize = items.iter().map(|item| 8 + 32 + 4 + item.proof.len()).sum();
+    let mut bytes = Vec::with_capacity(8 + records_len);
+    bytes.extend_from_slice(&SEAL_PROOF_PACK_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&len(items.len())?.to_le_bytes());
+    for item in items {
+        bytes.extend_from_slice(&u64::from(item.sector_id).to_le_bytes());
+        bytes.extend_from_slice(&item.comm_r);
+        bytes.extend_from_slice(&len(item.proof.len())?.to_le_bytes());
+        bytes.extend_from_slice(&item.proof);
+    }
+
+    Ok(bytes)
+}
+
+/// Unpacks the records packed by `pack_seal_proofs`, in order.
+///
+/// Fails if the blob was packed with another version, or is truncated or followed by trailing
+/// bytes.
+pub fn unpack_seal_proofs(bytes: &[u8]) -> Result<Vec<SealProofRecord>> {
+    let mut reader = bytes;
+
+    let version = u32::from_le_bytes(take(&mut reader, 4, "version")?.try_into()?);
+    ensure!(
+        version == SEAL_PROOF_PACK_VERSION,
+        "unsupported seal proof pack version {}, expected {}",
+        version,
+        SEAL_PROOF_PACK_VERSION
+    );
+    let count = u32::from_le_bytes(take(&mut reader, 4, "record count")?.try_into()?) as usize;
+
+    // Don't trust the count for the allocation, every record takes at least 44 bytes.
+    let mut items = Vec::with_capacity(std::cmp::min(count, reader.len() / 44));
+    for _ in 0..count {
+        let sector_id = u64::from_le_bytes(take(&mut reader, 8, "sector id")?.try_into()?);
+        let comm_r = take(&mut reader, 32, "comm_r")?.try_into()?;
+        let proof_len = u32::from_le_bytes(take(&mut reader, 4, "proof length")?.try_into()?);
+        let proof = take(&mut reader, proof_len as usize, "proof")?.to_vec();
+
+        items.push(SealProofRecord {
+            sector_id: SectorId::from(sector_id),
+            comm_r,
+            proof,
+        });
+    }
+    ensure!(
+        reader.is_empty(),
+        "malformed seal proof pack: {} trailing bytes",
+        reader.len()
+    );
+
+    Ok(items)
+}
+
+/// Splits off the `len` bytes of the next field of a seal proof pack.
+fn take<'a>(reader: &mut &'a [u8], len: usize, field: &str) -> Result<&'a [u8]> {
+    ensure!(
+        reader.len() >= len,
+        "seal proof pack is truncated: missing {}",
+        field
+    );
+    let (bytes, rest) = reader.split_at(len);
+    *reader = rest;
+
+    Ok(bytes)
+}
@@ -1,6 +1,12 @@
+use std::convert::TryFrom;
+
+use anyhow::{Error, Result};
 use storage_proofs_core::api_version::ApiVersion;
 
-use crate::types::{PoRepConfig, PoRepProofPartitions, SectorSize};
+use crate::{
+    constants::sector_shape_arities,
+    types::{PoRepConfig, PoRepProofPartitions, SectorSize},
+};
 
 #[derive(Clone, Copy, Debug)]
 pub struct SectorClass {
@@ -23,6 +29,41 @@ impl From<SectorClass> for PoRepConfig {
             partitions,
             porep_id,
             api_version,
+            challenge_derivation: None,
         }
     }
 }
+
+impl SectorClass {
+    pub fn sector_size(&self) -> u64 {
+        u64::from(self.sector_size)
+    }
+
+    /// Returns the `(base, sub, top)` arities of the sector shape used by this class.
+    pub fn shape_arities(&self) -> Result<(usize, usize, usize)> {
+        sector_shape_arities(self.sector_size())
+    }
+}
+
+/// Creates the class for a supported sector size, using the registered partition count, the
+/// latest api version and an all zero porep id.
+impl TryFrom<u64> for SectorClass {
+    type Error = Error;
+
+    fn try_from(sector_size: u64) -> Result<Self> {
+        let PoRepConfig {
+            sector_size,
+            partitions,
+            porep_id,
+            api_version,
+            ..
+        } = PoRepConfig::for_sector_size(sector_size, [0u8; 32], ApiVersion::V1_1_0)?;
+
+        Ok(SectorClass {
+            sector_size,
+            partitions,
+            porep_id,
+            api_version,
+        })
+    }
+}
@@ -28,3 +28,9 @@ impl From<SectorSize> for u64 {
         x.0
     }
 }
+
+/// Returns the number of unpadded bytes which fit into a sector of `sector_size` bytes once
+/// bit-padded.
+pub fn max_unpadded_bytes_for_sector(sector_size: u64) -> UnpaddedBytesAmount {
+    UnpaddedBytesAmount::from(SectorSize(sector_size))
+}
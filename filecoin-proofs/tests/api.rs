@@ -1,32 +1,79 @@
-use std::collections::BTreeMap;
-use std::fs::{read_dir, remove_file};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fs::{copy, read, read_dir, remove_file, write, File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use std::sync::Once;
-
-use anyhow::Result;
-use bellperson::bls::Fr;
+use std::sync::{Arc, Once};
+use std::thread;
+
+use anyhow::{anyhow, ensure, Result};
+use bellperson::{
+    bls::{Bls12, Fr},
+    groth16::PreparedVerifyingKey,
+    util_cs::bench_cs::BenchCS,
+    Circuit,
+};
 use ff::Field;
-use filecoin_hashers::Hasher;
+use filecoin_hashers::{Domain, Hasher};
+#[cfg(feature = "async")]
+use filecoin_proofs::generate_winning_post_async;
 use filecoin_proofs::{
-    add_piece, clear_cache, compute_comm_d, fauxrep_aux, generate_fallback_sector_challenges,
-    generate_piece_commitment, generate_single_vanilla_proof, generate_window_post,
-    generate_window_post_with_vanilla, generate_winning_post,
-    generate_winning_post_sector_challenge, generate_winning_post_with_vanilla, get_unsealed_range,
-    seal_commit_phase1, seal_commit_phase2, seal_pre_commit_phase1, seal_pre_commit_phase2,
-    validate_cache_for_commit, validate_cache_for_precommit_phase2, verify_seal,
-    verify_window_post, verify_winning_post, Commitment, DefaultTreeDomain, MerkleTreeTrait,
-    PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, PoStConfig, PoStType,
-    PrivateReplicaInfo, ProverId, PublicReplicaInfo, SealPreCommitOutput,
-    SealPreCommitPhase1Output, SectorShape16KiB, SectorShape2KiB, SectorShape32KiB,
-    SectorShape4KiB, SectorSize, UnpaddedByteIndex, UnpaddedBytesAmount, POREP_PARTITIONS,
-    SECTOR_SIZE_16_KIB, SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_KIB, SECTOR_SIZE_4_KIB,
+    add_piece, as_safe_commitment, bundle_window_post_proofs, challenge_partition, clear_cache,
+    clear_parameter_cache, clear_parameter_cache_for_sector_size, compute_challenges,
+    compute_comm_d, create_comm_r, decode_from, derive_replica_id, encode_into,
+    error::Error,
+    estimate_c2_constraints, estimate_memory_usage, estimate_window_post_constraints,
+    expected_cache_file_sizes, extract_lc_leaves, extract_leaves, fauxrep_aux, gen_inclusion_proof,
+    generate_fallback_sector_challenges, generate_piece_commitment, generate_single_vanilla_proof,
+    generate_window_post, generate_window_post_with_faults,
+    generate_window_post_with_thread_budget, generate_window_post_with_vanilla,
+    generate_winning_post, generate_winning_post_sector_challenge,
+    generate_winning_post_with_vanilla, get_unsealed_range, inspect_sealed_sector, layer_checksums,
+    layers, pack_seal_proofs,
+    param::read_verifying_key,
+    parameters::{public_params, winning_post_setup_params},
+    parents_for_node, porep_graph, porep_minimum_challenges, porep_partitions, rebuild_tree_r_last,
+    rows_to_discard, seal_all, seal_commit_phase1, seal_commit_phase2, seal_commit_phase2_batch,
+    seal_commit_phase2_check, seal_pre_commit_phase1, seal_pre_commit_phase1_with_graph,
+    seal_pre_commit_phase1_with_progress, seal_pre_commit_phase1_with_write_config,
+    seal_pre_commit_phase2, select_winning_sector, supported_sector_sizes, unpack_seal_proofs,
+    unseal_range, validate_cache_dir, validate_cache_for_commit,
+    validate_cache_for_precommit_phase2, validate_replica, validate_seal_randomness,
+    verify_comm_d_for_file, verify_comm_r, verify_inclusion_proof, verify_layer_checksums,
+    verify_seal, verify_seal_from_vk_bytes, verify_seal_with_param_version, verify_seal_with_vk,
+    verify_seals, verify_window_post, verify_window_post_bundle, verify_window_post_with_resolver,
+    verify_window_post_with_vk, verify_winning_post, verify_winning_post_with_vk,
+    window_post_challenged_leaves, window_post_public_inputs, winning_post_public_inputs, CommC,
+    CommD, CommR, Commitment, DefaultBinaryTree, DefaultOctTree, DefaultPieceHasher,
+    DefaultTreeDomain, DefaultTreeHasher, LabelControl, MerkleTreeTrait, PaddedBytesAmount,
+    PieceInfo, PoRepConfig, PoRepProofPartitions, PoStConfig, PoStType, PrivateReplicaInfo,
+    ProverId, PublicReplicaInfo, ReplicaWriteConfig, SealCommitPhase1Output, SealPhase,
+    SealPreCommitOutput, SealPreCommitPhase1Output, SealProofRecord, SealVerifyInput,
+    SectorShape16KiB, SectorShape2KiB, SectorShape32KiB, SectorShape4KiB, SectorShape8MiB,
+    StoreConfig, UnpaddedByteIndex, UnpaddedBytesAmount, WindowPoStPublicInputs, DRG_DEGREE,
+    EXP_DEGREE, SECTOR_SIZE_16_KIB, SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_GIB, SECTOR_SIZE_32_KIB,
+    SECTOR_SIZE_4_KIB, SECTOR_SIZE_64_GIB, SECTOR_SIZE_8_MIB, SINGLE_PARTITION_PROOF_LEN,
     WINDOW_POST_CHALLENGE_COUNT, WINDOW_POST_SECTOR_COUNT, WINNING_POST_CHALLENGE_COUNT,
     WINNING_POST_SECTOR_COUNT,
 };
+#[cfg(feature = "test-helpers")]
+use filecoin_proofs::{generate_test_sealed_sector, generate_window_post_with_randomness};
+use merkletree::merkle::{get_merkle_tree_cache_size, get_merkle_tree_len};
 use rand::{random, Rng, SeedableRng};
 use rand_xorshift::XorShiftRng;
-use storage_proofs_core::{api_version::ApiVersion, is_legacy_porep_id, sector::SectorId};
+use storage_proofs_core::{
+    api_version::ApiVersion,
+    cache_key::CacheKey,
+    compound_proof::CompoundProof,
+    is_legacy_porep_id,
+    merkle::create_base_merkle_tree,
+    parameter_cache::VERSION,
+    sector::SectorId,
+    util::{default_rows_to_discard, NODE_SIZE},
+};
+use storage_proofs_porep::stacked::{StackedCompound, StackedDrg};
+use storage_proofs_post::fallback::{self, FallbackPoStCompound, PublicSector};
 use tempfile::{tempdir, NamedTempFile, TempDir};
 
 // Use a fixed PoRep ID, so that the parents cache can be re-used between some tests.
@@ -188,9 +235,7 @@ fn seal_lifecycle<Tree: 'static + MerkleTreeTrait>(
     api_version: ApiVersion,
 ) -> Result<()> {
     let rng = &mut XorShiftRng::from_seed(TEST_SEED);
-    let prover_fr: DefaultTreeDomain = Fr::random(rng).into();
-    let mut prover_id = [0u8; 32];
-    prover_id.copy_from_slice(AsRef::<[u8]>::as_ref(&prover_fr));
+    let prover_id = random_prover_id(rng);
 
     create_seal::<_, Tree>(rng, sector_size, prover_id, false, porep_id, api_version)?;
     Ok(())
@@ -230,6 +275,1256 @@ fn clear_cache_dir_keep_data_layer(cache_dir: &TempDir) {
     }
 }
 
+#[test]
+#[ignore]
+fn test_seal_commit_phase2_batch_2kib() -> Result<()> {
+    init_logger();
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let porep_id_v1_1: u64 = 5; // This is a RegisteredSealProof value
+    let mut porep_id = [0u8; 32];
+    porep_id[..8].copy_from_slice(&porep_id_v1_1.to_le_bytes());
+    let config = porep_config(SECTOR_SIZE_2_KIB, porep_id, ApiVersion::V1_1_0);
+
+    let mut sectors = Vec::new();
+    let mut phase1_outputs = Vec::new();
+    for _ in 0..2 {
+        let (sector, phase1_output) = seal_2kib_to_commit_phase1(rng, config, prover_id, None)?;
+        sectors.push(sector);
+        phase1_outputs.push(phase1_output);
+    }
+
+    let sector_ids: Vec<SectorId> = sectors.iter().map(|sector| sector.sector_id).collect();
+    let outputs =
+        seal_commit_phase2_batch(&[config, config], phase1_outputs, prover_id, &sector_ids)?;
+    assert_eq!(outputs.len(), sectors.len());
+
+    for (output, sector) in outputs.iter().zip(sectors) {
+        let verified = verify_seal::<SectorShape2KiB>(
+            config,
+            sector.pre_commit_output.comm_r,
+            sector.pre_commit_output.comm_d,
+            prover_id,
+            sector.sector_id,
+            sector.ticket,
+            sector.seed,
+            &output.proof,
+        )?;
+        assert!(verified, "failed to verify batched seal");
+    }
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_seal_commit_phase2_check_2kib() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let (_sector, commit_phase1_output) = seal_2kib_to_commit_phase1(rng, config, prover_id, None)?;
+
+    seal_commit_phase2_check(config, commit_phase1_output.clone())?;
+
+    // A comm_r which is a valid field element, but not the commitment of the replica.
+    let mut corrupted = commit_phase1_output;
+    corrupted.comm_r[0] ^= 1;
+    let err = seal_commit_phase2_check(config, corrupted)
+        .expect_err("corrupted witness was not detected");
+    assert!(
+        err.to_string().contains("is not satisfied"),
+        "unexpected error: {:?}",
+        err
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_seal_pre_commit_phase1_with_shared_graph_2kib() -> Result<()> {
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let graph = porep_graph::<SectorShape2KiB>(config)?;
+
+    let sectors: Vec<_> = (0..2u8)
+        .map(|i| {
+            let graph = Arc::clone(&graph);
+            thread::spawn(move || -> Result<()> {
+                let prover_id = [i; 32];
+                let sector_id = SectorId::from(u64::from(i));
+                let ticket = [i + 1; 32];
+                let (mut piece_file, _) = generate_piece_file(SECTOR_SIZE_2_KIB)?;
+                let (staged_sector_file, piece_infos) = stage_sector(config, &mut piece_file)?;
+
+                let shared_cache_dir = tempdir()?;
+                let shared_sealed_file = NamedTempFile::new()?;
+                let shared = seal_pre_commit_phase1_with_graph::<_, _, _, SectorShape2KiB>(
+                    config,
+                    &graph,
+                    shared_cache_dir.path(),
+                    staged_sector_file.path(),
+                    shared_sealed_file.path(),
+                    prover_id,
+                    sector_id,
+                    ticket,
+                    &piece_infos,
+                )?;
+
+                let cache_dir = tempdir()?;
+                let sealed_file = NamedTempFile::new()?;
+                let independent = seal_pre_commit_phase1::<_, _, _, SectorShape2KiB>(
+                    config,
+                    cache_dir.path(),
+                    staged_sector_file.path(),
+                    sealed_file.path(),
+                    prover_id,
+                    sector_id,
+                    ticket,
+                    &piece_infos,
+                )?;
+
+                assert_eq!(shared.comm_d, independent.comm_d);
+                assert_eq!(shared.labels.labels.len(), independent.labels.labels.len());
+                for (shared, independent) in
+                    shared.labels.labels.iter().zip(&independent.labels.labels)
+                {
+                    assert_eq!(
+                        read(StoreConfig::data_path(&shared.path, &shared.id))?,
+                        read(StoreConfig::data_path(&independent.path, &independent.id))?,
+                        "labels of {} differ",
+                        shared.id
+                    );
+                }
+
+                Ok(())
+            })
+        })
+        .collect();
+    for sector in sectors {
+        sector.join().expect("labeling thread panicked")?;
+    }
+
+    // A graph of another porep id is rejected, even though it has the same size.
+    let other_config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_0_0,
+        ApiVersion::V1_0_0,
+    );
+    assert!(graph.is_for(config));
+    assert!(!graph.is_for(other_config));
+    let (mut piece_file, _) = generate_piece_file(SECTOR_SIZE_2_KIB)?;
+    let (staged_sector_file, piece_infos) = stage_sector(other_config, &mut piece_file)?;
+    let cache_dir = tempdir()?;
+    let sealed_file = NamedTempFile::new()?;
+    assert!(
+        seal_pre_commit_phase1_with_graph::<_, _, _, SectorShape2KiB>(
+            other_config,
+            &graph,
+            cache_dir.path(),
+            staged_sector_file.path(),
+            sealed_file.path(),
+            [0; 32],
+            SectorId::from(0),
+            [1; 32],
+            &piece_infos,
+        )
+        .is_err()
+    );
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_seal_with_tree_dir_2kib() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let tree_dir = tempdir()?;
+    let (sector, commit_phase1_output) =
+        seal_2kib_to_commit_phase1(rng, config, prover_id, Some(tree_dir.path()))?;
+    assert_eq!(commit_phase1_output.comm_r, sector.pre_commit_output.comm_r);
+
+    for key in &[CacheKey::CommCTree, CacheKey::CommRLastTree] {
+        let key = key.to_string();
+        assert!(StoreConfig::data_path(tree_dir.path(), &key).exists());
+        assert!(!StoreConfig::data_path(sector.cache_dir.path(), &key).exists());
+    }
+    validate_cache_for_commit::<_, _, SectorShape2KiB>(
+        sector.cache_dir.path(),
+        sector.sealed_sector_file.path(),
+        Some(tree_dir.path()),
+    )?;
+    assert!(validate_cache_for_commit::<_, _, SectorShape2KiB>(
+        sector.cache_dir.path(),
+        sector.sealed_sector_file.path(),
+        None,
+    )
+    .is_err());
+    validate_cache_dir::<_, SectorShape2KiB>(
+        config,
+        sector.cache_dir.path(),
+        Some(tree_dir.path()),
+    )?;
+    assert!(
+        validate_cache_dir::<_, SectorShape2KiB>(config, sector.cache_dir.path(), None).is_err()
+    );
+
+    // PoSt reads tree_r_last from the tree dir.
+    let replica_info = PrivateReplicaInfo::<SectorShape2KiB>::new_with_tree_dir(
+        sector.sealed_sector_file.path().to_path_buf(),
+        sector.pre_commit_output.comm_r,
+        sector.cache_dir.path().to_path_buf(),
+        Some(tree_dir.path().to_path_buf()),
+    )?;
+    assert_eq!(
+        replica_info.merkle_tree(config.sector_size)?.root(),
+        replica_info.safe_comm_r_last()
+    );
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_seal_commit_phase1_output_encoding_2kib() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let (sector, commit_phase1_output) = seal_2kib_to_commit_phase1(rng, config, prover_id, None)?;
+
+    // Both encodings round-trip exactly.
+    let bytes = commit_phase1_output.to_bytes()?;
+    let decoded = SealCommitPhase1Output::<SectorShape2KiB>::from_bytes(&bytes)?;
+    assert_eq!(decoded.to_bytes()?, bytes);
+    let json = serde_json::to_string(&commit_phase1_output)?;
+    let from_json: SealCommitPhase1Output<SectorShape2KiB> = serde_json::from_str(&json)?;
+    assert_eq!(from_json.to_bytes()?, bytes);
+
+    let mut other_version = bytes.clone();
+    other_version[0] ^= 1;
+    assert!(SealCommitPhase1Output::<SectorShape2KiB>::from_bytes(&other_version).is_err());
+
+    let commit_output = seal_commit_phase2(config, decoded, prover_id, sector.sector_id)?;
+    assert!(verify_seal::<SectorShape2KiB>(
+        config,
+        sector.pre_commit_output.comm_r,
+        sector.pre_commit_output.comm_d,
+        prover_id,
+        sector.sector_id,
+        sector.ticket,
+        sector.seed,
+        &commit_output.proof,
+    )?);
+
+    Ok(())
+}
+
+/// Records the names of all spans created while it is the default subscriber.
+#[cfg(feature = "tracing-spans")]
+#[derive(Default)]
+struct SpanRecorder {
+    names: std::sync::Arc<std::sync::Mutex<Vec<&'static str>>>,
+}
+
+#[cfg(feature = "tracing-spans")]
+impl tracing::Subscriber for SpanRecorder {
+    fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        let mut names = self.names.lock().expect("failed to lock span names");
+        names.push(span.metadata().name());
+        tracing::span::Id::from_u64(names.len() as u64)
+    }
+
+    fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+    fn event(&self, _event: &tracing::Event<'_>) {}
+
+    fn enter(&self, _span: &tracing::span::Id) {}
+
+    fn exit(&self, _span: &tracing::span::Id) {}
+}
+
+#[cfg(feature = "tracing-spans")]
+#[test]
+#[ignore]
+fn test_seal_spans_2kib() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let recorder = SpanRecorder::default();
+    let names = recorder.names.clone();
+    tracing::subscriber::with_default(recorder, || -> Result<()> {
+        let (sector, commit_phase1_output) =
+            seal_2kib_to_commit_phase1(rng, config, prover_id, None)?;
+        seal_commit_phase2(config, commit_phase1_output, prover_id, sector.sector_id)?;
+
+        Ok(())
+    })?;
+
+    let names = names.lock().expect("failed to lock span names");
+    for expected in &[
+        "seal_pre_commit_phase1",
+        "build_tree_d",
+        "labeling",
+        "label_layer",
+        "seal_pre_commit_phase2",
+        "build_tree_c",
+        "build_tree_r_last",
+        "seal_commit_phase1",
+        "prove_partition",
+        "seal_commit_phase2",
+        "snark_proof",
+    ] {
+        assert!(names.contains(expected), "missing span {}", expected);
+    }
+
+    let layers = names.iter().filter(|name| **name == "label_layer").count();
+    assert_eq!(layers, config.setup_params()?.layer_challenges.layers());
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_seal_all_2kib_base_8() -> Result<()> {
+    init_logger();
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let ticket = rng.gen();
+    let seed = rng.gen();
+    let sector_id: SectorId = rng.gen::<u64>().into();
+
+    let (mut piece_file, _) = generate_piece_file(SECTOR_SIZE_2_KIB)?;
+    let (staged_sector_file, piece_infos) = stage_sector(config, &mut piece_file)?;
+
+    let sealed_sector_file = NamedTempFile::new()?;
+    let cache_dir = tempdir()?;
+
+    let output = seal_all::<_, _, _, SectorShape2KiB>(
+        config,
+        cache_dir.path(),
+        staged_sector_file.path(),
+        sealed_sector_file.path(),
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        &piece_infos,
+        false,
+    )?;
+    assert_eq!(
+        output.comm_d,
+        compute_comm_d(config.sector_size, &piece_infos)?
+    );
+
+    let verified = verify_seal::<SectorShape2KiB>(
+        config,
+        output.comm_r,
+        output.comm_d,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        &output.proof,
+    )?;
+    assert!(verified, "failed to verify seal");
+    assert_eq!(output.proof.len(), SINGLE_PARTITION_PROOF_LEN);
+
+    let err = verify_seal::<SectorShape2KiB>(
+        config,
+        output.comm_r,
+        output.comm_d,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        &output.proof[1..],
+    )
+    .expect_err("truncated proof was not rejected");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::MalformedProof { .. })
+    ));
+
+    // The layers and trees only needed for the proof are gone.
+    assert!(validate_cache_for_commit::<_, _, SectorShape2KiB>(
+        cache_dir.path(),
+        sealed_sector_file.path(),
+        None,
+    )
+    .is_err());
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_verify_seals_2kib_base_8() -> Result<()> {
+    init_logger();
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let ticket = rng.gen();
+    let seed = rng.gen();
+    let sector_id: SectorId = rng.gen::<u64>().into();
+
+    let (mut piece_file, _) = generate_piece_file(SECTOR_SIZE_2_KIB)?;
+    let (staged_sector_file, piece_infos) = stage_sector(config, &mut piece_file)?;
+
+    let sealed_sector_file = NamedTempFile::new()?;
+    let cache_dir = tempdir()?;
+    let output = seal_all::<_, _, _, SectorShape2KiB>(
+        config,
+        cache_dir.path(),
+        staged_sector_file.path(),
+        sealed_sector_file.path(),
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        &piece_infos,
+        false,
+    )?;
+
+    let valid = SealVerifyInput {
+        comm_r: output.comm_r,
+        comm_d: output.comm_d,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        proof: output.proof,
+    };
+    let mut wrong_seed = valid.clone();
+    wrong_seed.seed[0] ^= 1;
+    let mut truncated = valid.clone();
+    truncated.proof.pop();
+
+    let items = vec![
+        (config, valid.clone()),
+        (config, wrong_seed),
+        (config, truncated),
+        (config, valid),
+    ];
+    assert_eq!(verify_seals(&items)?, vec![true, false, false, true]);
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_validate_cache_dir_truncated_tree_c_2kib() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let (mut piece_file, _) = generate_piece_file(SECTOR_SIZE_2_KIB)?;
+    let sealed_sector_file = NamedTempFile::new()?;
+    let cache_dir = tempdir()?;
+
+    let (_, phase1_output) = run_seal_pre_commit_phase1::<SectorShape2KiB>(
+        config,
+        prover_id,
+        rng.gen::<u64>().into(),
+        rng.gen(),
+        &cache_dir,
+        &mut piece_file,
+        &sealed_sector_file,
+    )?;
+    seal_pre_commit_phase2(
+        config,
+        phase1_output,
+        cache_dir.path(),
+        sealed_sector_file.path(),
+        None,
+    )?;
+    validate_cache_dir::<_, SectorShape2KiB>(config, cache_dir.path(), None)?;
+
+    let tree_c_path = StoreConfig::data_path(cache_dir.path(), &CacheKey::CommCTree.to_string());
+    let tree_c = OpenOptions::new().write(true).open(&tree_c_path)?;
+    let len = tree_c.metadata()?.len();
+    tree_c.set_len(len - 1)?;
+
+    let err = validate_cache_dir::<_, SectorShape2KiB>(config, cache_dir.path(), None)
+        .expect_err("truncated tree_c was not detected");
+    match err.downcast_ref::<Error>() {
+        Some(Error::CacheFileCorrupt {
+            path,
+            expected,
+            actual,
+        }) => {
+            assert_eq!(path, &tree_c_path);
+            assert_eq!(*expected, len);
+            assert_eq!(*actual, len - 1);
+        }
+        _ => panic!("unexpected error: {:?}", err),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_replica() -> Result<()> {
+    let replica = NamedTempFile::new()?;
+
+    replica.as_file().set_len(SECTOR_SIZE_2_KIB)?;
+    validate_replica(replica.path(), SECTOR_SIZE_2_KIB)?;
+
+    for len in &[SECTOR_SIZE_2_KIB - 1, SECTOR_SIZE_2_KIB + 1] {
+        replica.as_file().set_len(*len)?;
+        let err = validate_replica(replica.path(), SECTOR_SIZE_2_KIB)
+            .expect_err("replica of the wrong size was not detected");
+        match err.downcast_ref::<Error>() {
+            Some(Error::ReplicaSizeMismatch { expected, actual }) => {
+                assert_eq!(*expected, SECTOR_SIZE_2_KIB);
+                assert_eq!(actual, len);
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_seal_with_incompatible_param_version() {
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let proof = vec![0u8; SINGLE_PARTITION_PROOF_LEN];
+
+    let err = verify_seal_with_param_version::<SectorShape2KiB>(
+        config,
+        VERSION + 1,
+        [1u8; 32],
+        [1u8; 32],
+        [0u8; 32],
+        SectorId::from(0),
+        [0u8; 32],
+        [0u8; 32],
+        &proof,
+    )
+    .expect_err("verified with an incompatible parameter version");
+    assert!(
+        err.to_string().contains("does not share the circuits"),
+        "unexpected error: {:?}",
+        err
+    );
+}
+
+fn seal_proof_records() -> Vec<SealProofRecord> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    [1usize, 10, 0]
+        .iter()
+        .enumerate()
+        .map(|(i, partitions)| SealProofRecord {
+            sector_id: SectorId::from(i as u64 * 1000 + 7),
+            comm_r: rng.gen(),
+            proof: (0..partitions * SINGLE_PARTITION_PROOF_LEN)
+                .map(|_| rng.gen())
+                .collect(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_pack_seal_proofs() -> Result<()> {
+    let records = seal_proof_records();
+
+    let packed = pack_seal_proofs(&records)?;
+    assert_eq!(unpack_seal_proofs(&packed)?, records);
+    assert!(unpack_seal_proofs(&pack_seal_proofs(&[])?)?.is_empty());
+
+    let mut other_version = packed.clone();
+    other_version[0] ^= 1;
+    assert!(unpack_seal_proofs(&other_version).is_err());
+
+    let mut trailing = packed;
+    trailing.push(0);
+    assert!(unpack_seal_proofs(&trailing).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_unpack_truncated_seal_proofs() {
+    let packed = pack_seal_proofs(&seal_proof_records()).expect("failed to pack seal proofs");
+
+    for len in 0..packed.len() {
+        let err =
+            unpack_seal_proofs(&packed[..len]).expect_err("truncated seal proof pack was unpacked");
+        assert!(
+            err.to_string().contains("truncated"),
+            "unexpected error at length {}: {:?}",
+            len,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_layer_checksums() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let cache_dir = tempdir()?;
+    let layer_path =
+        |layer: usize| StoreConfig::data_path(cache_dir.path(), &CacheKey::label_layer(layer));
+
+    let layer_count = layers(SECTOR_SIZE_2_KIB)?;
+    for layer in 1..=layer_count {
+        let labels: Vec<u8> = (0..SECTOR_SIZE_2_KIB).map(|_| rng.gen()).collect();
+        write(layer_path(layer), labels)?;
+    }
+
+    let checksums = layer_checksums(cache_dir.path(), SECTOR_SIZE_2_KIB)?;
+    assert_eq!(checksums.len(), layer_count);
+    verify_layer_checksums(cache_dir.path(), SECTOR_SIZE_2_KIB, &checksums)?;
+
+    // Alter a single byte of the last layer.
+    let mut labels = read(layer_path(layer_count))?;
+    labels[100] ^= 1;
+    write(layer_path(layer_count), labels)?;
+
+    let altered = layer_checksums(cache_dir.path(), SECTOR_SIZE_2_KIB)?;
+    assert_eq!(altered[..layer_count - 1], checksums[..layer_count - 1]);
+    assert_ne!(altered[layer_count - 1], checksums[layer_count - 1]);
+
+    let err = verify_layer_checksums(cache_dir.path(), SECTOR_SIZE_2_KIB, &checksums)
+        .expect_err("altered layer was not detected");
+    match err.downcast_ref::<Error>() {
+        Some(Error::LayerChecksumMismatch { layer }) => assert_eq!(*layer, layer_count),
+        _ => panic!("unexpected error: {:?}", err),
+    }
+
+    remove_file(layer_path(1))?;
+    let err = layer_checksums(cache_dir.path(), SECTOR_SIZE_2_KIB)
+        .expect_err("missing layer was not detected");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::MissingCacheFile(_))
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "test-helpers")]
+#[test]
+fn test_generate_test_sealed_sector() -> Result<()> {
+    let (sealed_path, comm_r, comm_d) = generate_test_sealed_sector(SECTOR_SIZE_2_KIB, 7)?;
+    let (other_sealed_path, other_comm_r, other_comm_d) =
+        generate_test_sealed_sector(SECTOR_SIZE_2_KIB, 7)?;
+    assert_ne!(sealed_path, other_sealed_path);
+    assert_eq!(comm_r, other_comm_r);
+    assert_eq!(comm_d, other_comm_d);
+    assert_eq!(read(&sealed_path)?, read(&other_sealed_path)?);
+    assert_eq!(
+        comm_d.as_bytes(),
+        &filecoin_proofs::zero_comm_d(SECTOR_SIZE_2_KIB)?
+    );
+
+    let (seed_sealed_path, seed_comm_r, _) = generate_test_sealed_sector(SECTOR_SIZE_2_KIB, 8)?;
+    assert_ne!(comm_r, seed_comm_r);
+
+    assert!(generate_test_sealed_sector(SECTOR_SIZE_32_GIB, 7).is_err());
+
+    for path in &[sealed_path, other_sealed_path, seed_sealed_path] {
+        std::fs::remove_dir_all(path.parent().expect("sealed sector has a parent"))?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_seal_malformed_proof() {
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let proof = vec![0u8; SINGLE_PARTITION_PROOF_LEN - 1];
+
+    // The length is checked before the verifying key is loaded, so no parameters are needed.
+    let err = verify_seal::<SectorShape2KiB>(
+        config,
+        [1; 32],
+        [2; 32],
+        [3; 32],
+        SectorId::from(4),
+        [5; 32],
+        [6; 32],
+        &proof,
+    )
+    .expect_err("truncated proof was not rejected");
+    match err.downcast_ref::<Error>() {
+        Some(Error::MalformedProof {
+            expected_len,
+            actual_len,
+        }) => {
+            assert_eq!(*expected_len, SINGLE_PARTITION_PROOF_LEN);
+            assert_eq!(*actual_len, SINGLE_PARTITION_PROOF_LEN - 1);
+        }
+        _ => panic!("unexpected error: {:?}", err),
+    }
+}
+
+#[test]
+fn test_estimate_window_post_constraints() -> Result<()> {
+    // The figures documented for `WINDOW_POST_SECTOR_COUNT`.
+    assert_eq!(
+        estimate_window_post_constraints(SECTOR_SIZE_32_GIB)?,
+        125_279_217
+    );
+    assert_eq!(
+        estimate_window_post_constraints(SECTOR_SIZE_64_GIB)?,
+        129_887_900
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_estimate_c2_constraints_2kib() -> Result<()> {
+    let partitions = usize::from(porep_partitions(SECTOR_SIZE_2_KIB)?);
+    let public_params = public_params::<SectorShape2KiB>(
+        PaddedBytesAmount(SECTOR_SIZE_2_KIB),
+        partitions,
+        [0; 32],
+        ApiVersion::V1_1_0,
+    )?;
+
+    let mut cs = BenchCS::<Bls12>::new();
+    <StackedCompound<SectorShape2KiB, DefaultPieceHasher> as CompoundProof<
+        StackedDrg<'_, SectorShape2KiB, DefaultPieceHasher>,
+        _,
+    >>::blank_circuit(&public_params)
+    .synthesize(&mut cs)?;
+
+    assert_eq!(
+        estimate_c2_constraints(SECTOR_SIZE_2_KIB)?,
+        (cs.num_constraints() * partitions) as u64
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_estimate_memory_usage() -> Result<()> {
+    let estimates = supported_sector_sizes()
+        .map(|sector_size| estimate_memory_usage(sector_size, SealPhase::PreCommit1))
+        .collect::<Result<Vec<_>>>()?;
+    for pair in estimates.windows(2) {
+        assert!(pair[0] < pair[1], "{:?} doesn't increase", estimates);
+    }
+    // PreCommit1 holds at least two layers.
+    assert!(
+        estimate_memory_usage(SECTOR_SIZE_32_GIB, SealPhase::PreCommit1)? > 2 * SECTOR_SIZE_32_GIB
+    );
+
+    for phase in &[
+        SealPhase::PreCommit2,
+        SealPhase::Commit1,
+        SealPhase::Commit2,
+    ] {
+        assert!(estimate_memory_usage(SECTOR_SIZE_2_KIB, *phase)? > 0);
+    }
+    assert!(estimate_memory_usage(3 << 10, SealPhase::PreCommit1).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_compute_challenges() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let replica_id: DefaultTreeDomain = Fr::random(rng).into();
+    let seed: [u8; 32] = rng.gen();
+
+    for &sector_size in &[SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_GIB] {
+        let config = porep_config(sector_size, ARBITRARY_POREP_ID_V1_1_0, ApiVersion::V1_1_0);
+        let partitions = usize::from(PoRepProofPartitions::from(config));
+        let minimum_challenges = porep_minimum_challenges(sector_size)? as usize;
+        let nodes = sector_size as usize / 32;
+
+        let mut all_challenges = Vec::new();
+        for partition in 0..partitions {
+            let challenges = compute_challenges(&config, &replica_id, &seed, partition)?;
+            assert_eq!(
+                challenges,
+                compute_challenges(&config, &replica_id, &seed, partition)?,
+                "challenges are not deterministic"
+            );
+            assert!(challenges.iter().all(|&c| c > 0 && c < nodes));
+            all_challenges.push(challenges);
+        }
+
+        // Every partition gets the same number of challenges, rounded up so that the total
+        // reaches the minimum.
+        let per_partition = (minimum_challenges + partitions - 1) / partitions;
+        assert!(all_challenges.iter().all(|c| c.len() == per_partition));
+        assert!(compute_challenges(&config, &replica_id, &seed, partitions).is_err());
+    }
+
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    assert_eq!(
+        compute_challenges(&config, &replica_id, &seed, 0)?.len(),
+        porep_minimum_challenges(SECTOR_SIZE_2_KIB)? as usize
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_challenge_partition_32gib() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let replica_id: DefaultTreeDomain = Fr::random(rng).into();
+    let seed: [u8; 32] = rng.gen();
+
+    let config = porep_config(
+        SECTOR_SIZE_32_GIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let partitions = usize::from(PoRepProofPartitions::from(config));
+    assert_eq!(partitions, 10);
+    let per_partition = compute_challenges(&config, &replica_id, &seed, 0)?.len();
+
+    assert_eq!(challenge_partition(&config, 0)?, (0, 0));
+    for partition in 1..partitions {
+        let first = partition * per_partition;
+        assert_eq!(
+            challenge_partition(&config, first - 1)?,
+            (partition - 1, per_partition - 1)
+        );
+        assert_eq!(challenge_partition(&config, first)?, (partition, 0));
+    }
+
+    let total = partitions * per_partition;
+    assert_eq!(
+        challenge_partition(&config, total - 1)?,
+        (partitions - 1, per_partition - 1)
+    );
+    assert!(challenge_partition(&config, total).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_comm_d_for_file() -> Result<()> {
+    let sector_size = SECTOR_SIZE_2_KIB;
+
+    // A piece filling half of the sector, the rest is committed as zeros.
+    let (mut piece_file, _) = generate_piece_file(sector_size / 2)?;
+    let config = porep_config(sector_size, ARBITRARY_POREP_ID_V1_1_0, ApiVersion::V1_1_0);
+    let (staged_sector_file, piece_infos) = stage_sector(config, &mut piece_file)?;
+    let comm_d = CommD::try_from(compute_comm_d(config.sector_size, &piece_infos)?)?;
+
+    assert!(verify_comm_d_for_file(
+        staged_sector_file.path(),
+        sector_size,
+        comm_d
+    )?);
+
+    let mut data = read(staged_sector_file.path())?;
+    data[0] ^= 1;
+    let corrupted_file = NamedTempFile::new()?;
+    write(corrupted_file.path(), &data)?;
+    assert!(!verify_comm_d_for_file(
+        corrupted_file.path(),
+        sector_size,
+        comm_d
+    )?);
+
+    // More data than fits into the sector.
+    assert!(verify_comm_d_for_file(staged_sector_file.path(), sector_size / 4, comm_d).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_comm_r() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let (_, replica, comm_r, cache_dir) = create_fake_seal::<_, SectorShape2KiB>(
+        rng,
+        SECTOR_SIZE_2_KIB,
+        &ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    )?;
+    let replica_info = PrivateReplicaInfo::<SectorShape2KiB>::new(
+        replica.path().to_path_buf(),
+        comm_r,
+        cache_dir.path().to_path_buf(),
+    )?;
+
+    let mut comm_c = [0u8; 32];
+    comm_c.copy_from_slice(&replica_info.safe_comm_c().into_bytes());
+    let mut comm_r_last = [0u8; 32];
+    comm_r_last.copy_from_slice(&replica_info.safe_comm_r_last().into_bytes());
+
+    assert!(verify_comm_r(comm_r, comm_c, comm_r_last));
+    assert!(!verify_comm_r(comm_r, comm_r_last, comm_c));
+
+    let mut corrupted = comm_r;
+    corrupted[0] ^= 1;
+    assert!(!verify_comm_r(corrupted, comm_c, comm_r_last));
+
+    let mut corrupted = comm_c;
+    corrupted[0] ^= 1;
+    assert!(!verify_comm_r(comm_r, corrupted, comm_r_last));
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_seal_randomness() {
+    let mut ticket = [0xff; 32];
+    ticket[31] &= 0x3f;
+    let seed = [7; 32];
+    validate_seal_randomness(&ticket, &seed).expect("masked randomness was rejected");
+
+    // All 0xff exceeds the modulus.
+    let err = validate_seal_randomness(&[0xff; 32], &seed).expect_err("invalid ticket accepted");
+    assert!(
+        err.to_string().contains("ticket"),
+        "unexpected error: {:?}",
+        err
+    );
+    let err = validate_seal_randomness(&ticket, &[0xff; 32]).expect_err("invalid seed accepted");
+    assert!(
+        err.to_string().contains("seed"),
+        "unexpected error: {:?}",
+        err
+    );
+}
+
+#[test]
+fn test_create_comm_r() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let mut comm_c = [0u8; 32];
+    comm_c.copy_from_slice(&DefaultTreeDomain::random(rng).into_bytes());
+    let mut comm_r_last = [0u8; 32];
+    comm_r_last.copy_from_slice(&DefaultTreeDomain::random(rng).into_bytes());
+
+    let comm_r = create_comm_r(CommC::try_from(comm_c)?, comm_r_last)?;
+    assert!(verify_comm_r(comm_r.into(), comm_c, comm_r_last));
+    assert!(!verify_comm_r(comm_r.into(), comm_r_last, comm_c));
+
+    // comm_r_last is not a field element.
+    assert!(create_comm_r(CommC::try_from(comm_c)?, [0xff; 32]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_empty_sector_update_2kib() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let nodes = SECTOR_SIZE_2_KIB as usize / NODE_SIZE;
+    let mut random_commitment = || {
+        let mut commitment = [0u8; 32];
+        commitment.copy_from_slice(&DefaultTreeDomain::random(rng).into_bytes());
+        commitment
+    };
+    let new_data: Vec<u8> = (0..nodes).flat_map(|_| random_commitment()).collect();
+    let old_replica: Vec<u8> = (0..nodes).flat_map(|_| random_commitment()).collect();
+    let comm_c = random_commitment();
+    let key = random_commitment();
+    let other_key = random_commitment();
+
+    let mut new_replica = old_replica.clone();
+    let comm_r_new = encode_into::<SectorShape2KiB>(&new_data, &mut new_replica, comm_c, key)?;
+    assert_ne!(new_replica, old_replica);
+
+    // comm_r commits to the tree_r_last of the updated replica.
+    let tree_r_last = create_base_merkle_tree::<DefaultOctTree>(None, nodes, &new_replica)?;
+    let mut comm_r_last = [0u8; 32];
+    comm_r_last.copy_from_slice(&tree_r_last.root().into_bytes());
+    assert!(verify_comm_r(comm_r_new.into(), comm_c, comm_r_last));
+
+    let mut encoded_again = old_replica.clone();
+    assert_eq!(
+        encode_into::<SectorShape2KiB>(&new_data, &mut encoded_again, comm_c, key)?,
+        comm_r_new
+    );
+    assert_eq!(encoded_again, new_replica);
+
+    // Data that isn't a field element is rejected without touching the replica.
+    let mut invalid_data = new_data.clone();
+    invalid_data[(nodes - 1) * NODE_SIZE..].copy_from_slice(&[0xff; NODE_SIZE]);
+    let mut untouched = old_replica.clone();
+    assert!(encode_into::<SectorShape2KiB>(&invalid_data, &mut untouched, comm_c, key).is_err());
+    assert_eq!(untouched, old_replica);
+
+    assert_eq!(
+        decode_from::<SectorShape2KiB>(&new_replica, &old_replica, key)?,
+        new_data
+    );
+    assert_ne!(
+        decode_from::<SectorShape2KiB>(&new_replica, &old_replica, other_key)?,
+        new_data
+    );
+    assert!(decode_from::<SectorShape2KiB>(&new_replica, &old_replica[NODE_SIZE..], key).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_inclusion_proof() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let leaves: Vec<DefaultTreeDomain> = (0..8).map(|_| DefaultTreeDomain::random(rng)).collect();
+    let data: Vec<u8> = leaves.iter().flat_map(|leaf| leaf.into_bytes()).collect();
+    let tree = create_base_merkle_tree::<DefaultBinaryTree>(None, leaves.len(), &data)?;
+    let root = tree.root();
+
+    for (i, leaf) in leaves.iter().enumerate() {
+        let path = gen_inclusion_proof(&tree, i)?;
+        assert_eq!(path.len(), 3);
+        assert!(verify_inclusion_proof::<DefaultTreeHasher>(
+            root, *leaf, i, &path
+        ));
+        assert!(!verify_inclusion_proof::<DefaultTreeHasher>(
+            root,
+            leaves[(i + 1) % 8],
+            i,
+            &path
+        ));
+        assert!(!verify_inclusion_proof::<DefaultTreeHasher>(
+            root,
+            *leaf,
+            (i + 1) % 8,
+            &path
+        ));
+    }
+    assert!(gen_inclusion_proof(&tree, 8).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_expected_cache_file_sizes_8mib() -> Result<()> {
+    let sizes = expected_cache_file_sizes(SECTOR_SIZE_8_MIB)?;
+    assert_eq!(sizes["replica"], SECTOR_SIZE_8_MIB);
+    assert!(!sizes.contains_key("t_aux"));
+
+    // The replica holds the leaves of tree_r_last, the cache the rows left after discarding.
+    let leafs = SECTOR_SIZE_8_MIB as usize / NODE_SIZE;
+    let tree_r_last_len =
+        get_merkle_tree_cache_size(leafs, 8, rows_to_discard(SECTOR_SIZE_8_MIB)?)?;
+    assert_eq!(sizes["tree-r-last"], (tree_r_last_len * NODE_SIZE) as u64);
+    assert_eq!(
+        sizes["tree-c"],
+        (get_merkle_tree_len(leafs, 8)? * NODE_SIZE) as u64
+    );
+    assert_eq!(
+        sizes["tree-d"],
+        (get_merkle_tree_len(leafs, 2)? * NODE_SIZE) as u64
+    );
+    assert_eq!(sizes["p_aux"], 64);
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_leaves_8mib() -> Result<()> {
+    let sector_size = SECTOR_SIZE_8_MIB;
+    let leafs = sector_size as usize / NODE_SIZE;
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let leaves: Vec<DefaultTreeDomain> =
+        (0..leafs).map(|_| DefaultTreeDomain::random(rng)).collect();
+    let data: Vec<u8> = leaves.iter().flat_map(|leaf| leaf.into_bytes()).collect();
+    let cache_dir = tempdir()?;
+
+    // A tree storing every row starts with its leaves.
+    let config = StoreConfig::new(cache_dir.path(), "tree-full", 0);
+    let tree_file = StoreConfig::data_path(&config.path, &config.id);
+    create_base_merkle_tree::<DefaultOctTree>(Some(config), leafs, &data)?;
+    assert_eq!(
+        extract_leaves::<SectorShape8MiB>(&tree_file, sector_size)?,
+        leaves
+    );
+
+    // A level cache tree needs the replica.
+    let config = StoreConfig::new(
+        cache_dir.path(),
+        CacheKey::CommRLastTree.to_string(),
+        default_rows_to_discard(leafs, 8),
+    );
+    let tree_file = StoreConfig::data_path(&config.path, &config.id);
+    create_base_merkle_tree::<SectorShape8MiB>(Some(config), leafs, &data)?;
+    let replica = cache_dir.path().join("replica");
+    write(&replica, &data)?;
+    assert!(extract_leaves::<SectorShape8MiB>(&tree_file, sector_size).is_err());
+    assert_eq!(
+        extract_lc_leaves::<SectorShape8MiB>(&tree_file, &replica, 0, sector_size)?,
+        leaves
+    );
+
+    // The recomputed rows catch a replica which doesn't belong to the tree.
+    let mut corrupted = data;
+    corrupted[..NODE_SIZE].copy_from_slice(&leaves[1].into_bytes());
+    write(&replica, &corrupted)?;
+    assert!(extract_lc_leaves::<SectorShape8MiB>(&tree_file, &replica, 0, sector_size).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_parents_for_node() -> Result<()> {
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let node = 32;
+
+    let parents = parents_for_node(&config, 2, node)?;
+    assert_eq!(parents.len(), DRG_DEGREE + EXP_DEGREE);
+    // Base parents precede the node, expander parents may be anywhere in the previous layer.
+    assert!(parents[..DRG_DEGREE].iter().all(|parent| *parent < node));
+    assert!(parents.iter().all(|parent| *parent < 64));
+
+    // The first layer only has base parents.
+    assert_eq!(parents_for_node(&config, 1, node)?, &parents[..DRG_DEGREE]);
+
+    assert!(parents_for_node(&config, 0, node).is_err());
+    assert!(parents_for_node(&config, 3, node).is_err());
+    assert!(parents_for_node(&config, 2, 64).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_derive_replica_id() {
+    let prover_id = [1u8; 32];
+    let ticket = [2u8; 32];
+    let comm_d = [3u8; 32];
+    let porep_seed = [4u8; 32];
+
+    let replica_id = derive_replica_id(prover_id, SectorId::from(42), ticket, comm_d, &porep_seed);
+    assert_eq!(
+        hex::encode(replica_id.into_bytes()),
+        "67ddb316f4c78e1b28014308fca2805f2701a5a4ab1bdc6e4c7d5b04ea3f2036"
+    );
+
+    assert_ne!(
+        replica_id,
+        derive_replica_id(prover_id, SectorId::from(43), ticket, comm_d, &porep_seed)
+    );
+}
+
+#[test]
+#[ignore]
+fn test_unseal_range_window_8mib() -> Result<()> {
+    init_logger();
+
+    let sector_size = SECTOR_SIZE_8_MIB;
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let (mut piece_file, piece_bytes) = generate_piece_file(sector_size)?;
+    let sealed_sector_file = NamedTempFile::new()?;
+    let cache_dir = tempdir()?;
+
+    let config = porep_config(sector_size, ARBITRARY_POREP_ID_V1_1_0, ApiVersion::V1_1_0);
+    let ticket = rng.gen();
+    let sector_id: SectorId = rng.gen::<u64>().into();
+
+    let (_, phase1_output) = run_seal_pre_commit_phase1::<SectorShape8MiB>(
+        config,
+        prover_id,
+        sector_id,
+        ticket,
+        &cache_dir,
+        &mut piece_file,
+        &sealed_sector_file,
+    )?;
+    let pre_commit_output = seal_pre_commit_phase2(
+        config,
+        phase1_output,
+        cache_dir.path(),
+        sealed_sector_file.path(),
+        None,
+    )?;
+
+    let unseal = |offset: u64, num_bytes: u64| -> Result<Vec<u8>> {
+        let mut unsealed = Vec::new();
+        unseal_range::<_, _, _, SectorShape8MiB>(
+            config,
+            cache_dir.path(),
+            File::open(sealed_sector_file.path())?,
+            &mut unsealed,
+            prover_id,
+            sector_id,
+            pre_commit_output.comm_d,
+            ticket,
+            UnpaddedByteIndex(offset),
+            UnpaddedBytesAmount(num_bytes),
+        )?;
+        Ok(unsealed)
+    };
+
+    let full = unseal(0, piece_bytes.len() as u64)?;
+    assert_eq!(full, piece_bytes);
+
+    // A 1KiB window starting in the middle of the sector.
+    let offset = 127 * 1000;
+    let window = unseal(offset as u64, 1024)?;
+    assert_eq!(&window[..], &full[offset..offset + 1024]);
+
+    Ok(())
+}
+
 #[test]
 fn test_resumable_seal_skip_proofs_v1() {
     let porep_id_v1: u64 = 0; // This is a RegisteredSealProof value
@@ -289,10 +1584,8 @@ fn run_resumable_seal<Tree: 'static + MerkleTreeTrait>(
     init_logger();
 
     let sector_size = SECTOR_SIZE_2_KIB;
-    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
-    let prover_fr: DefaultTreeDomain = Fr::random(rng).into();
-    let mut prover_id = [0u8; 32];
-    prover_id.copy_from_slice(AsRef::<[u8]>::as_ref(&prover_fr));
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
 
     let (mut piece_file, piece_bytes) =
         generate_piece_file(sector_size).expect("failed to generate piece file");
@@ -362,10 +1655,11 @@ fn run_resumable_seal<Tree: 'static + MerkleTreeTrait>(
             phase1_output,
             cache_dir.path(),
             sealed_sector_file.path(),
+            None,
         )
         .expect("failed to run seal pre commit phase2");
 
-        validate_cache_for_commit::<_, _, Tree>(cache_dir.path(), sealed_sector_file.path())
+        validate_cache_for_commit::<_, _, Tree>(cache_dir.path(), sealed_sector_file.path(), None)
             .expect("failed to validate cache for commit");
 
         let seed = rng.gen();
@@ -385,6 +1679,295 @@ fn run_resumable_seal<Tree: 'static + MerkleTreeTrait>(
     }
 }
 
+#[test]
+fn test_seal_pre_commit_phase1_cancel_and_resume() -> Result<()> {
+    init_logger();
+
+    let sector_size = SECTOR_SIZE_2_KIB;
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let (mut piece_file, _) = generate_piece_file(sector_size)?;
+    let sealed_sector_file = NamedTempFile::new()?;
+    let cache_dir = tempdir()?;
+
+    let config = porep_config(sector_size, ARBITRARY_POREP_ID_V1_1_0, ApiVersion::V1_1_0);
+    let ticket = rng.gen();
+    let sector_id: SectorId = rng.gen::<u64>().into();
+
+    let (staged_sector_file, piece_infos) = stage_sector(config, &mut piece_file)?;
+
+    // Stop once the first layer is done.
+    let mut reported = Vec::new();
+    let err = seal_pre_commit_phase1_with_progress::<_, _, _, _, SectorShape2KiB>(
+        config,
+        cache_dir.path(),
+        staged_sector_file.path(),
+        sealed_sector_file.path(),
+        prover_id,
+        sector_id,
+        ticket,
+        &piece_infos,
+        true,
+        |progress| {
+            reported.push(progress);
+            LabelControl::Break
+        },
+    )
+    .expect_err("labeling was not cancelled");
+    assert!(matches!(
+        err.downcast_ref::<storage_proofs_core::error::Error>(),
+        Some(storage_proofs_core::error::Error::Cancelled)
+    ));
+    assert_eq!(reported.len(), 1);
+    assert_eq!(reported[0].layer, 1);
+    let mut layers = get_layer_file_paths(&cache_dir);
+    assert_eq!(layers.len(), 1);
+    let first_layer = layers.remove(0);
+    let first_layer_metadata = first_layer.metadata()?;
+
+    // Resuming reuses the first layer and labels the rest.
+    let mut reported = Vec::new();
+    seal_pre_commit_phase1_with_progress::<_, _, _, _, SectorShape2KiB>(
+        config,
+        cache_dir.path(),
+        staged_sector_file.path(),
+        sealed_sector_file.path(),
+        prover_id,
+        sector_id,
+        ticket,
+        &piece_infos,
+        true,
+        |progress| {
+            reported.push(progress.layer);
+            LabelControl::Continue
+        },
+    )?;
+    assert_eq!(reported, vec![1, 2]);
+    assert_eq!(get_layer_file_paths(&cache_dir).len(), 2);
+    // Labeling writes a layer to a temporary file and renames it, so a relabeled layer
+    // would be a new file.
+    let resumed_first_layer_metadata = first_layer.metadata()?;
+    assert_eq!(
+        resumed_first_layer_metadata.ino(),
+        first_layer_metadata.ino()
+    );
+    assert_eq!(
+        resumed_first_layer_metadata.modified()?,
+        first_layer_metadata.modified()?
+    );
+
+    // The resumed labels are the same as the ones of an uninterrupted run.
+    let uninterrupted_cache_dir = tempdir()?;
+    seal_pre_commit_phase1::<_, _, _, SectorShape2KiB>(
+        config,
+        uninterrupted_cache_dir.path(),
+        staged_sector_file.path(),
+        sealed_sector_file.path(),
+        prover_id,
+        sector_id,
+        ticket,
+        &piece_infos,
+    )?;
+    let layers = get_layer_file_paths(&cache_dir);
+    let uninterrupted_layers = get_layer_file_paths(&uninterrupted_cache_dir);
+    assert_eq!(layers.len(), uninterrupted_layers.len());
+    for (layer, uninterrupted_layer) in layers.iter().zip(&uninterrupted_layers) {
+        assert_eq!(read(layer)?, read(uninterrupted_layer)?);
+    }
+
+    // A checkpoint of another sector is not resumed from.
+    let mut reported = Vec::new();
+    seal_pre_commit_phase1_with_progress::<_, _, _, _, SectorShape2KiB>(
+        config,
+        cache_dir.path(),
+        staged_sector_file.path(),
+        sealed_sector_file.path(),
+        prover_id,
+        sector_id,
+        rng.gen(),
+        &piece_infos,
+        true,
+        |progress| {
+            reported.push(progress.layer);
+            LabelControl::Continue
+        },
+    )?;
+    assert_eq!(reported, vec![1, 2]);
+    assert_ne!(read(&layers[0])?, read(&uninterrupted_layers[0])?);
+
+    // Nor is one of another prover for the same sector id and ticket.
+    let run = |prover_id: ProverId| -> Result<Vec<usize>> {
+        let mut reported = Vec::new();
+        seal_pre_commit_phase1_with_progress::<_, _, _, _, SectorShape2KiB>(
+            config,
+            cache_dir.path(),
+            staged_sector_file.path(),
+            sealed_sector_file.path(),
+            prover_id,
+            sector_id,
+            ticket,
+            &piece_infos,
+            true,
+            |progress| {
+                reported.push(progress.layer);
+                LabelControl::Continue
+            },
+        )?;
+        Ok(reported)
+    };
+    assert_eq!(run(prover_id)?, vec![1, 2]);
+    assert_eq!(read(&layers[0])?, read(&uninterrupted_layers[0])?);
+    let mut other_prover_id = prover_id;
+    other_prover_id[0] ^= 1;
+    assert_eq!(run(other_prover_id)?, vec![1, 2]);
+    assert_ne!(read(&layers[0])?, read(&uninterrupted_layers[0])?);
+
+    Ok(())
+}
+
+#[test]
+fn test_seal_pre_commit_phase1_with_write_config() -> Result<()> {
+    init_logger();
+
+    let sector_size = SECTOR_SIZE_2_KIB;
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let (mut piece_file, _) = generate_piece_file(sector_size)?;
+    let config = porep_config(sector_size, ARBITRARY_POREP_ID_V1_1_0, ApiVersion::V1_1_0);
+    let ticket = rng.gen();
+    let sector_id: SectorId = rng.gen::<u64>().into();
+
+    let (staged_sector_file, piece_infos) = stage_sector(config, &mut piece_file)?;
+
+    let mut replicas = Vec::new();
+    // A buffer smaller than a node, and one larger than the sector.
+    for buffer_size in &[None, Some(31), Some(1 << 20)] {
+        let sealed_sector_file = NamedTempFile::new()?;
+        let cache_dir = tempdir()?;
+
+        let phase1_output = seal_pre_commit_phase1_with_write_config::<_, _, _, SectorShape2KiB>(
+            config,
+            cache_dir.path(),
+            staged_sector_file.path(),
+            sealed_sector_file.path(),
+            prover_id,
+            sector_id,
+            ticket,
+            &piece_infos,
+            &ReplicaWriteConfig {
+                buffer_size: *buffer_size,
+                direct_io: false,
+            },
+        )?;
+        assert_eq!(
+            read(sealed_sector_file.path())?,
+            read(staged_sector_file.path())?
+        );
+
+        let pre_commit_output = seal_pre_commit_phase2(
+            config,
+            phase1_output,
+            cache_dir.path(),
+            sealed_sector_file.path(),
+            None,
+        )?;
+        replicas.push((pre_commit_output.comm_r, read(sealed_sector_file.path())?));
+    }
+    assert_eq!(replicas[0], replicas[1]);
+    assert_eq!(replicas[0], replicas[2]);
+
+    // Direct I/O needs an aligned buffer.
+    let err = seal_pre_commit_phase1_with_write_config::<_, _, _, SectorShape2KiB>(
+        config,
+        tempdir()?.path(),
+        staged_sector_file.path(),
+        NamedTempFile::new()?.path(),
+        prover_id,
+        sector_id,
+        ticket,
+        &piece_infos,
+        &ReplicaWriteConfig {
+            buffer_size: Some(1000),
+            direct_io: true,
+        },
+    )
+    .expect_err("unaligned direct I/O buffer was accepted");
+    assert!(format!("{:#}", err).contains("direct I/O"));
+
+    Ok(())
+}
+
+#[test]
+fn test_rebuild_tree_r_last() -> Result<()> {
+    let sector_size = SECTOR_SIZE_2_KIB;
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let config = porep_config(sector_size, ARBITRARY_POREP_ID_V1_1_0, ApiVersion::V1_1_0);
+    let PreCommittedSector {
+        sealed_sector_file,
+        cache_dir,
+        ..
+    } = seal_2kib_to_pre_commit_phase2(rng, config, prover_id, None)?;
+    let comm_r_last = inspect_sealed_sector(cache_dir.path())?.comm_r_last;
+
+    let tree_r_last_path =
+        StoreConfig::data_path(cache_dir.path(), &CacheKey::CommRLastTree.to_string());
+    let tree_r_last = read(&tree_r_last_path)?;
+    remove_file(&tree_r_last_path)?;
+
+    let rebuilt = rebuild_tree_r_last::<SectorShape2KiB>(
+        sealed_sector_file.path(),
+        cache_dir.path(),
+        sector_size,
+    )?;
+    assert_eq!(rebuilt, comm_r_last);
+    assert_eq!(read(&tree_r_last_path)?, tree_r_last);
+    validate_cache_dir::<_, SectorShape2KiB>(config, cache_dir.path(), None)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_inspect_sealed_sector() -> Result<()> {
+    init_logger();
+
+    let sector_size = SECTOR_SIZE_2_KIB;
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_id = random_prover_id(rng);
+
+    let config = porep_config(sector_size, ARBITRARY_POREP_ID_V1_1_0, ApiVersion::V1_1_0);
+    let PreCommittedSector {
+        cache_dir,
+        pre_commit_output,
+        ..
+    } = seal_2kib_to_pre_commit_phase2(rng, config, prover_id, None)?;
+
+    let info = inspect_sealed_sector(cache_dir.path())?;
+    assert_eq!(info.sector_size, sector_size);
+    assert_eq!(info.comm_r, pre_commit_output.comm_r);
+    assert_eq!(info.comm_d, Some(pre_commit_output.comm_d));
+    assert!(verify_comm_r(info.comm_r, info.comm_c, info.comm_r_last));
+
+    // comm_r is still recovered once tree_d is gone.
+    clear_cache::<SectorShape2KiB>(cache_dir.path())?;
+    let info = inspect_sealed_sector(cache_dir.path())?;
+    assert_eq!(info.comm_r, pre_commit_output.comm_r);
+    assert_eq!(info.comm_d, None);
+
+    let err = inspect_sealed_sector(tempdir()?.path()).expect_err("empty cache was inspected");
+    match err.downcast_ref::<Error>() {
+        Some(Error::MissingCacheFile(path)) => {
+            assert!(path.ends_with(CacheKey::PAux.to_string()))
+        }
+        _ => panic!("unexpected error: {:?}", err),
+    }
+
+    Ok(())
+}
+
 #[test]
 #[ignore]
 fn test_winning_post_2kib_base_8() -> Result<()> {
@@ -421,13 +2004,24 @@ fn test_winning_post_32kib_top_8_8_2() -> Result<()> {
     winning_post::<SectorShape32KiB>(SECTOR_SIZE_32_KIB, true, ApiVersion::V1_1_0)
 }
 
+#[test]
+#[ignore]
+fn test_clear_parameter_cache_2kib_base_8() -> Result<()> {
+    winning_post::<SectorShape2KiB>(SECTOR_SIZE_2_KIB, true, ApiVersion::V1_1_0)?;
+
+    // The parameters are transparently loaded again after being evicted.
+    clear_parameter_cache_for_sector_size(SECTOR_SIZE_2_KIB);
+    winning_post::<SectorShape2KiB>(SECTOR_SIZE_2_KIB, true, ApiVersion::V1_1_0)?;
+
+    clear_parameter_cache();
+    winning_post::<SectorShape2KiB>(SECTOR_SIZE_2_KIB, true, ApiVersion::V1_1_0)
+}
+
 #[test]
 fn test_winning_post_empty_sector_challenge() -> Result<()> {
     let rng = &mut XorShiftRng::from_seed(TEST_SEED);
 
-    let prover_fr: DefaultTreeDomain = Fr::random(rng).into();
-    let mut prover_id = [0u8; 32];
-    prover_id.copy_from_slice(AsRef::<[u8]>::as_ref(&prover_fr));
+    let prover_id = random_prover_id(rng);
 
     let sector_count = 0;
     let sector_size = SECTOR_SIZE_2_KIB;
@@ -466,6 +2060,179 @@ fn test_winning_post_empty_sector_challenge() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_select_winning_sector() -> Result<()> {
+    let randomness = [1u8; 32];
+    let prover_id = [2u8; 32];
+    let sector_ids: Vec<SectorId> = (100..110).map(SectorId::from).collect();
+
+    let selected = select_winning_sector(&randomness, prover_id, &sector_ids)?;
+    assert_eq!(selected, SectorId::from(108));
+
+    let config = PoStConfig {
+        sector_size: SECTOR_SIZE_2_KIB.into(),
+        challenge_count: WINNING_POST_CHALLENGE_COUNT,
+        sector_count: WINNING_POST_SECTOR_COUNT,
+        typ: PoStType::Winning,
+        priority: false,
+        api_version: ApiVersion::V1_0_0,
+    };
+    let challenges = generate_winning_post_sector_challenge::<SectorShape2KiB>(
+        &config,
+        &randomness,
+        sector_ids.len() as u64,
+        prover_id,
+    )?;
+    assert_eq!(sector_ids[challenges[0] as usize], selected);
+
+    assert!(select_winning_sector(&randomness, prover_id, &[]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_window_post_challenged_leaves() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let random_fr: DefaultTreeDomain = Fr::random(rng).into();
+    let mut randomness = [0u8; 32];
+    randomness.copy_from_slice(AsRef::<[u8]>::as_ref(&random_fr));
+
+    let config = PoStConfig {
+        sector_size: SECTOR_SIZE_2_KIB.into(),
+        sector_count: 2,
+        challenge_count: WINDOW_POST_CHALLENGE_COUNT,
+        typ: PoStType::Window,
+        priority: false,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let sector_ids: Vec<SectorId> = [7u64, 3, 5].iter().copied().map(SectorId::from).collect();
+
+    let leaves =
+        window_post_challenged_leaves::<SectorShape2KiB>(&config, &randomness, &sector_ids)?;
+    assert_eq!(leaves.len(), sector_ids.len());
+    let nodes = SECTOR_SIZE_2_KIB as usize / 32;
+    for sector_id in &sector_ids {
+        let sector_leaves = &leaves[sector_id];
+        assert_eq!(sector_leaves.len(), WINDOW_POST_CHALLENGE_COUNT);
+        assert!(sector_leaves.iter().all(|leaf| *leaf < nodes));
+    }
+
+    // The leaves don't depend on the order of the sector ids, and are the challenges of the
+    // sectors in the order they are proven.
+    let mut sorted_ids = sector_ids.clone();
+    sorted_ids.sort();
+    assert_eq!(
+        window_post_challenged_leaves::<SectorShape2KiB>(&config, &randomness, &sorted_ids)?,
+        leaves
+    );
+    let challenges = generate_fallback_sector_challenges::<SectorShape2KiB>(
+        &config,
+        &randomness,
+        &sorted_ids,
+        [0u8; 32],
+    )?;
+    for (sector_id, sector_challenges) in challenges {
+        let expected: Vec<usize> = sector_challenges.iter().map(|c| *c as usize).collect();
+        assert_eq!(leaves[&sector_id], expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_post_public_inputs() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let mut random_commitment = || {
+        let fr: DefaultTreeDomain = Fr::random(&mut *rng).into();
+        let mut commitment = [0u8; 32];
+        commitment.copy_from_slice(AsRef::<[u8]>::as_ref(&fr));
+        commitment
+    };
+    let prover_id = random_commitment();
+    let randomness = random_commitment();
+    let sector_ids: Vec<SectorId> = (0..3).map(SectorId::from).collect();
+    let comm_rs: Vec<Commitment> = (0..3).map(|_| random_commitment()).collect();
+
+    // Three sectors with two sectors per partition are proven in two partitions, of which
+    // the first one covers the same sectors as a proof over just the first two.
+    let window_config = PoStConfig {
+        sector_size: SECTOR_SIZE_2_KIB.into(),
+        sector_count: 2,
+        challenge_count: WINDOW_POST_CHALLENGE_COUNT,
+        typ: PoStType::Window,
+        priority: false,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let inputs = window_post_public_inputs::<SectorShape2KiB>(
+        &window_config,
+        &randomness,
+        &sector_ids,
+        &comm_rs,
+        prover_id,
+    )?;
+    assert_eq!(inputs.len(), 2);
+    assert_ne!(inputs[0], inputs[1]);
+
+    let first_inputs = window_post_public_inputs::<SectorShape2KiB>(
+        &window_config,
+        &randomness,
+        &sector_ids[..2],
+        &comm_rs[..2],
+        prover_id,
+    )?;
+    assert_eq!(first_inputs, vec![inputs[0].clone()]);
+
+    assert!(window_post_public_inputs::<SectorShape2KiB>(
+        &window_config,
+        &randomness,
+        &[sector_ids[0], sector_ids[0]],
+        &comm_rs[..2],
+        prover_id,
+    )
+    .is_err());
+
+    // Winning PoSt challenges its single sector as many times as the circuit has sectors.
+    let winning_config = PoStConfig {
+        sector_size: SECTOR_SIZE_2_KIB.into(),
+        sector_count: 1,
+        challenge_count: WINNING_POST_CHALLENGE_COUNT,
+        typ: PoStType::Winning,
+        priority: false,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let inputs = winning_post_public_inputs::<SectorShape2KiB>(
+        &winning_config,
+        &randomness,
+        &sector_ids[..1],
+        &comm_rs[..1],
+        prover_id,
+    )?;
+
+    let vanilla_params = winning_post_setup_params(&winning_config)?;
+    let comm_r: DefaultTreeDomain = as_safe_commitment(&comm_rs[0], "comm_r")?;
+    let pub_inputs = fallback::PublicInputs {
+        randomness: as_safe_commitment(&randomness, "randomness")?,
+        prover_id: as_safe_commitment(&prover_id, "prover_id")?,
+        sectors: vec![
+            PublicSector {
+                id: sector_ids[0],
+                comm_r,
+            };
+            vanilla_params.sector_count
+        ],
+        k: None,
+    };
+    let expected = FallbackPoStCompound::<SectorShape2KiB>::generate_public_inputs(
+        &pub_inputs,
+        &vanilla_params,
+        Some(0),
+    )?;
+    assert_eq!(inputs, expected);
+
+    Ok(())
+}
+
 fn winning_post<Tree: 'static + MerkleTreeTrait>(
     sector_size: u64,
     fake: bool,
@@ -473,9 +2240,7 @@ fn winning_post<Tree: 'static + MerkleTreeTrait>(
 ) -> Result<()> {
     let rng = &mut XorShiftRng::from_seed(TEST_SEED);
 
-    let prover_fr: DefaultTreeDomain = Fr::random(rng).into();
-    let mut prover_id = [0u8; 32];
-    prover_id.copy_from_slice(AsRef::<[u8]>::as_ref(&prover_fr));
+    let prover_id = random_prover_id(rng);
 
     let porep_id = match api_version {
         ApiVersion::V1_0_0 => ARBITRARY_POREP_ID_V1_0_0,
@@ -526,6 +2291,28 @@ fn winning_post<Tree: 'static + MerkleTreeTrait>(
         verify_winning_post::<Tree>(&config, &randomness, &pub_replicas[..], prover_id, &proof)?;
     assert!(valid, "proof did not verify");
 
+    // The async variant produces an equivalent proof (proofs are randomized, so the bytes differ).
+    #[cfg(feature = "async")]
+    {
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        let async_proof = runtime.block_on(generate_winning_post_async::<Tree>(
+            config.clone(),
+            randomness,
+            priv_replicas.clone(),
+            prover_id,
+        ))?;
+        assert_eq!(async_proof.len(), proof.len());
+
+        let valid = verify_winning_post::<Tree>(
+            &config,
+            &randomness,
+            &pub_replicas[..],
+            prover_id,
+            &async_proof,
+        )?;
+        assert!(valid, "async proof did not verify");
+    }
+
     //
     // 2)
     let mut vanilla_proofs = Vec::with_capacity(sector_count);
@@ -557,6 +2344,80 @@ fn winning_post<Tree: 'static + MerkleTreeTrait>(
         verify_winning_post::<Tree>(&config, &randomness, &pub_replicas[..], prover_id, &proof)?;
     assert!(valid, "proof did not verify");
 
+    let verifying_key = read_verifying_key_copy(config.get_cache_verifying_key_path::<Tree>()?)?;
+    let valid = verify_winning_post_with_vk::<Tree>(
+        &config,
+        &verifying_key,
+        &randomness,
+        &pub_replicas[..],
+        prover_id,
+        &proof,
+    )?;
+    assert!(valid, "proof did not verify with the verifying key only");
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_window_post_bundle_2kib_base_8() -> Result<()> {
+    let sector_size = SECTOR_SIZE_2_KIB;
+    let api_version = ApiVersion::V1_1_0;
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let prover_id = random_prover_id(rng);
+
+    let FakeSealedReplicas {
+        sectors: _sectors,
+        pub_replicas,
+        priv_replicas,
+    } = fake_sealed_replicas(rng, 2)?;
+
+    let config = PoStConfig {
+        sector_size: sector_size.into(),
+        sector_count: 2,
+        challenge_count: WINDOW_POST_CHALLENGE_COUNT,
+        typ: PoStType::Window,
+        priority: false,
+        api_version,
+    };
+
+    let mut proofs = Vec::new();
+    let mut public_inputs = Vec::new();
+    for _ in 0..3 {
+        let random_fr: DefaultTreeDomain = Fr::random(rng).into();
+        let mut randomness = [0u8; 32];
+        randomness.copy_from_slice(AsRef::<[u8]>::as_ref(&random_fr));
+
+        proofs.push(generate_window_post::<SectorShape2KiB>(
+            &config,
+            &randomness,
+            &priv_replicas,
+            prover_id,
+        )?);
+        public_inputs.push(WindowPoStPublicInputs {
+            randomness,
+            replicas: &pub_replicas,
+            prover_id,
+        });
+    }
+
+    let bundle = bundle_window_post_proofs(&proofs)?;
+    assert!(verify_window_post_bundle::<SectorShape2KiB>(
+        &config,
+        &bundle,
+        &public_inputs
+    )?);
+
+    // A valid proof for other public inputs makes the whole bundle invalid.
+    proofs[1] = proofs[0].clone();
+    let bundle = bundle_window_post_proofs(&proofs)?;
+    assert!(!verify_window_post_bundle::<SectorShape2KiB>(
+        &config,
+        &bundle,
+        &public_inputs
+    )?);
+
     Ok(())
 }
 
@@ -840,6 +2701,183 @@ fn test_window_post_single_partition_matching_2kib_base_8() -> Result<()> {
     )
 }
 
+#[cfg(feature = "test-helpers")]
+#[test]
+#[ignore]
+fn test_window_post_with_randomness_2kib_base_8() -> Result<()> {
+    let sector_size = SECTOR_SIZE_2_KIB;
+    let api_version = ApiVersion::V1_1_0;
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let prover_id = random_prover_id(rng);
+
+    let FakeSealedReplicas {
+        sectors: _sectors,
+        pub_replicas,
+        priv_replicas,
+    } = fake_sealed_replicas(rng, 2)?;
+
+    let config = PoStConfig {
+        sector_size: sector_size.into(),
+        sector_count: 2,
+        challenge_count: WINDOW_POST_CHALLENGE_COUNT,
+        typ: PoStType::Window,
+        priority: false,
+        api_version,
+    };
+
+    let random_fr: DefaultTreeDomain = Fr::random(rng).into();
+    let mut randomness = [0u8; 32];
+    randomness.copy_from_slice(AsRef::<[u8]>::as_ref(&random_fr));
+
+    let proof = generate_window_post_with_randomness::<SectorShape2KiB>(
+        &config,
+        &randomness,
+        &priv_replicas,
+        prover_id,
+    )?;
+    let proof_again = generate_window_post_with_randomness::<SectorShape2KiB>(
+        &config,
+        &randomness,
+        &priv_replicas,
+        prover_id,
+    )?;
+    assert_eq!(proof, proof_again);
+
+    // The challenges are the same as in production.
+    assert!(verify_window_post::<SectorShape2KiB>(
+        &config,
+        &randomness,
+        &pub_replicas,
+        prover_id,
+        &proof,
+    )?);
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_window_post_thread_budget_2kib_base_8() -> Result<()> {
+    let sector_size = SECTOR_SIZE_2_KIB;
+    let api_version = ApiVersion::V1_1_0;
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let prover_id = random_prover_id(rng);
+
+    let FakeSealedReplicas {
+        sectors: _sectors,
+        pub_replicas,
+        priv_replicas,
+    } = fake_sealed_replicas(rng, 4)?;
+
+    // Two sectors per partition, so the proof has two partitions.
+    let config = PoStConfig {
+        sector_size: sector_size.into(),
+        sector_count: 2,
+        challenge_count: WINDOW_POST_CHALLENGE_COUNT,
+        typ: PoStType::Window,
+        priority: false,
+        api_version,
+    };
+
+    let random_fr: DefaultTreeDomain = Fr::random(rng).into();
+    let mut randomness = [0u8; 32];
+    randomness.copy_from_slice(AsRef::<[u8]>::as_ref(&random_fr));
+
+    for thread_budget in &[1, 4] {
+        let proof = generate_window_post_with_thread_budget::<SectorShape2KiB>(
+            &config,
+            &randomness,
+            &priv_replicas,
+            prover_id,
+            *thread_budget,
+        )?;
+        assert!(verify_window_post::<SectorShape2KiB>(
+            &config,
+            &randomness,
+            &pub_replicas,
+            prover_id,
+            &proof,
+        )?);
+    }
+
+    assert!(generate_window_post_with_thread_budget::<SectorShape2KiB>(
+        &config,
+        &randomness,
+        &priv_replicas,
+        prover_id,
+        0,
+    )
+    .is_err());
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_window_post_with_faults_2kib_base_8() -> Result<()> {
+    let sector_size = SECTOR_SIZE_2_KIB;
+    let api_version = ApiVersion::V1_1_0;
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let prover_id = random_prover_id(rng);
+
+    let FakeSealedReplicas {
+        sectors,
+        pub_replicas,
+        priv_replicas,
+    } = fake_sealed_replicas(rng, 3)?;
+
+    let faulty_sector_id = sectors[1].0;
+    remove_file(sectors[1].1.path())?;
+
+    let random_fr: DefaultTreeDomain = Fr::random(rng).into();
+    let mut randomness = [0u8; 32];
+    randomness.copy_from_slice(AsRef::<[u8]>::as_ref(&random_fr));
+
+    let config = PoStConfig {
+        sector_size: sector_size.into(),
+        sector_count: *WINDOW_POST_SECTOR_COUNT
+            .read()
+            .expect("WINDOW_POST_SECTOR_COUNT poisoned")
+            .get(&sector_size)
+            .expect("unknown sector size"),
+        challenge_count: WINDOW_POST_CHALLENGE_COUNT,
+        typ: PoStType::Window,
+        priority: false,
+        api_version,
+    };
+
+    assert!(generate_window_post::<SectorShape2KiB>(
+        &config,
+        &randomness,
+        &priv_replicas,
+        prover_id
+    )
+    .is_err());
+
+    let (proof, faults) = generate_window_post_with_faults::<SectorShape2KiB>(
+        &config,
+        &randomness,
+        &priv_replicas,
+        prover_id,
+    )?;
+    assert_eq!(faults, vec![faulty_sector_id]);
+
+    pub_replicas.remove(&faulty_sector_id);
+    let valid = verify_window_post::<SectorShape2KiB>(
+        &config,
+        &randomness,
+        &pub_replicas,
+        prover_id,
+        &proof,
+    )?;
+    assert!(valid, "proof did not verify for the remaining sectors");
+
+    Ok(())
+}
+
 fn window_post<Tree: 'static + MerkleTreeTrait>(
     sector_size: u64,
     total_sector_count: usize,
@@ -930,9 +2968,63 @@ fn window_post<Tree: 'static + MerkleTreeTrait>(
     let valid = verify_window_post::<Tree>(&config, &randomness, &pub_replicas, prover_id, &proof)?;
     assert!(valid, "proof did not verify");
 
+    let verifying_key = read_verifying_key_copy(config.get_cache_verifying_key_path::<Tree>()?)?;
+    let valid = verify_window_post_with_vk::<Tree>(
+        &config,
+        &verifying_key,
+        &randomness,
+        &pub_replicas,
+        prover_id,
+        &proof,
+    )?;
+    assert!(valid, "proof did not verify with the verifying key only");
+
+    let comm_rs: HashMap<SectorId, CommR> = sectors
+        .iter()
+        .map(|(sector_id, _, comm_r, _, _)| Ok((*sector_id, CommR::try_from(*comm_r)?)))
+        .collect::<Result<_>>()?;
+    let resolver = |sector_id| {
+        comm_rs
+            .get(&sector_id)
+            .copied()
+            .ok_or_else(|| anyhow!("unknown sector {:?}", sector_id))
+    };
+    let mut sector_ids: Vec<SectorId> = comm_rs.keys().copied().collect();
+    sector_ids.reverse();
+    let valid = verify_window_post_with_resolver::<Tree, _>(
+        &config,
+        &randomness,
+        &sector_ids,
+        prover_id,
+        &proof,
+        resolver,
+    )?;
+    assert!(valid, "proof did not verify with the resolver");
+
+    // A sector the resolver doesn't know fails verification instead of being skipped.
+    sector_ids.push(SectorId::from(u64::MAX));
+    verify_window_post_with_resolver::<Tree, _>(
+        &config,
+        &randomness,
+        &sector_ids,
+        prover_id,
+        &proof,
+        resolver,
+    )
+    .expect_err("unresolved sector was verified");
+
     Ok(())
 }
 
+/// Reads a verifying key from a copy outside of the parameter cache.
+fn read_verifying_key_copy(cache_path: PathBuf) -> Result<PreparedVerifyingKey<Bls12>> {
+    let dir = tempdir()?;
+    let path = dir.path().join("verifying-key.vk");
+    copy(cache_path, &path)?;
+
+    read_verifying_key(&path)
+}
+
 fn generate_piece_file(sector_size: u64) -> Result<(NamedTempFile, Vec<u8>)> {
     let number_of_bytes_in_piece = UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size));
 
@@ -949,31 +3041,28 @@ fn generate_piece_file(sector_size: u64) -> Result<(NamedTempFile, Vec<u8>)> {
 }
 
 fn porep_config(sector_size: u64, porep_id: [u8; 32], api_version: ApiVersion) -> PoRepConfig {
-    PoRepConfig {
-        sector_size: SectorSize(sector_size),
-        partitions: PoRepProofPartitions(
-            *POREP_PARTITIONS
-                .read()
-                .expect("POREP_PARTITIONS poisoned")
-                .get(&sector_size)
-                .expect("unknown sector size"),
-        ),
-        porep_id,
-        api_version,
-    }
+    PoRepConfig::for_sector_size(sector_size, porep_id, api_version).expect("unknown sector size")
 }
 
-fn run_seal_pre_commit_phase1<Tree: 'static + MerkleTreeTrait>(
+fn random_prover_id<R: Rng>(rng: &mut R) -> ProverId {
+    let prover_fr: DefaultTreeDomain = Fr::random(rng).into();
+    let mut prover_id = [0u8; 32];
+    prover_id.copy_from_slice(AsRef::<[u8]>::as_ref(&prover_fr));
+
+    prover_id
+}
+
+/// Adds the piece in `piece_file` to a new staged sector file. The rest of the sector, if
+/// any, is left empty.
+fn stage_sector(
     config: PoRepConfig,
-    prover_id: ProverId,
-    sector_id: SectorId,
-    ticket: [u8; 32],
-    cache_dir: &TempDir,
     mut piece_file: &mut NamedTempFile,
-    sealed_sector_file: &NamedTempFile,
-) -> Result<(Vec<PieceInfo>, SealPreCommitPhase1Output<Tree>)> {
-    let number_of_bytes_in_piece =
-        UnpaddedBytesAmount::from(PaddedBytesAmount(config.sector_size.into()));
+) -> Result<(NamedTempFile, Vec<PieceInfo>)> {
+    let number_of_bytes_in_piece = UnpaddedBytesAmount(piece_file.as_file().metadata()?.len());
+    ensure!(
+        number_of_bytes_in_piece <= UnpaddedBytesAmount::from(config.sector_size),
+        "piece does not fit into the sector"
+    );
 
     let piece_info = generate_piece_commitment(piece_file.as_file_mut(), number_of_bytes_in_piece)?;
     piece_file.as_file_mut().seek(SeekFrom::Start(0))?;
@@ -986,7 +3075,19 @@ fn run_seal_pre_commit_phase1<Tree: 'static + MerkleTreeTrait>(
         &[],
     )?;
 
-    let piece_infos = vec![piece_info];
+    Ok((staged_sector_file, vec![piece_info]))
+}
+
+fn run_seal_pre_commit_phase1<Tree: 'static + MerkleTreeTrait>(
+    config: PoRepConfig,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: [u8; 32],
+    cache_dir: &TempDir,
+    piece_file: &mut NamedTempFile,
+    sealed_sector_file: &NamedTempFile,
+) -> Result<(Vec<PieceInfo>, SealPreCommitPhase1Output<Tree>)> {
+    let (staged_sector_file, piece_infos) = stage_sector(config, piece_file)?;
 
     let phase1_output = seal_pre_commit_phase1::<_, _, _, Tree>(
         config,
@@ -1008,6 +3109,86 @@ fn run_seal_pre_commit_phase1<Tree: 'static + MerkleTreeTrait>(
     Ok((piece_infos, phase1_output))
 }
 
+/// A 2KiB sector sealed up to `seal_pre_commit_phase2`, with what committing to it takes.
+struct PreCommittedSector {
+    sector_id: SectorId,
+    ticket: [u8; 32],
+    seed: [u8; 32],
+    sealed_sector_file: NamedTempFile,
+    cache_dir: TempDir,
+    piece_infos: Vec<PieceInfo>,
+    pre_commit_output: SealPreCommitOutput,
+}
+
+/// Seals a new 2KiB sector of `prover_id` with random data, ticket, seed and sector id up to
+/// `seal_pre_commit_phase2`, writing tree_c and tree_r_last to `tree_dir` if given.
+fn seal_2kib_to_pre_commit_phase2<R: Rng>(
+    rng: &mut R,
+    config: PoRepConfig,
+    prover_id: ProverId,
+    tree_dir: Option<&Path>,
+) -> Result<PreCommittedSector> {
+    let (mut piece_file, _) = generate_piece_file(SECTOR_SIZE_2_KIB)?;
+    let sealed_sector_file = NamedTempFile::new()?;
+    let cache_dir = tempdir()?;
+
+    let ticket = rng.gen();
+    let seed = rng.gen();
+    let sector_id: SectorId = rng.gen::<u64>().into();
+
+    let (piece_infos, phase1_output) = run_seal_pre_commit_phase1::<SectorShape2KiB>(
+        config,
+        prover_id,
+        sector_id,
+        ticket,
+        &cache_dir,
+        &mut piece_file,
+        &sealed_sector_file,
+    )?;
+    let pre_commit_output = seal_pre_commit_phase2(
+        config,
+        phase1_output,
+        cache_dir.path(),
+        sealed_sector_file.path(),
+        tree_dir,
+    )?;
+
+    Ok(PreCommittedSector {
+        sector_id,
+        ticket,
+        seed,
+        sealed_sector_file,
+        cache_dir,
+        piece_infos,
+        pre_commit_output,
+    })
+}
+
+/// Seals a new 2KiB sector like `seal_2kib_to_pre_commit_phase2` and runs
+/// `seal_commit_phase1` on it.
+fn seal_2kib_to_commit_phase1<R: Rng>(
+    rng: &mut R,
+    config: PoRepConfig,
+    prover_id: ProverId,
+    tree_dir: Option<&Path>,
+) -> Result<(PreCommittedSector, SealCommitPhase1Output<SectorShape2KiB>)> {
+    let sector = seal_2kib_to_pre_commit_phase2(rng, config, prover_id, tree_dir)?;
+    let commit_phase1_output = seal_commit_phase1::<_, SectorShape2KiB>(
+        config,
+        sector.cache_dir.path(),
+        sector.sealed_sector_file.path(),
+        tree_dir,
+        prover_id,
+        sector.sector_id,
+        sector.ticket,
+        sector.seed,
+        sector.pre_commit_output.clone(),
+        &sector.piece_infos,
+    )?;
+
+    Ok((sector, commit_phase1_output))
+}
+
 fn proof_and_unseal<Tree: 'static + MerkleTreeTrait>(
     config: PoRepConfig,
     cache_dir_path: &Path,
@@ -1028,6 +3209,7 @@ fn proof_and_unseal<Tree: 'static + MerkleTreeTrait>(
         config,
         cache_dir_path,
         sealed_sector_file.path(),
+        None,
         prover_id,
         sector_id,
         ticket,
@@ -1081,6 +3263,71 @@ fn proof_and_unseal<Tree: 'static + MerkleTreeTrait>(
         &commit_output.proof,
     )?;
     assert!(verified, "failed to verify valid seal");
+
+    let verifying_key = read_verifying_key_copy(config.get_cache_verifying_key_path::<Tree>()?)?;
+    let verified = verify_seal_with_vk::<Tree>(
+        config,
+        &verifying_key,
+        comm_r,
+        comm_d,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        &commit_output.proof,
+    )?;
+    assert!(
+        verified,
+        "failed to verify valid seal with the verifying key only"
+    );
+
+    let vk_bytes = read(config.get_cache_verifying_key_path::<Tree>()?)?;
+    let verified = verify_seal_from_vk_bytes::<Tree>(
+        config,
+        &vk_bytes,
+        comm_r,
+        comm_d,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        &commit_output.proof,
+    )?;
+    assert!(
+        verified,
+        "failed to verify valid seal with the verifying key bytes"
+    );
+
+    let verified = verify_seal_with_param_version::<Tree>(
+        config,
+        VERSION,
+        comm_r,
+        comm_d,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        &commit_output.proof,
+    )?;
+    assert!(
+        verified,
+        "failed to verify valid seal with an explicit parameter version"
+    );
+
+    let truncated = &commit_output.proof[..commit_output.proof.len() - 1];
+    let err = verify_seal::<Tree>(
+        config, comm_r, comm_d, prover_id, sector_id, ticket, seed, truncated,
+    )
+    .expect_err("verified truncated seal proof");
+    assert!(
+        matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::MalformedProof { .. })
+        ),
+        "unexpected error: {:?}",
+        err
+    );
+
     Ok(())
 }
 
@@ -1118,11 +3365,12 @@ fn create_seal<R: Rng, Tree: 'static + MerkleTreeTrait>(
         phase1_output,
         cache_dir.path(),
         sealed_sector_file.path(),
+        None,
     )?;
 
     let comm_r = pre_commit_output.comm_r;
 
-    validate_cache_for_commit::<_, _, Tree>(cache_dir.path(), sealed_sector_file.path())?;
+    validate_cache_for_commit::<_, _, Tree>(cache_dir.path(), sealed_sector_file.path(), None)?;
 
     if skip_proof {
         clear_cache::<Tree>(cache_dir.path())?;
@@ -1145,6 +3393,40 @@ fn create_seal<R: Rng, Tree: 'static + MerkleTreeTrait>(
     Ok((sector_id, sealed_sector_file, comm_r, cache_dir))
 }
 
+/// Fake sealed 2KiB replicas, as proven by the Window PoSt tests.
+struct FakeSealedReplicas {
+    /// The sector ids with the replica and cache dir, which are removed once dropped.
+    sectors: Vec<(SectorId, NamedTempFile, TempDir)>,
+    pub_replicas: BTreeMap<SectorId, PublicReplicaInfo>,
+    priv_replicas: BTreeMap<SectorId, PrivateReplicaInfo<SectorShape2KiB>>,
+}
+
+fn fake_sealed_replicas<R: Rng>(rng: &mut R, n: usize) -> Result<FakeSealedReplicas> {
+    let mut sectors = Vec::with_capacity(n);
+    let mut pub_replicas = BTreeMap::new();
+    let mut priv_replicas = BTreeMap::new();
+    for _ in 0..n {
+        let (sector_id, replica, comm_r, cache_dir) = create_fake_seal::<_, SectorShape2KiB>(
+            rng,
+            SECTOR_SIZE_2_KIB,
+            &ARBITRARY_POREP_ID_V1_1_0,
+            ApiVersion::V1_1_0,
+        )?;
+        priv_replicas.insert(
+            sector_id,
+            PrivateReplicaInfo::new(replica.path().into(), comm_r, cache_dir.path().into())?,
+        );
+        pub_replicas.insert(sector_id, PublicReplicaInfo::new(comm_r)?);
+        sectors.push((sector_id, replica, cache_dir));
+    }
+
+    Ok(FakeSealedReplicas {
+        sectors,
+        pub_replicas,
+        priv_replicas,
+    })
+}
+
 fn create_fake_seal<R: Rng, Tree: 'static + MerkleTreeTrait>(
     mut rng: &mut R,
     sector_size: u64,
@@ -0,0 +1,101 @@
+use std::convert::TryFrom;
+
+use anyhow::Result;
+use filecoin_proofs::{
+    split_commitments, zero_comm_d, CommC, CommD, CommR, Commitment, SECTOR_SIZE_32_GIB,
+};
+
+#[test]
+fn test_comm_d_cid() -> Result<()> {
+    // The well known CID of a zero filled 32GiB piece.
+    let cid = "baga6ea4seaqao7s73y24kcutaosvacpdjgfe5pw76ooefnyqw4ynr3d2y6x2mpq";
+    let hex = "077e5fde35c50a9303a55009e3498a4ebedff39c42b710b730d8ec7ac7afa63e";
+
+    let comm_d = CommD::try_from(zero_comm_d(SECTOR_SIZE_32_GIB)?)?;
+    assert_eq!(comm_d.to_hex(), hex);
+    assert_eq!(comm_d.to_cid(), cid);
+    assert_eq!(CommD::from_cid(cid)?, comm_d);
+    assert_eq!(CommD::from_hex(hex)?, comm_d);
+
+    // A comm_d CID is not a comm_r CID.
+    assert!(CommR::from_cid(cid).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_comm_r_cid() -> Result<()> {
+    let mut bytes = [0u8; 32];
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    let cid = "bagboea4b5abcaaabaibqibiga4eascqlbqgq4dyqcejbgfavcylrqgi2dmob2hq7";
+
+    let comm_r = CommR::try_from(bytes)?;
+    assert_eq!(comm_r.as_bytes(), &bytes);
+    assert_eq!(comm_r.to_cid(), cid);
+    assert_eq!(CommR::from_cid(cid)?, comm_r);
+    assert_eq!(Commitment::from(comm_r), bytes);
+
+    assert!(CommD::from_cid(cid).is_err());
+    assert!(CommR::from_cid(&cid[..cid.len() - 1]).is_err());
+    assert!(CommR::from_cid(&cid.to_uppercase()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_commitment_validation() {
+    // Not a field element.
+    assert!(CommC::try_from([0xff; 32]).is_err());
+    assert!(CommC::from_hex(&"ff".repeat(32)).is_err());
+
+    assert!(CommC::from_hex("00").is_err());
+    assert!(CommC::from_hex(&"zz".repeat(32)).is_err());
+
+    let comm_c = CommC::from_hex(&"00".repeat(32)).expect("failed to parse comm_c");
+    assert_eq!(comm_c.to_hex(), "00".repeat(32));
+}
+
+#[test]
+fn test_commitment_endianness() -> Result<()> {
+    let mut le_bytes = [0u8; 32];
+    for (i, b) in le_bytes.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    le_bytes[0] = 0xff;
+    let mut be_bytes = le_bytes;
+    be_bytes.reverse();
+
+    let comm_r = CommR::from_le_bytes(le_bytes)?;
+    assert_eq!(comm_r.to_le_bytes(), le_bytes);
+    assert_eq!(comm_r.to_be_bytes(), be_bytes);
+    assert_eq!(CommR::from_be_bytes(be_bytes)?, comm_r);
+    assert_eq!(CommR::from_le_bytes(le_bytes)?, CommR::try_from(le_bytes)?);
+
+    // The most significant byte of a field element is the last little-endian byte, so a
+    // commitment read in the wrong order is usually not a valid field element.
+    assert!(CommR::from_be_bytes(le_bytes).is_err());
+    assert!(CommR::from_le_bytes(be_bytes).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_split_commitments() -> Result<()> {
+    let buf: Vec<u8> = (0..96).map(|i| i as u8).collect();
+
+    let commitments: Vec<_> = split_commitments(&buf)?.collect();
+    assert_eq!(commitments.len(), 3);
+    for (commitment, expected) in commitments.iter().zip(buf.chunks(32)) {
+        assert_eq!(&commitment.as_bytes()[..], expected);
+        assert_eq!(commitment.as_bytes().as_ptr(), expected.as_ptr());
+    }
+    assert_eq!(Commitment::from(commitments[1]), *commitments[1].as_bytes());
+
+    assert_eq!(split_commitments(&[])?.count(), 0);
+    assert!(split_commitments(&buf[..95]).is_err());
+    assert!(split_commitments(&buf[..16]).is_err());
+
+    Ok(())
+}
@@ -1,57 +1,56 @@
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use filecoin_hashers::{Domain, Hasher};
 use filecoin_proofs::{
-    with_shape, SECTOR_SIZE_16_MIB, SECTOR_SIZE_1_GIB, SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_GIB,
-    SECTOR_SIZE_4_KIB, SECTOR_SIZE_512_MIB, SECTOR_SIZE_64_GIB, SECTOR_SIZE_8_MIB,
+    error::Error, is_supported_sector_size, layers, lc_tree_type, maximum_pieces_per_sector,
+    minimum_piece_size, porep_minimum_challenges, porep_partitions, post_challenge_count,
+    recommended_rows_to_discard, rows_to_discard, sector_size_for_post_proof,
+    sector_size_for_proof, set_layers, set_porep_minimum_challenges, set_porep_partitions,
+    set_rows_to_discard, supported_sector_shapes, supported_sector_sizes, try_with_shape,
+    validate_sector_shapes, validate_tree_shape, window_post_partitions, window_post_proof_len,
+    window_post_sector_count, with_sector_shape, with_sector_shape_blake2s, with_shape,
+    Blake2sTreeHasher, PoRepConfig, PoRepProofPartitions, PoStType, RegisteredPoStProof,
+    RegisteredSealProof, SectorClass, ShapeFn, UnpaddedBytesAmount, MIN_PIECE_SIZE,
+    SECTOR_SIZE_16_KIB, SECTOR_SIZE_16_MIB, SECTOR_SIZE_1_GIB, SECTOR_SIZE_256_MIB,
+    SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_GIB, SECTOR_SIZE_32_KIB, SECTOR_SIZE_4_KIB,
+    SECTOR_SIZE_512_MIB, SECTOR_SIZE_64_GIB, SECTOR_SIZE_8_MIB, SINGLE_PARTITION_PROOF_LEN,
+    TEST_SEED,
 };
-use generic_array::typenum::Unsigned;
-use storage_proofs_core::merkle::MerkleTreeTrait;
-
-fn canonical_shape(sector_size: u64) -> (usize, usize, usize) {
-    // This could perhaps be cleaned up, but I think it expresses the intended constraints
-    // and is consistent with our current hard-coded size->shape mappings.
-    assert_eq!(sector_size.count_ones(), 1);
-    let log_byte_size = sector_size.trailing_zeros();
-    let log_nodes = log_byte_size - 5; // 2^5 = 32-byte nodes
-
-    let max_tree_log = 3; // Largest allowable arity. The optimal shape.
-
-    let log_max_base = 27; // 4 GiB worth of nodes
-    let log_base = max_tree_log; // Base must be oct trees.x
-    let log_in_base = u32::min(log_max_base, (log_nodes / log_base) * log_base); // How many nodes in base?
-
-    let log_upper = log_nodes - log_in_base; // Nodes in sub and upper combined.
-    let log_rem = log_upper % max_tree_log; // Remainder after filling optimal trees.
-
-    let (log_sub, log_top) = {
-        // Are the upper trees empty?
-        if log_upper > 0 {
-            // Do we need a remainder tree?
-            if log_rem == 0 {
-                (Some(max_tree_log), None) // No remainder tree, fill the sub tree optimall.y
-            } else {
-                // Need a remainder tree.
-
-                // Do we have room for another max tree?
-                if log_upper > max_tree_log {
-                    // There is room. Use the sub tree for as much overflow as we can fit optimally.
-                    // And put the rest in the top tree.
-                    (Some(max_tree_log), Some(log_rem))
-                } else {
-                    // Can't fit another max tree.
-                    // Just put the remainder in the sub tree.
-                    (Some(log_rem), None)
-                }
-            }
-        } else {
-            // Upper trees are empty.
-            (None, None)
-        }
-    };
+use generic_array::typenum::{Unsigned, U0, U2, U4, U8};
+use lazy_static::lazy_static;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use storage_proofs_core::{
+    api_version::ApiVersion,
+    merkle::{generate_tree, MerkleTreeTrait},
+};
+use tempfile::tempdir;
 
-    let base = 1 << log_base;
-    let sub = if let Some(l) = log_sub { 1 << l } else { 0 };
-    let top = if let Some(l) = log_top { 1 << l } else { 0 };
+lazy_static! {
+    /// Held by the tests which override the process-wide per-sector-size constants, and by
+    /// those reading the constants of every sector size.
+    static ref CONSTANTS_LOCK: Mutex<()> = Mutex::new(());
+}
 
-    (base, sub, top)
+/// The `(base, sub, top)` arities each supported sector size is expected to use.
+fn expected_shape(sector_size: u64) -> (usize, usize, usize) {
+    match sector_size {
+        SECTOR_SIZE_2_KIB => (8, 0, 0),
+        SECTOR_SIZE_4_KIB => (8, 2, 0),
+        // 16 KiB and 32 KiB sectors use the 32 GiB and 64 GiB shapes at test scale.
+        SECTOR_SIZE_16_KIB => (8, 8, 0),
+        SECTOR_SIZE_32_KIB => (8, 8, 2),
+        SECTOR_SIZE_8_MIB => (8, 0, 0),
+        SECTOR_SIZE_16_MIB => (8, 2, 0),
+        SECTOR_SIZE_256_MIB => (8, 4, 0),
+        SECTOR_SIZE_512_MIB => (8, 0, 0),
+        SECTOR_SIZE_1_GIB => (8, 2, 0),
+        SECTOR_SIZE_32_GIB => (8, 8, 0),
+        SECTOR_SIZE_64_GIB => (8, 8, 2),
+        _ => panic!("no expected shape for sector size {}", sector_size),
+    }
 }
 
 fn arities_to_usize<Tree: MerkleTreeTrait>() -> (usize, usize, usize) {
@@ -62,20 +61,128 @@ fn arities_to_usize<Tree: MerkleTreeTrait>() -> (usize, usize, usize) {
     )
 }
 
+fn tree_type_arities<Tree: MerkleTreeTrait>(_tree: PhantomData<Tree>) -> (usize, usize, usize) {
+    arities_to_usize::<Tree>()
+}
+
 #[test]
 fn test_with_shape_macro() {
+    validate_sector_shapes().expect("sector shapes do not match their canonical shapes");
+
     test_with_shape_macro_aux(SECTOR_SIZE_2_KIB);
     test_with_shape_macro_aux(SECTOR_SIZE_4_KIB);
     test_with_shape_macro_aux(SECTOR_SIZE_8_MIB);
     test_with_shape_macro_aux(SECTOR_SIZE_16_MIB);
+    test_with_shape_macro_aux(SECTOR_SIZE_256_MIB);
     test_with_shape_macro_aux(SECTOR_SIZE_512_MIB);
     test_with_shape_macro_aux(SECTOR_SIZE_1_GIB);
     test_with_shape_macro_aux(SECTOR_SIZE_32_GIB);
     test_with_shape_macro_aux(SECTOR_SIZE_64_GIB);
 }
 
+struct Arities;
+
+impl ShapeFn for Arities {
+    type Output = (usize, usize, usize);
+
+    fn call<Tree: 'static + MerkleTreeTrait>(self) -> Self::Output {
+        arities_to_usize::<Tree>()
+    }
+}
+
+#[test]
+fn test_with_sector_shape() {
+    for &sector_size in &[
+        SECTOR_SIZE_2_KIB,
+        SECTOR_SIZE_4_KIB,
+        SECTOR_SIZE_16_KIB,
+        SECTOR_SIZE_32_KIB,
+        SECTOR_SIZE_8_MIB,
+        SECTOR_SIZE_16_MIB,
+        SECTOR_SIZE_256_MIB,
+        SECTOR_SIZE_512_MIB,
+        SECTOR_SIZE_1_GIB,
+        SECTOR_SIZE_32_GIB,
+        SECTOR_SIZE_64_GIB,
+    ] {
+        let arities = with_sector_shape(sector_size, Arities).expect("known sector size");
+        assert_eq!(arities, expected_shape(sector_size));
+        assert_eq!(arities, with_shape!(sector_size, arities_to_usize));
+    }
+
+    let unknown_sector_size = SECTOR_SIZE_2_KIB + 1;
+    let err =
+        with_sector_shape(unknown_sector_size, Arities).expect_err("accepted unknown sector size");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::UnsupportedSectorSize(size)) if *size == unknown_sector_size
+    ));
+}
+
+#[test]
+fn test_with_sector_shape_blake2s() {
+    for sector_size in supported_sector_sizes() {
+        let arities = with_sector_shape_blake2s(sector_size, Arities).expect("known sector size");
+        assert_eq!(
+            arities,
+            with_sector_shape(sector_size, Arities).expect("known sector size")
+        );
+    }
+
+    let unknown_sector_size = SECTOR_SIZE_2_KIB + 1;
+    let err = with_sector_shape_blake2s(unknown_sector_size, Arities)
+        .expect_err("accepted unknown sector size");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::UnsupportedSectorSize(size)) if *size == unknown_sector_size
+    ));
+}
+
+struct Blake2sTreeRoot;
+
+impl ShapeFn for Blake2sTreeRoot {
+    type Output = (Vec<u8>, Vec<u8>);
+
+    fn call<Tree: 'static + MerkleTreeTrait>(self) -> Self::Output {
+        assert_eq!(Tree::Hasher::name(), Blake2sTreeHasher::name());
+
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+        let temp_dir = tempdir().expect("failed to create temp dir");
+        let nodes = SECTOR_SIZE_2_KIB as usize / 32;
+        let (data, tree) =
+            generate_tree::<Tree, _>(rng, nodes, Some(temp_dir.path().to_path_buf()));
+
+        (data, tree.root().into_bytes())
+    }
+}
+
+#[test]
+fn test_blake2s_tree_root_is_deterministic() {
+    let (data, root) =
+        with_sector_shape_blake2s(SECTOR_SIZE_2_KIB, Blake2sTreeRoot).expect("known sector size");
+    let (data2, root2) =
+        with_sector_shape_blake2s(SECTOR_SIZE_2_KIB, Blake2sTreeRoot).expect("known sector size");
+
+    assert_eq!(data, data2);
+    assert_eq!(root, root2);
+    assert_ne!(root, vec![0u8; 32]);
+}
+
+#[test]
+fn test_try_with_shape_macro() {
+    let arities = try_with_shape!(SECTOR_SIZE_32_GIB, arities_to_usize);
+    assert!(matches!(arities, Ok(shape) if shape == expected_shape(SECTOR_SIZE_32_GIB)));
+
+    let unknown_sector_size = SECTOR_SIZE_2_KIB + 1;
+    let arities = try_with_shape!(unknown_sector_size, arities_to_usize);
+    assert!(matches!(
+        arities,
+        Err(Error::UnsupportedSectorSize(size)) if size == unknown_sector_size
+    ));
+}
+
 fn test_with_shape_macro_aux(sector_size: u64) {
-    let expected = canonical_shape(sector_size);
+    let expected = expected_shape(sector_size);
     let arities = with_shape!(sector_size, arities_to_usize);
     assert_eq!(
         arities, expected,
@@ -83,3 +190,397 @@ fn test_with_shape_macro_aux(sector_size: u64) {
         sector_size, arities, expected
     );
 }
+
+#[test]
+fn test_validate_tree_shape() {
+    let shape = tree_type_arities(lc_tree_type::<U8, U8, U2>());
+    assert_eq!(shape, expected_shape(SECTOR_SIZE_64_GIB));
+    validate_tree_shape(SECTOR_SIZE_64_GIB, shape).expect("canonical shape is valid");
+
+    // A binary tree with sub and top trees isn't canonical, but still a valid tree.
+    let shape = tree_type_arities(lc_tree_type::<U2, U4, U2>());
+    assert_ne!(shape, expected_shape(SECTOR_SIZE_8_MIB));
+    validate_tree_shape(SECTOR_SIZE_8_MIB, shape).expect("custom shape is valid");
+
+    for (sector_size, shape) in vec![
+        (
+            SECTOR_SIZE_8_MIB,
+            tree_type_arities(lc_tree_type::<U8, U0, U2>()),
+        ),
+        (
+            SECTOR_SIZE_8_MIB,
+            tree_type_arities(lc_tree_type::<U8, U2, U0>()),
+        ),
+        (
+            SECTOR_SIZE_2_KIB,
+            tree_type_arities(lc_tree_type::<U8, U8, U2>()),
+        ),
+    ] {
+        let err = validate_tree_shape(sector_size, shape).expect_err("shape is invalid");
+        match err.downcast_ref::<Error>() {
+            Some(Error::InvalidSectorShape {
+                sector_size: size,
+                actual,
+                expected,
+            }) => {
+                assert_eq!(*size, sector_size);
+                assert_eq!(*actual, shape);
+                assert_eq!(*expected, expected_shape(sector_size));
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    assert!(validate_tree_shape(3 * SECTOR_SIZE_2_KIB, (8, 0, 0)).is_err());
+}
+
+#[test]
+fn test_set_constants_unknown_sector_size() {
+    let unknown_sector_size = SECTOR_SIZE_2_KIB + 1;
+
+    for err in vec![
+        set_porep_partitions(unknown_sector_size, 1).expect_err("accepted unknown sector size"),
+        set_porep_minimum_challenges(unknown_sector_size, 2)
+            .expect_err("accepted unknown sector size"),
+        set_layers(unknown_sector_size, 2).expect_err("accepted unknown sector size"),
+    ] {
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::UnsupportedSectorSize(size)) if *size == unknown_sector_size
+        ));
+    }
+}
+
+#[test]
+fn test_set_constants_invalid_values() {
+    for err in vec![
+        set_porep_partitions(SECTOR_SIZE_16_KIB, 0).expect_err("accepted zero partitions"),
+        set_porep_minimum_challenges(SECTOR_SIZE_16_KIB, 0).expect_err("accepted zero challenges"),
+        set_layers(SECTOR_SIZE_16_KIB, 1).expect_err("accepted a single layer"),
+    ] {
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InvalidSectorConstant { .. })
+        ));
+    }
+}
+
+#[test]
+fn test_set_constants_override() {
+    let _lock = CONSTANTS_LOCK.lock().expect("constants lock poisoned");
+
+    set_porep_partitions(SECTOR_SIZE_32_KIB, 2).expect("failed to set porep partitions");
+    set_porep_minimum_challenges(SECTOR_SIZE_32_KIB, 4).expect("failed to set challenges");
+    set_layers(SECTOR_SIZE_32_KIB, 3).expect("failed to set layers");
+
+    assert_eq!(porep_partitions(SECTOR_SIZE_32_KIB).expect("known size"), 2);
+    assert_eq!(
+        porep_minimum_challenges(SECTOR_SIZE_32_KIB).expect("known size"),
+        4
+    );
+    assert_eq!(layers(SECTOR_SIZE_32_KIB).expect("known size"), 3);
+
+    set_porep_partitions(SECTOR_SIZE_32_KIB, 1).expect("failed to restore porep partitions");
+    set_porep_minimum_challenges(SECTOR_SIZE_32_KIB, 2).expect("failed to restore challenges");
+    set_layers(SECTOR_SIZE_32_KIB, 2).expect("failed to restore layers");
+}
+
+#[test]
+fn test_recommended_rows_to_discard() {
+    // The eight base trees of a 32 GiB tree_r_last cache 585 MiB with 2 rows discarded, and
+    // 73 MiB with 3.
+    let tight = recommended_rows_to_discard(SECTOR_SIZE_32_GIB, 100 << 20).expect("known size");
+    assert_eq!(tight, 3);
+    let generous = recommended_rows_to_discard(SECTOR_SIZE_32_GIB, 1 << 30).expect("known size");
+    assert_eq!(generous, 2);
+
+    // Nothing is discarded if the whole tree fits, and at most the rows between the leaves and
+    // the root are.
+    let unlimited = recommended_rows_to_discard(SECTOR_SIZE_32_GIB, u64::MAX).expect("known size");
+    assert_eq!(unlimited, 0);
+    let none = recommended_rows_to_discard(SECTOR_SIZE_32_GIB, 0).expect("known size");
+    assert_eq!(none, 8);
+
+    let unknown_sector_size = 1 << 20;
+    let err = recommended_rows_to_discard(unknown_sector_size, 1 << 30)
+        .expect_err("accepted unknown size");
+    match err.downcast_ref::<Error>() {
+        Some(Error::UnsupportedSectorSize(size)) => assert_eq!(*size, unknown_sector_size),
+        _ => panic!("unexpected error: {:?}", err),
+    }
+}
+
+#[test]
+fn test_set_rows_to_discard() {
+    let _lock = CONSTANTS_LOCK.lock().expect("constants lock poisoned");
+
+    // A 32KiB tree_r_last is made of 16 base trees of 64 leaves, so only the single row between
+    // the leaves and the root can be discarded.
+    assert_eq!(rows_to_discard(SECTOR_SIZE_32_KIB).expect("known size"), 1);
+
+    set_rows_to_discard(SECTOR_SIZE_32_KIB, 0).expect("failed to set rows to discard");
+    assert_eq!(rows_to_discard(SECTOR_SIZE_32_KIB).expect("known size"), 0);
+
+    let err = set_rows_to_discard(SECTOR_SIZE_32_KIB, 2).expect_err("accepted too many rows");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::InvalidSectorConstant { value: 2, .. })
+    ));
+    assert_eq!(rows_to_discard(SECTOR_SIZE_32_KIB).expect("known size"), 0);
+
+    let unknown_sector_size = SECTOR_SIZE_2_KIB + 1;
+    let err = set_rows_to_discard(unknown_sector_size, 0).expect_err("accepted unknown size");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::UnsupportedSectorSize(size)) if *size == unknown_sector_size
+    ));
+
+    set_rows_to_discard(SECTOR_SIZE_32_KIB, 1).expect("failed to restore rows to discard");
+}
+
+#[test]
+fn test_constant_getters() {
+    assert_eq!(porep_partitions(SECTOR_SIZE_2_KIB).expect("known size"), 1);
+    assert_eq!(layers(SECTOR_SIZE_2_KIB).expect("known size"), 2);
+    assert_eq!(
+        window_post_sector_count(SECTOR_SIZE_2_KIB).expect("known size"),
+        2
+    );
+    assert_eq!(
+        porep_minimum_challenges(SECTOR_SIZE_2_KIB).expect("known size"),
+        2
+    );
+
+    assert_eq!(
+        porep_partitions(SECTOR_SIZE_32_GIB).expect("known size"),
+        10
+    );
+    assert_eq!(layers(SECTOR_SIZE_32_GIB).expect("known size"), 11);
+    assert_eq!(
+        window_post_sector_count(SECTOR_SIZE_32_GIB).expect("known size"),
+        2349
+    );
+    assert_eq!(
+        porep_minimum_challenges(SECTOR_SIZE_32_GIB).expect("known size"),
+        176
+    );
+}
+
+#[test]
+fn test_post_challenge_count() {
+    assert_eq!(post_challenge_count(PoStType::Winning), 66);
+    assert_eq!(post_challenge_count(PoStType::Window), 10);
+}
+
+#[test]
+fn test_window_post_proof_len() {
+    let single = window_post_proof_len(SECTOR_SIZE_32_GIB, 1).expect("known size");
+    assert_eq!(single, SINGLE_PARTITION_PROOF_LEN);
+    assert_eq!(
+        window_post_proof_len(SECTOR_SIZE_32_GIB, 2349).expect("known size"),
+        SINGLE_PARTITION_PROOF_LEN
+    );
+
+    // One sector past a full partition needs a second one.
+    assert_eq!(
+        window_post_proof_len(SECTOR_SIZE_32_GIB, 2350).expect("known size"),
+        2 * SINGLE_PARTITION_PROOF_LEN
+    );
+    assert_eq!(
+        window_post_proof_len(SECTOR_SIZE_32_GIB, 3 * 2349).expect("known size"),
+        3 * SINGLE_PARTITION_PROOF_LEN
+    );
+
+    assert!(window_post_proof_len(SECTOR_SIZE_32_GIB, 0).is_err());
+}
+
+#[test]
+fn test_window_post_partitions() {
+    let partitions = window_post_partitions(SECTOR_SIZE_32_GIB, 3000).expect("known size");
+    assert_eq!(partitions, vec![0..2349, 2349..3000]);
+
+    // The partitions cover every sector once, in order.
+    let mut next = 0;
+    for partition in &partitions {
+        assert_eq!(partition.start, next);
+        assert!(partition.len() <= 2349);
+        next = partition.end;
+    }
+    assert_eq!(next, 3000);
+
+    assert!(window_post_partitions(SECTOR_SIZE_32_GIB, 0)
+        .expect("known size")
+        .is_empty());
+}
+
+#[test]
+fn test_constant_getters_unknown_sector_size() {
+    let unknown_sector_size = 1 << 27;
+
+    let errors = vec![
+        porep_partitions(unknown_sector_size).map(|_| ()),
+        layers(unknown_sector_size).map(|_| ()),
+        window_post_sector_count(unknown_sector_size).map(|_| ()),
+        porep_minimum_challenges(unknown_sector_size).map(|_| ()),
+    ];
+
+    for result in errors {
+        let err = result.expect_err("unknown sector size must fail");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::UnsupportedSectorSize(size)) if *size == unknown_sector_size
+        ));
+    }
+}
+
+#[test]
+fn test_porep_config_for_sector_size() {
+    let porep_id = [0u8; 32];
+
+    let config = PoRepConfig::for_sector_size(SECTOR_SIZE_32_GIB, porep_id, ApiVersion::V1_1_0)
+        .expect("known sector size");
+    assert_eq!(u64::from(config.sector_size), SECTOR_SIZE_32_GIB);
+    assert_eq!(usize::from(PoRepProofPartitions::from(config)), 10);
+    assert_eq!(config.layers().expect("known sector size"), 11);
+    assert_eq!(config.minimum_challenges().expect("known sector size"), 176);
+
+    let unknown_sector_size = SECTOR_SIZE_2_KIB + 1;
+    let err = PoRepConfig::for_sector_size(unknown_sector_size, porep_id, ApiVersion::V1_1_0)
+        .expect_err("accepted unknown sector size");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::UnsupportedSectorSize(size)) if *size == unknown_sector_size
+    ));
+}
+
+#[test]
+fn test_is_supported_sector_size() {
+    for &sector_size in &[
+        SECTOR_SIZE_2_KIB,
+        SECTOR_SIZE_4_KIB,
+        SECTOR_SIZE_16_KIB,
+        SECTOR_SIZE_32_KIB,
+        SECTOR_SIZE_8_MIB,
+        SECTOR_SIZE_16_MIB,
+        SECTOR_SIZE_256_MIB,
+        SECTOR_SIZE_512_MIB,
+        SECTOR_SIZE_1_GIB,
+        SECTOR_SIZE_32_GIB,
+        SECTOR_SIZE_64_GIB,
+    ] {
+        assert!(is_supported_sector_size(sector_size), "{}", sector_size);
+    }
+
+    for &sector_size in &[
+        0,
+        1,
+        SECTOR_SIZE_2_KIB - 1,
+        SECTOR_SIZE_2_KIB + 1,
+        3 * SECTOR_SIZE_2_KIB,
+        1 << 27, // 128 MiB
+        1 << 37, // 128 GiB
+        u64::MAX,
+    ] {
+        assert!(!is_supported_sector_size(sector_size), "{}", sector_size);
+    }
+}
+
+#[test]
+fn test_supported_sector_sizes() {
+    let sector_sizes: Vec<u64> = supported_sector_sizes().collect();
+    assert_eq!(
+        sector_sizes,
+        vec![
+            SECTOR_SIZE_2_KIB,
+            SECTOR_SIZE_4_KIB,
+            SECTOR_SIZE_16_KIB,
+            SECTOR_SIZE_32_KIB,
+            SECTOR_SIZE_8_MIB,
+            SECTOR_SIZE_16_MIB,
+            SECTOR_SIZE_256_MIB,
+            SECTOR_SIZE_512_MIB,
+            SECTOR_SIZE_1_GIB,
+            SECTOR_SIZE_32_GIB,
+            SECTOR_SIZE_64_GIB,
+        ]
+    );
+    assert!(sector_sizes.windows(2).all(|w| w[0] < w[1]));
+    assert!(sector_sizes.iter().all(|s| is_supported_sector_size(*s)));
+
+    for (sector_size, arities) in supported_sector_shapes() {
+        assert_eq!(arities, with_shape!(sector_size, arities_to_usize));
+    }
+    assert_eq!(supported_sector_shapes().count(), sector_sizes.len());
+}
+
+#[test]
+fn test_piece_size_limits() {
+    assert_eq!(minimum_piece_size(SECTOR_SIZE_2_KIB), MIN_PIECE_SIZE);
+    assert_eq!(maximum_pieces_per_sector(SECTOR_SIZE_2_KIB), 16);
+
+    assert_eq!(minimum_piece_size(SECTOR_SIZE_32_GIB), MIN_PIECE_SIZE);
+    assert_eq!(maximum_pieces_per_sector(SECTOR_SIZE_32_GIB), 1 << 28);
+
+    assert_eq!(minimum_piece_size(0), UnpaddedBytesAmount(0));
+    assert_eq!(maximum_pieces_per_sector(0), 0);
+}
+
+#[test]
+fn test_sector_class() {
+    let _lock = CONSTANTS_LOCK.lock().expect("constants lock poisoned");
+
+    for sector_size in supported_sector_sizes() {
+        let sector_class = SectorClass::try_from(sector_size).expect("supported sector size");
+        assert_eq!(sector_class.sector_size(), sector_size);
+        assert_eq!(
+            sector_class.shape_arities().expect("supported sector size"),
+            with_shape!(sector_size, arities_to_usize)
+        );
+
+        let porep_config = PoRepConfig::from(sector_class);
+        assert_eq!(u64::from(porep_config.sector_size), sector_size);
+        assert_eq!(porep_config.porep_id, sector_class.porep_id);
+    }
+
+    let unknown_sector_size = SECTOR_SIZE_2_KIB + 1;
+    let err = SectorClass::try_from(unknown_sector_size).expect_err("accepted unknown sector size");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::UnsupportedSectorSize(size)) if *size == unknown_sector_size
+    ));
+}
+
+#[test]
+fn test_sector_size_for_proof() {
+    let sizes = [
+        SECTOR_SIZE_2_KIB,
+        SECTOR_SIZE_8_MIB,
+        SECTOR_SIZE_512_MIB,
+        SECTOR_SIZE_32_GIB,
+        SECTOR_SIZE_64_GIB,
+    ];
+
+    // Ids 0 to 4 are the V1 proofs and 5 to 9 the V1_1 proofs, of increasing sector size.
+    for id in 0..10 {
+        let proof = RegisteredSealProof::try_from(id).expect("unknown seal proof");
+        assert_eq!(proof as u64, id);
+
+        let sector_size = sector_size_for_proof(proof).expect("failed to get sector size");
+        assert_eq!(sector_size, sizes[id as usize % 5], "{:?}", proof);
+        assert_eq!(
+            with_shape!(sector_size, arities_to_usize),
+            expected_shape(sector_size)
+        );
+    }
+    assert!(RegisteredSealProof::try_from(10).is_err());
+
+    // Ids 0 to 4 are the Winning PoSt proofs and 5 to 9 the Window PoSt proofs.
+    for id in 0..10 {
+        let proof = RegisteredPoStProof::try_from(id).expect("unknown post proof");
+        assert_eq!(proof as u64, id);
+
+        let sector_size = sector_size_for_post_proof(proof).expect("failed to get sector size");
+        assert_eq!(sector_size, sizes[id as usize % 5], "{:?}", proof);
+    }
+    assert!(RegisteredPoStProof::try_from(10).is_err());
+}
@@ -0,0 +1,61 @@
+use anyhow::Result;
+use bellperson::bls::Fr;
+use ff::Field;
+use filecoin_hashers::Domain;
+use filecoin_proofs::{
+    conversions::{
+        bytes_into_domain, bytes_into_fr, domain_into_fr, fr_into_bytes, fr_into_domain,
+    },
+    DefaultTreeDomain, TEST_SEED,
+};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+/// The modulus of the scalar field of BLS12-381, little-endian.
+const MODULUS: [u8; 32] = [
+    0x01, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0x02, 0xa4, 0xbd, 0x53,
+    0x05, 0xd8, 0xa1, 0x09, 0x08, 0xd8, 0x39, 0x33, 0x48, 0x7d, 0x9d, 0x29, 0x53, 0xa7, 0xed, 0x73,
+];
+
+#[test]
+fn test_conversions_round_trip() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    for _ in 0..100 {
+        let fr = Fr::random(rng);
+
+        let bytes = fr_into_bytes(&fr);
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(bytes_into_fr(&bytes)?, fr);
+
+        let domain = fr_into_domain(fr);
+        assert_eq!(domain_into_fr(domain)?, fr);
+        assert_eq!(bytes_into_domain(&bytes)?, domain);
+        assert_eq!(domain.into_bytes(), bytes);
+    }
+
+    // The largest field element.
+    let mut max = MODULUS;
+    max[0] -= 1;
+    let fr = bytes_into_fr(&max)?;
+    assert_eq!(fr_into_bytes(&fr), max);
+
+    Ok(())
+}
+
+#[test]
+fn test_conversions_reject_non_canonical() {
+    let mut above = MODULUS;
+    above[0] += 1;
+
+    for bytes in &[MODULUS, above, [0xff; 32]] {
+        assert!(bytes_into_fr(bytes).is_err());
+        assert!(bytes_into_domain(bytes).is_err());
+
+        // Domain elements can hold any 32 bytes, only their conversion fails.
+        let domain = DefaultTreeDomain::try_from_bytes(bytes).expect("failed to read domain");
+        assert!(domain_into_fr(domain).is_err());
+    }
+
+    assert!(bytes_into_fr(&[0; 31]).is_err());
+}
@@ -35,6 +35,7 @@ fn test_verify_seal_fr32_validation() {
                 ),
                 porep_id: arbitrary_porep_id,
                 api_version: ApiVersion::V1_1_0,
+                challenge_derivation: None,
             },
             not_convertible_to_fr_bytes,
             convertible_to_fr_bytes,
@@ -73,6 +74,7 @@ fn test_verify_seal_fr32_validation() {
                 ),
                 porep_id: arbitrary_porep_id,
                 api_version: ApiVersion::V1_1_0,
+                challenge_derivation: None,
             },
             convertible_to_fr_bytes,
             not_convertible_to_fr_bytes,
@@ -0,0 +1,234 @@
+use std::fs::{remove_file, File};
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+use anyhow::Result;
+use filecoin_proofs::{
+    error::Error,
+    param::{
+        get_digest_for_file, missing_parameters, prefetch_parameters_in, published_parameter_ids,
+        sector_size_manifest, verify_parameter_file, verifying_key_fingerprint,
+        verifying_key_from_bytes,
+    },
+    parameters::public_params,
+    DefaultPieceHasher, MerkleTreeTrait, PaddedBytesAmount, PoRepConfig, PoRepProofPartitions,
+    SectorShape2KiB, SectorShape4KiB, PUBLISHED_SECTOR_SIZES, SECTOR_SIZE_256_MIB,
+    SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_GIB, SECTOR_SIZE_4_KIB,
+};
+use lazy_static::lazy_static;
+use rand::rngs::OsRng;
+use storage_proofs_core::{
+    api_version::ApiVersion,
+    compound_proof::CompoundProof,
+    parameter_cache::{load_parameters_from_path, parameter_id, PARAMETERS_DATA},
+};
+use storage_proofs_porep::stacked::{StackedCompound, StackedDrg};
+use tempfile::{tempdir, NamedTempFile};
+
+const FIXTURE: &[u8] = b"filecoin-proofs parameter fixture\n";
+const FIXTURE_DIGEST: &str = "c8cf29c8441f03560658b311c43958e9";
+
+lazy_static! {
+    /// Held by the tests which override or read the parameters of parameters.json, as the
+    /// override applies to the whole process.
+    static ref PARAMETERS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+#[test]
+fn test_verify_parameter_file() {
+    let _lock = PARAMETERS_LOCK.lock().expect("parameters lock poisoned");
+    let mut fixture = NamedTempFile::new().expect("failed to create fixture");
+    fixture.write_all(FIXTURE).expect("failed to write fixture");
+    assert_eq!(
+        get_digest_for_file(fixture.path()).expect("failed to hash fixture"),
+        FIXTURE_DIGEST
+    );
+
+    let valid = "fixture-valid";
+    let invalid = "fixture-invalid";
+    let json = format!(
+        r#"{{
+            "{}": {{ "cid": "Qm", "digest": "{}", "sector_size": 2048 }},
+            "{}": {{ "cid": "Qm", "digest": "00000000000000000000000000000000", "sector_size": 2048 }}
+        }}"#,
+        parameter_id(valid),
+        FIXTURE_DIGEST,
+        parameter_id(invalid),
+    );
+    let mut parameters = NamedTempFile::new().expect("failed to create parameters.json");
+    parameters
+        .write_all(json.as_bytes())
+        .expect("failed to write parameters.json");
+    load_parameters_from_path(parameters.path()).expect("failed to load parameters.json");
+
+    assert!(verify_parameter_file(valid, fixture.path()).expect("failed to verify fixture"));
+    assert!(!verify_parameter_file(invalid, fixture.path()).expect("failed to verify fixture"));
+
+    let err = verify_parameter_file("fixture-unknown", fixture.path())
+        .expect_err("verified unknown cache id");
+    assert!(err.to_string().contains("fixture-unknown"));
+    match err.downcast_ref::<Error>() {
+        Some(Error::ParameterNotFound(cache_id)) => assert_eq!(cache_id, "fixture-unknown"),
+        _ => panic!("unexpected error: {:?}", err),
+    }
+
+    let missing = fixture.path().with_extension("missing");
+    let err = verify_parameter_file(valid, &missing).expect_err("verified missing file");
+    match err.downcast_ref::<Error>() {
+        Some(Error::Io(io_err)) => assert_eq!(io_err.kind(), io::ErrorKind::NotFound),
+        _ => panic!("unexpected error: {:?}", err),
+    }
+
+    // Load the builtin parameters.json again for the other tests.
+    let mut builtin = NamedTempFile::new().expect("failed to create parameters.json");
+    builtin
+        .write_all(PARAMETERS_DATA.as_bytes())
+        .expect("failed to write parameters.json");
+    load_parameters_from_path(builtin.path()).expect("failed to load builtin parameters.json");
+}
+
+#[test]
+fn test_verifying_key_from_malformed_bytes() {
+    assert!(verifying_key_from_bytes(&[]).is_err());
+    assert!(verifying_key_from_bytes(&[0xff; 96]).is_err());
+    assert!(verifying_key_from_bytes(FIXTURE).is_err());
+}
+
+#[test]
+fn test_missing_parameters() {
+    let cache_dir = tempdir().expect("failed to create temp dir");
+
+    let filenames = missing_parameters(cache_dir.path(), &[SECTOR_SIZE_2_KIB])
+        .expect("failed to list missing parameters");
+    // PoRep, Winning PoSt and Window PoSt each need parameters and a verifying key.
+    assert_eq!(filenames.len(), 6);
+    assert_eq!(
+        filenames.iter().filter(|f| f.ends_with(".params")).count(),
+        3
+    );
+
+    for filename in &filenames[1..] {
+        File::create(cache_dir.path().join(filename)).expect("failed to create parameter file");
+    }
+    assert_eq!(
+        missing_parameters(cache_dir.path(), &[SECTOR_SIZE_2_KIB])
+            .expect("failed to list missing parameters"),
+        &filenames[..1]
+    );
+
+    let err = missing_parameters(cache_dir.path(), &[SECTOR_SIZE_2_KIB, 3 << 10])
+        .expect_err("listed parameters for an unknown sector size");
+    match err.downcast_ref::<Error>() {
+        Some(Error::UnsupportedSectorSize(sector_size)) => assert_eq!(*sector_size, 3 << 10),
+        _ => panic!("unexpected error: {:?}", err),
+    }
+}
+
+#[test]
+fn test_prefetch_parameters() {
+    let cache_dir = tempdir().expect("failed to create temp dir");
+    let filenames = missing_parameters(cache_dir.path(), &[SECTOR_SIZE_2_KIB])
+        .expect("failed to list missing parameters");
+    for filename in &filenames {
+        let mut file =
+            File::create(cache_dir.path().join(filename)).expect("failed to create parameter file");
+        file.write_all(FIXTURE)
+            .expect("failed to write parameter file");
+    }
+
+    prefetch_parameters_in(cache_dir.path(), &[SECTOR_SIZE_2_KIB])
+        .expect("failed to prefetch parameters");
+
+    let absent = cache_dir.path().join(&filenames[0]);
+    remove_file(&absent).expect("failed to remove parameter file");
+    let err = prefetch_parameters_in(cache_dir.path(), &[SECTOR_SIZE_2_KIB])
+        .expect_err("prefetched absent parameters");
+    match err.downcast_ref::<Error>() {
+        Some(Error::MissingCacheFile(path)) => assert_eq!(path, &absent),
+        _ => panic!("unexpected error: {:?}", err),
+    }
+
+    let err = prefetch_parameters_in(cache_dir.path(), &[3 << 10])
+        .expect_err("prefetched parameters for an unknown sector size");
+    match err.downcast_ref::<Error>() {
+        Some(Error::UnsupportedSectorSize(sector_size)) => assert_eq!(*sector_size, 3 << 10),
+        _ => panic!("unexpected error: {:?}", err),
+    }
+}
+
+#[test]
+fn test_sector_size_manifest() {
+    let manifest =
+        sector_size_manifest(SECTOR_SIZE_32_GIB).expect("failed to build sector size manifest");
+    assert_eq!(manifest["sector_size"], SECTOR_SIZE_32_GIB);
+    assert_eq!(manifest["partitions"], 10);
+    assert_eq!(manifest["layers"], 11);
+    assert_eq!(manifest["window_post_sector_count"], 2349);
+    assert_eq!(manifest["arities"], serde_json::json!([8, 8, 0]));
+    assert_eq!(manifest["minimum_piece_size"], 127);
+    for circuit in &["porep", "winning_post", "window_post"] {
+        assert!(manifest["cache_ids"][circuit].is_string());
+    }
+
+    let err = sector_size_manifest(3 << 10).expect_err("built a manifest for an unknown size");
+    match err.downcast_ref::<Error>() {
+        Some(Error::UnsupportedSectorSize(sector_size)) => assert_eq!(*sector_size, 3 << 10),
+        _ => panic!("unexpected error: {:?}", err),
+    }
+}
+
+#[test]
+#[ignore]
+fn test_verifying_key_fingerprint() -> Result<()> {
+    let config_2kib = cached_porep_config::<SectorShape2KiB>(SECTOR_SIZE_2_KIB)?;
+    let config_4kib = cached_porep_config::<SectorShape4KiB>(SECTOR_SIZE_4_KIB)?;
+
+    let fingerprint_2kib = verifying_key_fingerprint(&config_2kib)?;
+    assert_eq!(verifying_key_fingerprint(&config_2kib)?, fingerprint_2kib);
+    assert_ne!(verifying_key_fingerprint(&config_4kib)?, fingerprint_2kib);
+
+    Ok(())
+}
+
+/// Returns a porep config for `sector_size`, with its verifying key in the parameter cache.
+fn cached_porep_config<Tree: 'static + MerkleTreeTrait>(sector_size: u64) -> Result<PoRepConfig> {
+    let config = PoRepConfig::for_sector_size(sector_size, [0; 32], ApiVersion::V1_1_0)?;
+    let public_params = public_params::<Tree>(
+        PaddedBytesAmount::from(config),
+        usize::from(PoRepProofPartitions::from(config)),
+        config.porep_id,
+        config.api_version,
+    )?;
+    <StackedCompound<Tree, DefaultPieceHasher> as CompoundProof<
+        StackedDrg<'_, Tree, DefaultPieceHasher>,
+        _,
+    >>::verifying_key(Some(&mut OsRng), &public_params)?;
+
+    Ok(config)
+}
+
+#[test]
+fn test_published_parameter_ids_256mib() {
+    let _lock = PARAMETERS_LOCK.lock().expect("parameters lock poisoned");
+    let err = published_parameter_ids(SECTOR_SIZE_256_MIB)
+        .expect_err("found published parameters for 256 MiB");
+    match err.downcast_ref::<Error>() {
+        Some(Error::ParameterNotFound(cache_id)) => {
+            let manifest =
+                sector_size_manifest(SECTOR_SIZE_256_MIB).expect("failed to get manifest");
+            assert_eq!(manifest["cache_ids"]["porep"], *cache_id);
+        }
+        _ => panic!("unexpected error: {:?}", err),
+    }
+
+    assert!(published_parameter_ids(SECTOR_SIZE_2_KIB + 1).is_err());
+
+    assert!(!PUBLISHED_SECTOR_SIZES.contains(&SECTOR_SIZE_256_MIB));
+    for sector_size in &PUBLISHED_SECTOR_SIZES {
+        assert!(
+            published_parameter_ids(*sector_size).is_ok(),
+            "no published parameters for {}",
+            sector_size
+        );
+    }
+}
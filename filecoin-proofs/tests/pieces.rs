@@ -1,16 +1,25 @@
-use std::io::{Cursor, Read};
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{Cursor, Read, Write};
 use std::iter::Iterator;
 
 use anyhow::Result;
 use bellperson::bls::Fr;
+use filecoin_hashers::{poseidon::PoseidonHasher, HashFunction, Hasher};
 use filecoin_proofs::{
-    add_piece, commitment_from_fr,
+    add_piece, add_piece_from_readers, add_piece_streaming, commitment_from_fr,
+    compute_piece_commitments,
+    error::Error,
+    generate_piece_commitment, max_unpadded_bytes_for_sector,
     pieces::{
-        compute_comm_d, get_piece_alignment, get_piece_start_byte, piece_hash, verify_pieces,
-        zero_padding, EmptySource, PieceAlignment,
+        combine_subtree_commitments, compute_comm_d, compute_comm_d_with_hasher,
+        get_piece_alignment, get_piece_start_byte, piece_hash, validate_piece_placement,
+        validate_piece_size, verify_pieces, zero_padding, EmptySource, PieceAlignment,
     },
-    Commitment, DataTree, DefaultPieceHasher, PaddedBytesAmount, PieceInfo, SectorSize,
-    UnpaddedByteIndex, UnpaddedBytesAmount, DRG_DEGREE, EXP_DEGREE, TEST_SEED,
+    write_and_preprocess, write_and_preprocess_streaming, zero_comm_d, CommD, Commitment, DataTree,
+    DefaultPieceHasher, IncrementalDataTreeBuilder, PaddedBytesAmount, PieceInfo, SectorSize,
+    StreamingCommDBuilder, UnpaddedByteIndex, UnpaddedBytesAmount, DRG_DEGREE, EXP_DEGREE,
+    SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_GIB, SECTOR_SIZE_8_MIB, TEST_SEED,
 };
 use rand::{Rng, RngCore, SeedableRng};
 use rand_xorshift::XorShiftRng;
@@ -18,6 +27,7 @@ use storage_proofs_core::{
     api_version::ApiVersion, drgraph::Graph, merkle::create_base_merkle_tree, util::NODE_SIZE,
 };
 use storage_proofs_porep::stacked::StackedBucketGraph;
+use tempfile::tempdir;
 
 #[test]
 fn test_empty_source() {
@@ -29,6 +39,163 @@ fn test_empty_source() {
     assert_eq!(target, vec![0u8; 12]);
 }
 
+#[test]
+fn test_add_piece_streaming() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let piece_size = UnpaddedBytesAmount::from(PaddedBytesAmount(1 << 22));
+    let mut piece_bytes = vec![0u8; u64::from(piece_size) as usize];
+    rng.fill_bytes(&mut piece_bytes);
+
+    let mut chunks = 0;
+    let mut consumed = 0;
+    let mut streamed = Vec::new();
+    let (streamed_info, streamed_written) =
+        add_piece_streaming(Cursor::new(&piece_bytes), &mut streamed, piece_size, |n| {
+            chunks += 1;
+            consumed += n;
+        })?;
+
+    assert!(chunks > 1, "piece was not streamed in chunks");
+    assert_eq!(consumed, u64::from(piece_size));
+
+    let mut preprocessed = Vec::new();
+    let (piece_info, written) =
+        write_and_preprocess(Cursor::new(&piece_bytes), &mut preprocessed, piece_size)?;
+
+    assert_eq!(streamed_info, piece_info);
+    assert_eq!(streamed_written, written);
+    assert_eq!(streamed, preprocessed);
+
+    Ok(())
+}
+
+#[test]
+fn test_add_piece_from_readers() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let piece_size = UnpaddedBytesAmount::from(PaddedBytesAmount(1024));
+    let mut piece_bytes = vec![0u8; u64::from(piece_size) as usize];
+    rng.fill_bytes(&mut piece_bytes);
+
+    let mut concatenated = Vec::new();
+    let (piece_info, written) = add_piece(
+        Cursor::new(&piece_bytes),
+        &mut concatenated,
+        piece_size,
+        &[],
+    )?;
+
+    // Split the piece in the middle of an fr32 chunk.
+    let split = 100;
+    let sources: Vec<Box<dyn Read>> = vec![
+        Box::new(Cursor::new(piece_bytes[..split].to_vec())),
+        Box::new(Cursor::new(piece_bytes[split..].to_vec())),
+    ];
+    let sizes = [
+        UnpaddedBytesAmount(split as u64),
+        UnpaddedBytesAmount((piece_bytes.len() - split) as u64),
+    ];
+    let mut segmented = Vec::new();
+    let (segmented_info, segmented_written) =
+        add_piece_from_readers(sources, &sizes, &mut segmented, &[])?;
+
+    assert_eq!(segmented_info, piece_info);
+    assert_eq!(segmented_written, written);
+    assert_eq!(segmented, concatenated);
+
+    // A source shorter than its size is an error, rather than shifting the following data.
+    let sources: Vec<Box<dyn Read>> = vec![
+        Box::new(Cursor::new(piece_bytes[..split - 1].to_vec())),
+        Box::new(Cursor::new(piece_bytes[split..].to_vec())),
+    ];
+    assert!(add_piece_from_readers(sources, &sizes, Vec::new(), &[]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_compute_piece_commitments() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let dir = tempdir()?;
+
+    let mut pieces = Vec::new();
+    for (i, size) in [508, 127, 1016].iter().enumerate() {
+        let path = dir.path().join(format!("piece-{}", i));
+        let mut data = vec![0u8; *size];
+        rng.fill_bytes(&mut data);
+        fs::write(&path, &data)?;
+        pieces.push((path, UnpaddedBytesAmount(*size as u64)));
+    }
+
+    let piece_infos = compute_piece_commitments(&pieces)?;
+    assert_eq!(piece_infos.len(), pieces.len());
+    for ((path, size), piece_info) in pieces.iter().zip(&piece_infos) {
+        let expected = generate_piece_commitment(fs::File::open(path)?, *size)?;
+        assert_eq!(piece_info, &expected);
+    }
+
+    assert!(
+        compute_piece_commitments(&[(dir.path().join("missing"), UnpaddedBytesAmount(127))])
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_write_and_preprocess_streaming() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let piece_size = UnpaddedBytesAmount::from(PaddedBytesAmount(1 << 22));
+    let mut piece_bytes = vec![0u8; u64::from(piece_size) as usize];
+    rng.fill_bytes(&mut piece_bytes);
+
+    let mut streamed = Vec::new();
+    let mut writer = write_and_preprocess_streaming(&mut streamed);
+    let mut rest = &piece_bytes[..];
+    while !rest.is_empty() {
+        let len = std::cmp::min(rng.gen_range(1, 100_000), rest.len());
+        writer.write_all(&rest[..len])?;
+        rest = &rest[len..];
+    }
+    let (streamed_written, streamed_info) = writer.finish()?;
+
+    let mut preprocessed = Vec::new();
+    let (piece_info, written) =
+        write_and_preprocess(Cursor::new(&piece_bytes), &mut preprocessed, piece_size)?;
+
+    assert_eq!(streamed_info, piece_info);
+    assert_eq!(streamed_written, PaddedBytesAmount::from(written));
+    assert_eq!(streamed, preprocessed);
+
+    let mut writer = write_and_preprocess_streaming(Vec::new());
+    writer.write_all(&piece_bytes[..200])?;
+    assert!(writer.finish().is_err(), "accepted a partial block");
+
+    Ok(())
+}
+
+#[test]
+fn test_max_unpadded_bytes_for_sector() {
+    assert_eq!(
+        max_unpadded_bytes_for_sector(SECTOR_SIZE_2_KIB),
+        UnpaddedBytesAmount(2032)
+    );
+    assert_eq!(
+        max_unpadded_bytes_for_sector(SECTOR_SIZE_32_GIB),
+        UnpaddedBytesAmount(34_091_302_912)
+    );
+
+    for &sector_size in &[SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_GIB] {
+        let max = max_unpadded_bytes_for_sector(sector_size);
+        assert_eq!(PaddedBytesAmount::from(max), PaddedBytesAmount(sector_size));
+        assert!(
+            PaddedBytesAmount::from(max + UnpaddedBytesAmount(1)) > PaddedBytesAmount(sector_size)
+        );
+    }
+}
+
 #[test]
 fn test_compute_comm_d_empty() {
     let comm_d =
@@ -48,6 +215,47 @@ fn test_compute_comm_d_empty() {
     );
 }
 
+#[test]
+fn test_compute_comm_d_with_hasher() -> Result<()> {
+    let mut a = [1u8; 32];
+    let mut b = [2u8; 32];
+    // Keep the commitments valid field elements, so they can be fed to Poseidon.
+    a[31] = 0;
+    b[31] = 0;
+    let piece_infos = vec![
+        PieceInfo::new(a, UnpaddedBytesAmount(127))?,
+        PieceInfo::new(b, UnpaddedBytesAmount(127))?,
+    ];
+
+    for sector_size in &[SectorSize(256), SectorSize(2048)] {
+        assert_eq!(
+            compute_comm_d_with_hasher::<DefaultPieceHasher>(*sector_size, &[])?,
+            compute_comm_d(*sector_size, &[])?,
+        );
+        assert_eq!(
+            compute_comm_d_with_hasher::<DefaultPieceHasher>(*sector_size, &piece_infos)?,
+            compute_comm_d(*sector_size, &piece_infos)?,
+        );
+    }
+
+    let comm_d = compute_comm_d_with_hasher::<PoseidonHasher>(SectorSize(256), &piece_infos)?;
+    let mut buf = [0u8; 64];
+    buf[..32].copy_from_slice(&a);
+    buf[32..].copy_from_slice(&b);
+    let expected = <PoseidonHasher as Hasher>::Function::hash(&buf);
+    assert_eq!(&comm_d[..], expected.as_ref());
+    assert_ne!(comm_d, compute_comm_d(SectorSize(256), &piece_infos)?);
+
+    let empty = compute_comm_d_with_hasher::<PoseidonHasher>(SectorSize(2048), &[])?;
+    assert_eq!(
+        empty,
+        compute_comm_d_with_hasher::<PoseidonHasher>(SectorSize(2048), &[])?
+    );
+    assert_ne!(empty, compute_comm_d(SectorSize(2048), &[])?);
+
+    Ok(())
+}
+
 #[test]
 fn test_get_piece_alignment() {
     let table = vec![
@@ -103,6 +311,73 @@ fn test_get_piece_start_byte() {
     );
 }
 
+#[test]
+fn test_validate_piece_size() {
+    validate_piece_size(UnpaddedBytesAmount(127)).expect("minimum piece size rejected");
+    validate_piece_size(UnpaddedBytesAmount(127 << 20)).expect("1 MiB piece rejected");
+
+    let too_small = UnpaddedBytesAmount::from(PaddedBytesAmount(64));
+    let err = validate_piece_size(too_small).expect_err("accepted too small piece");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::InvalidPaddedPieceSize(_))
+    ));
+
+    let err = validate_piece_size(UnpaddedBytesAmount(381)).expect_err("accepted non power of two");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::InvalidPaddedPieceSize(384))
+    ));
+}
+
+#[test]
+fn test_validate_piece_placement() -> Result<()> {
+    let sector_size = SECTOR_SIZE_2_KIB;
+    let piece = |padded_size: u64| PieceInfo {
+        commitment: [1; 32],
+        size: UnpaddedBytesAmount::from(PaddedBytesAmount(padded_size)),
+    };
+
+    // The 512 byte piece is aligned to offset 512, after the first two pieces.
+    validate_piece_placement(
+        sector_size,
+        &[piece(128), piece(256), piece(512), piece(1024)],
+    )?;
+    validate_piece_placement(sector_size, &[piece(2048)])?;
+    validate_piece_placement(sector_size, &[])?;
+
+    // A piece which isn't a power of two can't be aligned without overlapping the next one.
+    let err = validate_piece_placement(sector_size, &[piece(128), piece(384), piece(128)])
+        .expect_err("accepted unaligned piece");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::InvalidPieceSize {
+            index: 1,
+            size: 384
+        })
+    ));
+    let err = validate_piece_placement(sector_size, &[piece(64)])
+        .expect_err("accepted piece below the minimum size");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::InvalidPieceSize { index: 0, .. })
+    ));
+
+    // The padding in front of the 1024 byte piece pushes it past the end of the sector.
+    let err = validate_piece_placement(sector_size, &[piece(128), piece(1024), piece(1024)])
+        .expect_err("accepted pieces exceeding the sector");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::PieceExceedsSector {
+            index: 2,
+            end: 3072,
+            sector_size: SECTOR_SIZE_2_KIB,
+        })
+    ));
+
+    Ok(())
+}
+
 #[test]
 fn test_verify_simple_pieces() {
     let rng = &mut XorShiftRng::from_seed(TEST_SEED);
@@ -361,6 +636,49 @@ fn test_verify_random_pieces() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_compute_comm_d_with_padding() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let sector_size = SectorSize(SECTOR_SIZE_2_KIB);
+
+    // The second piece is aligned to its size, leaving a gap after the first one, and the
+    // pieces don't fill the sector.
+    let piece_sizes = [
+        UnpaddedBytesAmount::from(PaddedBytesAmount(128)),
+        UnpaddedBytesAmount::from(PaddedBytesAmount(512)),
+    ];
+    let mut staged_sector = Vec::new();
+    let mut piece_infos = Vec::new();
+    for (i, piece_size) in piece_sizes.iter().enumerate() {
+        let mut piece_bytes = vec![0u8; u64::from(*piece_size) as usize];
+        rng.fill_bytes(&mut piece_bytes);
+
+        let (piece_info, _) = add_piece(
+            Cursor::new(&piece_bytes),
+            &mut staged_sector,
+            *piece_size,
+            &piece_sizes[..i],
+        )?;
+        piece_infos.push(piece_info);
+    }
+    assert_eq!(staged_sector.len(), 1024);
+    staged_sector.resize(u64::from(sector_size) as usize, 0);
+
+    let data_tree = create_base_merkle_tree::<DataTree>(
+        None,
+        u64::from(sector_size) as usize / NODE_SIZE,
+        &staged_sector,
+    )?;
+    let comm_d_root: Fr = data_tree.root().into();
+
+    assert_eq!(
+        compute_comm_d(sector_size, &piece_infos)?,
+        commitment_from_fr(comm_d_root)
+    );
+
+    Ok(())
+}
+
 fn build_sector(
     piece_sizes: &[UnpaddedBytesAmount],
     sector_size: SectorSize,
@@ -414,3 +732,154 @@ fn prev_power_of_two(mut x: u32) -> u32 {
     x |= x >> 16;
     x - (x >> 1)
 }
+
+#[test]
+fn test_zero_comm_d() -> Result<()> {
+    for &sector_size in &[SECTOR_SIZE_2_KIB, SECTOR_SIZE_8_MIB] {
+        // Hashes the whole zero filled sector.
+        let expected = compute_comm_d(SectorSize(sector_size), &[])?;
+        assert_eq!(zero_comm_d(sector_size)?, expected);
+    }
+
+    assert!(zero_comm_d(3 << 10).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_incremental_data_tree_builder() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let sector_size = SectorSize(SECTOR_SIZE_2_KIB);
+
+    let pieces: Vec<Vec<u8>> = [512, 256, 256]
+        .iter()
+        .map(|&size| {
+            let mut piece = vec![0u8; size];
+            rng.fill_bytes(&mut piece);
+            // Keep every node a valid field element, as after preprocessing.
+            for node in piece.chunks_mut(NODE_SIZE) {
+                node[NODE_SIZE - 1] &= 0b0011_1111;
+            }
+            piece
+        })
+        .collect();
+
+    let mut builder = IncrementalDataTreeBuilder::new(sector_size)?;
+    for piece in &pieces {
+        // Pieces don't need to arrive in whole nodes.
+        for chunk in piece.chunks(100) {
+            builder.add_leaves(chunk)?;
+        }
+    }
+    let comm_d = builder.finalize()?;
+
+    let mut staged_sector = pieces.concat();
+    staged_sector.resize(SECTOR_SIZE_2_KIB as usize, 0);
+    let data_tree =
+        create_base_merkle_tree::<DataTree>(None, staged_sector.len() / NODE_SIZE, &staged_sector)?;
+    let mut expected = [0u8; 32];
+    expected.copy_from_slice(data_tree.root().as_ref());
+    assert_eq!(comm_d, expected);
+
+    let empty = IncrementalDataTreeBuilder::new(sector_size)?.finalize()?;
+    assert_eq!(empty, zero_comm_d(SECTOR_SIZE_2_KIB)?);
+
+    let mut builder = IncrementalDataTreeBuilder::new(sector_size)?;
+    builder.add_leaves(&[1u8; 33])?;
+    assert!(builder.clone().finalize().is_err());
+    assert!(builder
+        .add_leaves(&vec![0u8; SECTOR_SIZE_2_KIB as usize])
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_streaming_comm_d_builder_16mib() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let sector_size = 16 << 20;
+
+    let mut staged_sector = vec![0u8; sector_size];
+    rng.fill_bytes(&mut staged_sector);
+    // Keep every node a valid field element, as after preprocessing.
+    for node in staged_sector.chunks_mut(NODE_SIZE) {
+        node[NODE_SIZE - 1] &= 0b0011_1111;
+    }
+
+    let mut builder = StreamingCommDBuilder::new(Vec::new(), SectorSize(sector_size as u64))?;
+    // Chunks don't need to line up with nodes.
+    for chunk in staged_sector.chunks(1000) {
+        builder.write_all(chunk)?;
+    }
+    assert!(builder.write_all(&[0u8; NODE_SIZE]).is_err());
+    let (written, comm_d) = builder.finalize()?;
+    assert!(written == staged_sector, "written data differs");
+
+    let data_tree =
+        create_base_merkle_tree::<DataTree>(None, sector_size / NODE_SIZE, &staged_sector)?;
+    let mut expected = [0u8; 32];
+    expected.copy_from_slice(data_tree.root().as_ref());
+    assert_eq!(comm_d, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_streaming_comm_d_builder_failed_write() -> Result<()> {
+    let mut data = [0u8; 3 * NODE_SIZE];
+    for (i, node) in data.chunks_mut(NODE_SIZE).enumerate() {
+        node[0] = i as u8 + 1;
+    }
+
+    // The target only has room for two nodes, so writing the third one fails.
+    let mut target = [0u8; 2 * NODE_SIZE];
+    let mut builder = StreamingCommDBuilder::new(&mut target[..], SectorSize(SECTOR_SIZE_2_KIB))?;
+    builder.write_all(&data[..2 * NODE_SIZE])?;
+    assert!(builder.write_all(&data[2 * NODE_SIZE..]).is_err());
+    let (_, comm_d) = builder.finalize()?;
+
+    let mut expected = IncrementalDataTreeBuilder::new(SectorSize(SECTOR_SIZE_2_KIB))?;
+    expected.add_leaves(&data[..2 * NODE_SIZE])?;
+    assert_eq!(comm_d, expected.finalize()?);
+
+    Ok(())
+}
+
+#[test]
+fn test_combine_subtree_commitments() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let mut staged_sector = vec![0u8; SECTOR_SIZE_8_MIB as usize];
+    rng.fill_bytes(&mut staged_sector);
+    // Keep every node a valid field element, as after preprocessing.
+    for node in staged_sector.chunks_mut(NODE_SIZE) {
+        node[NODE_SIZE - 1] &= 0b0011_1111;
+    }
+
+    let data_tree_root = |data: &[u8]| -> Result<CommD> {
+        let tree = create_base_merkle_tree::<DataTree>(None, data.len() / NODE_SIZE, data)?;
+        let mut root = [0u8; 32];
+        root.copy_from_slice(tree.root().as_ref());
+        CommD::try_from(root)
+    };
+
+    let comm_d = data_tree_root(&staged_sector)?;
+
+    // Two 4MiB halves, as built on separate machines.
+    let (left, right) = staged_sector.split_at(staged_sector.len() / 2);
+    let combined = combine_subtree_commitments(data_tree_root(left)?, data_tree_root(right)?);
+    assert_eq!(combined, comm_d);
+
+    // Recursively from the quarters.
+    let quarters = staged_sector
+        .chunks(staged_sector.len() / 4)
+        .map(data_tree_root)
+        .collect::<Result<Vec<_>>>()?;
+    let combined = combine_subtree_commitments(
+        combine_subtree_commitments(quarters[0], quarters[1]),
+        combine_subtree_commitments(quarters[2], quarters[3]),
+    );
+    assert_eq!(combined, comm_d);
+
+    Ok(())
+}
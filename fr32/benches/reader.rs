@@ -0,0 +1,29 @@
+use std::io::{Cursor, Read};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use fr32::Fr32Reader;
+use rand::{thread_rng, RngCore};
+
+fn reader_benchmark(c: &mut Criterion) {
+    // 8192 blocks of 127 bytes, padded to 1 MiB.
+    let mut data = vec![0u8; 127 * 8192];
+    thread_rng().fill_bytes(&mut data);
+
+    let mut group = c.benchmark_group("fr32-reader");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("pad-1MiB", |b| {
+        let mut padded = Vec::with_capacity(128 * 8192);
+
+        b.iter(|| {
+            padded.clear();
+            Fr32Reader::new(Cursor::new(&data))
+                .read_to_end(&mut padded)
+                .unwrap();
+            black_box(&padded);
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, reader_benchmark);
+criterion_main!(benches);
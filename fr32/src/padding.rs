@@ -1,5 +1,7 @@
 use std::cmp::{min, Ordering};
-use std::io::{self, Error, ErrorKind, Write};
+use std::io::{self, Error, ErrorKind, Read, Write};
+
+use crate::Fr32Reader;
 
 /** PaddingMap represents a mapping between data and its padded equivalent.
 
@@ -611,6 +613,83 @@ where
     Ok(written)
 }
 
+/// Number of full 127 byte blocks (4 `Fr32` elements) padded or unpadded at once by
+/// `write_padded` and `write_unpadded_stream`.
+const STREAM_CHUNK_BLOCKS: usize = 1024;
+const UNPADDED_STREAM_CHUNK_SIZE: usize = 127 * STREAM_CHUNK_BLOCKS;
+const PADDED_STREAM_CHUNK_SIZE: usize = 128 * STREAM_CHUNK_BLOCKS;
+
+/// Pads all raw data read from `source` and writes it to `target`, exactly like the
+/// preprocessing done before sealing. Returns the number of padded bytes written.
+///
+/// If the raw data isn't a multiple of 127 bytes the last element is incomplete, the output
+/// is then `to_padded_bytes` of the raw data length long, which `write_unpadded_stream`
+/// turns back into the original data.
+pub fn write_padded<R, W>(mut source: R, mut target: W) -> io::Result<u64>
+where
+    R: Read,
+    W: Write,
+{
+    let mut chunk = vec![0u8; UNPADDED_STREAM_CHUNK_SIZE];
+    let mut padded = Vec::with_capacity(PADDED_STREAM_CHUNK_SIZE);
+    let mut written = 0;
+
+    loop {
+        let chunk_len = read_chunk(&mut source, &mut chunk)?;
+        if chunk_len == 0 {
+            break;
+        }
+
+        // Only the last chunk can be partial, so padding chunk by chunk gives the same
+        // result as padding the whole stream at once.
+        padded.clear();
+        Fr32Reader::new(&chunk[..chunk_len]).read_to_end(&mut padded)?;
+        padded.truncate(to_padded_bytes(chunk_len));
+        target.write_all(&padded)?;
+        written += padded.len() as u64;
+    }
+
+    Ok(written)
+}
+
+/// Recovers the raw data from the padded data read from `source`, the inverse of
+/// `write_padded`, and writes it to `target`. Returns the number of raw bytes written.
+pub fn write_unpadded_stream<R, W>(mut source: R, mut target: W) -> io::Result<u64>
+where
+    R: Read,
+    W: Write,
+{
+    let mut chunk = vec![0u8; PADDED_STREAM_CHUNK_SIZE];
+    let mut written = 0;
+
+    loop {
+        let chunk_len = read_chunk(&mut source, &mut chunk)?;
+        if chunk_len == 0 {
+            break;
+        }
+
+        let raw_len = to_unpadded_bytes(chunk_len as u64) as usize;
+        written += write_unpadded(&chunk[..chunk_len], &mut target, 0, raw_len)? as u64;
+    }
+
+    Ok(written)
+}
+
+/// Reads from `source` until `buf` is full or the end of the data is reached.
+fn read_chunk<R: Read>(source: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut bytes_read = 0;
+    while bytes_read < buf.len() {
+        match source.read(&mut buf[bytes_read..]) {
+            Ok(0) => break,
+            Ok(n) => bytes_read += n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(bytes_read)
+}
+
 /**  Unpadding process.
 
 Read a `source` of padded data and recover from it the byte-aligned
@@ -750,15 +829,13 @@ where
 mod tests {
     use super::*;
 
-    use std::io::{Cursor, Read};
+    use std::io::Cursor;
 
     use bitvec::{order::Lsb0 as LittleEndian, vec::BitVec};
     use itertools::Itertools;
     use rand::{Rng, SeedableRng};
     use rand_xorshift::XorShiftRng;
 
-    use crate::Fr32Reader;
-
     const TEST_SEED: [u8; 16] = [
         0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc,
         0xe5,
@@ -928,6 +1005,54 @@ mod tests {
         }
     }
 
+    // `write_padded` and `write_unpadded_stream` for lengths around the 127 byte block
+    // and the chunk boundaries, check the padding and the recovered raw data.
+    #[test]
+    fn test_write_padded_stream_round_trip() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+        for &len in &[
+            1,
+            31,
+            32,
+            126,
+            127,
+            128,
+            253,
+            254,
+            255,
+            UNPADDED_STREAM_CHUNK_SIZE - 1,
+            UNPADDED_STREAM_CHUNK_SIZE,
+            UNPADDED_STREAM_CHUNK_SIZE + 1,
+            UNPADDED_STREAM_CHUNK_SIZE + 127,
+        ] {
+            let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+
+            let mut padded = Vec::new();
+            let padded_written =
+                write_padded(Cursor::new(&data), &mut padded).expect("padded write failed");
+            assert_eq!(padded_written, padded.len() as u64);
+            assert_eq!(padded.len(), to_padded_bytes(len));
+            if len % 127 == 0 {
+                let mut expected = Vec::new();
+                Fr32Reader::new(Cursor::new(&data))
+                    .read_to_end(&mut expected)
+                    .expect("in-memory read failed");
+                assert_eq!(padded, expected);
+            }
+            assert_eq!(
+                padded.clone().into_boxed_slice(),
+                bit_vec_padding(data.clone())
+            );
+
+            let mut unpadded = Vec::new();
+            let unpadded_written = write_unpadded_stream(Cursor::new(&padded), &mut unpadded)
+                .expect("un-padded write failed");
+            assert_eq!(unpadded_written, len as u64);
+            assert_eq!(data, unpadded);
+        }
+    }
+
     // TODO: Add a test that drops the last part of an element and tries to recover
     // the rest of the data (may already be present in some form in the above tests).
 }
@@ -24,6 +24,14 @@ const MASK_SKIP_HIGH_2: u128 = 0b0011_1111_1111_1111_1111_1111_1111_1111_1111_11
 #[repr(align(16))]
 struct AlignedBuffer([u8; NUM_BYTES_IN_BLOCK + 1]);
 
+/// The implementation used to pad blocks, detected at runtime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Implementation {
+    Portable,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+}
+
 /// An `io::Reader` that converts unpadded input into valid `Fr32` padded output.
 pub struct Fr32Reader<R> {
     /// The source being padded.
@@ -40,6 +48,7 @@ pub struct Fr32Reader<R> {
     available_frs: usize,
     /// Are we done reading?
     done: bool,
+    implementation: Implementation,
 }
 
 macro_rules! process_fr {
@@ -66,35 +75,14 @@ impl<R: Read> Fr32Reader<R> {
             out_offset: 0,
             available_frs: 0,
             done: false,
+            implementation: Implementation::detect(),
         }
     }
 
     /// Processes a single block in in_buffer, writing the result to out_buffer.
     fn process_block(&mut self) {
-        let in_buffer: &[u128] = {
-            #[cfg(target_arch = "aarch64")]
-            // Safety: This is safe because the struct/data is aligned on
-            // a 16 byte boundary and can therefore be casted from u128
-            // to u8 without alignment safety issues.
-            unsafe {
-                &mut (*(&self.in_buffer.0 as *const [u8] as *mut [u128]))
-            }
-            #[cfg(not(target_arch = "aarch64"))]
-            self.in_buffer.0.as_slice_of::<u128>().unwrap()
-        };
-        let out = &mut self.out_buffer;
-
-        // 0..254
-        {
-            out[0] = in_buffer[0];
-            out[1] = in_buffer[1] & MASK_SKIP_HIGH_2;
-        }
-        // 254..508
-        process_fr!(&in_buffer[1..], out[2], out[3], 2);
-        // 508..762
-        process_fr!(&in_buffer[3..], out[4], out[5], 4);
-        // 762..1016
-        process_fr!(&in_buffer[5..], out[6], out[7], 6);
+        self.implementation
+            .pad_block(&self.in_buffer, &mut self.out_buffer);
 
         // Reset buffer offset.
         self.out_offset = 0;
@@ -127,6 +115,88 @@ impl<R: Read> Fr32Reader<R> {
     }
 }
 
+impl Implementation {
+    fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return Implementation::Avx2;
+            }
+        }
+
+        Implementation::Portable
+    }
+
+    /// Pads the 127 bytes in `in_buffer` into the four Frs of `out`.
+    #[inline]
+    fn pad_block(self, in_buffer: &AlignedBuffer, out: &mut [u128; NUM_U128S_PER_BLOCK]) {
+        match self {
+            Implementation::Portable => pad_block_portable(in_buffer, out),
+            // Safety: AVX2 is only detected if the CPU supports it.
+            #[cfg(target_arch = "x86_64")]
+            Implementation::Avx2 => unsafe { pad_block_avx2(in_buffer, out) },
+        }
+    }
+}
+
+fn pad_block_portable(in_buffer: &AlignedBuffer, out: &mut [u128; NUM_U128S_PER_BLOCK]) {
+    let in_buffer: &[u128] = {
+        #[cfg(target_arch = "aarch64")]
+        // Safety: This is safe because the struct/data is aligned on
+        // a 16 byte boundary and can therefore be casted from u128
+        // to u8 without alignment safety issues.
+        unsafe {
+            &*(&in_buffer.0 as *const [u8] as *const [u128])
+        }
+        #[cfg(not(target_arch = "aarch64"))]
+        in_buffer.0.as_slice_of::<u128>().unwrap()
+    };
+
+    // 0..254
+    {
+        out[0] = in_buffer[0];
+        out[1] = in_buffer[1] & MASK_SKIP_HIGH_2;
+    }
+    // 254..508
+    process_fr!(&in_buffer[1..], out[2], out[3], 2);
+    // 508..762
+    process_fr!(&in_buffer[3..], out[4], out[5], 4);
+    // 762..1016
+    process_fr!(&in_buffer[5..], out[6], out[7], 6);
+}
+
+/// Same as `pad_block_portable`, but shifts the four 64 bit words of an Fr at once.
+///
+/// Fr `k` starts `2 * k` bits below the word `4 * k` of the input, so its words are the
+/// words from `4 * k - 1` shifted right by `64 - 2 * k`, combined with the words from
+/// `4 * k` shifted left by `2 * k`.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn pad_block_avx2(in_buffer: &AlignedBuffer, out: &mut [u128; NUM_U128S_PER_BLOCK]) {
+    use std::arch::x86_64::*;
+
+    let input = in_buffer.0.as_ptr() as *const u64;
+    let output = out.as_mut_ptr() as *mut __m256i;
+    // Zeroes the high 2 bits of an Fr.
+    let mask = _mm256_set_epi64x(0x3fff_ffff_ffff_ffff, -1, -1, -1);
+
+    // 0..254
+    let fr = _mm256_loadu_si256(input as *const __m256i);
+    _mm256_storeu_si256(output, _mm256_and_si256(fr, mask));
+
+    // 254..1016
+    for k in 1..NUM_FRS_PER_BLOCK {
+        let shift = 2 * k as i64;
+        let low = _mm256_loadu_si256(input.add(4 * k - 1) as *const __m256i);
+        let high = _mm256_loadu_si256(input.add(4 * k) as *const __m256i);
+        let fr = _mm256_or_si256(
+            _mm256_srl_epi64(low, _mm_set_epi64x(0, 64 - shift)),
+            _mm256_sll_epi64(high, _mm_set_epi64x(0, shift)),
+        );
+        _mm256_storeu_si256(output.add(k), _mm256_and_si256(fr, mask));
+    }
+}
+
 /// Division of x by y, rounding up.
 /// x must be > 0
 #[inline]
@@ -329,6 +399,38 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_implementations_match() {
+        let implementation = Implementation::detect();
+        // Nothing to compare against without SIMD support.
+        if implementation == Implementation::Portable {
+            return;
+        }
+
+        for &len in &[1, 31, 32, 126, 127, 128, 254, 255, 381, 1000, 127 * 16 + 5] {
+            let data: Vec<u8> = (0..len).map(|_| random::<u8>()).collect();
+            assert_eq!(
+                pad_blocks(Implementation::Portable, &data),
+                pad_blocks(implementation, &data),
+                "{} bytes",
+                len
+            );
+        }
+    }
+
+    fn pad_blocks(implementation: Implementation, data: &[u8]) -> Vec<u8> {
+        let mut padded = Vec::new();
+        for chunk in data.chunks(NUM_BYTES_IN_BLOCK) {
+            let mut in_buffer = AlignedBuffer([0; NUM_BYTES_IN_BLOCK + 1]);
+            in_buffer.0[..chunk.len()].copy_from_slice(chunk);
+            let mut out = [0; NUM_U128S_PER_BLOCK];
+            implementation.pad_block(&in_buffer, &mut out);
+            padded.extend_from_slice(out.as_byte_slice());
+        }
+
+        padded
+    }
+
     fn bit_vec_padding(raw_data: Vec<u8>) -> Box<[u8]> {
         let mut padded_data: BitVec<LittleEndian, u8> = BitVec::new();
         let raw_data: BitVec<LittleEndian, u8> = BitVec::from(raw_data);
@@ -78,6 +78,19 @@ where
         pub_in: &S::PublicInputs,
         priv_in: &S::PrivateInputs,
         groth_params: &'b groth16::MappedParameters<Bls12>,
+    ) -> Result<MultiProof<'b>> {
+        Self::prove_with_rng(pub_params, pub_in, priv_in, groth_params, &mut OsRng)
+    }
+
+    /// prove_with_rng is prove, drawing the randomness of the groth proofs from `rng`.
+    /// Proofs are only zero-knowledge if `rng` is a secure, unpredictable source, use this
+    /// for reproducible test vectors only.
+    fn prove_with_rng<'b, R: RngCore>(
+        pub_params: &PublicParams<'a, S>,
+        pub_in: &S::PublicInputs,
+        priv_in: &S::PrivateInputs,
+        groth_params: &'b groth16::MappedParameters<Bls12>,
+        rng: &mut R,
     ) -> Result<MultiProof<'b>> {
         let partition_count = Self::partition_count(pub_params);
 
@@ -99,12 +112,13 @@ where
         ensure!(sanity_check, "sanity check failed");
 
         info!("snark_proof:start");
-        let groth_proofs = Self::circuit_proofs(
+        let groth_proofs = Self::circuit_proofs_with_rng(
             pub_in,
             vanilla_proofs,
             &pub_params.vanilla_params,
             groth_params,
             pub_params.priority,
+            rng,
         )?;
         info!("snark_proof:finish");
 
@@ -235,7 +249,26 @@ where
         groth_params: &groth16::MappedParameters<Bls12>,
         priority: bool,
     ) -> Result<Vec<groth16::Proof<Bls12>>> {
-        let mut rng = OsRng;
+        Self::circuit_proofs_with_rng(
+            pub_in,
+            vanilla_proofs,
+            pub_params,
+            groth_params,
+            priority,
+            &mut OsRng,
+        )
+    }
+
+    /// circuit_proofs_with_rng is circuit_proofs, drawing the randomness of the groth proofs
+    /// from `rng`.
+    fn circuit_proofs_with_rng<R: RngCore>(
+        pub_in: &S::PublicInputs,
+        vanilla_proofs: Vec<S::Proof>,
+        pub_params: &S::PublicParams,
+        groth_params: &groth16::MappedParameters<Bls12>,
+        priority: bool,
+        rng: &mut R,
+    ) -> Result<Vec<groth16::Proof<Bls12>>> {
         ensure!(
             !vanilla_proofs.is_empty(),
             "cannot create a circuit proof over missing vanilla proofs"
@@ -256,9 +289,9 @@ where
             .collect::<Result<Vec<_>>>()?;
 
         let groth_proofs = if priority {
-            create_random_proof_batch_in_priority(circuits, groth_params, &mut rng)?
+            create_random_proof_batch_in_priority(circuits, groth_params, rng)?
         } else {
-            create_random_proof_batch(circuits, groth_params, &mut rng)?
+            create_random_proof_batch(circuits, groth_params, rng)?
         };
 
         groth_proofs
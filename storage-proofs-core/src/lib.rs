@@ -29,6 +29,7 @@ pub mod por;
 pub mod proof;
 pub mod sector;
 pub mod settings;
+pub mod spans;
 pub mod test_helper;
 pub mod util;
 
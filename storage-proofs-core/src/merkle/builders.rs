@@ -8,6 +8,7 @@ use anyhow::ensure;
 use filecoin_hashers::{Domain, Hasher, PoseidonArity};
 use generic_array::typenum::{Unsigned, U0};
 use log::trace;
+use memmap::Mmap;
 use merkletree::{
     merkle::{
         get_merkle_tree_leafs, is_merkle_tree_size_valid, FromIndexedParallelIterator, MerkleTree,
@@ -227,6 +228,53 @@ pub fn create_base_merkle_tree<Tree: MerkleTreeTrait>(
     Ok(Tree::from_merkle(tree))
 }
 
+/// Builds a tree of shape `Tree` whose leaves are the nodes in `mmap`, e.g. a memory mapped
+/// replica.
+///
+/// Leaves are read straight from the map instead of being copied into a buffer first. Each
+/// base tree is stored according to `config`, split as by `split_config` when there are
+/// several, with `rows_to_discard` replacing `config.rows_to_discard`. The stored rows match
+/// those written by `create_base_merkle_tree` for the same data. Level cache stores don't keep
+/// the leaves, so such trees must be reopened with `create_lc_tree` to generate proofs.
+pub fn build_tree_from_mmap<Tree: MerkleTreeTrait>(
+    mmap: &Mmap,
+    mut config: StoreConfig,
+    rows_to_discard: usize,
+) -> Result<Tree>
+where
+    Tree::Store: 'static,
+{
+    let base_tree_count = get_base_tree_count::<Tree>();
+    ensure!(
+        mmap.len() % (base_tree_count * NODE_SIZE) == 0,
+        "Mapped data length {} is not a multiple of {} base trees",
+        mmap.len(),
+        base_tree_count
+    );
+    let base_tree_len = mmap.len() / NODE_SIZE / base_tree_count;
+
+    config.rows_to_discard = rows_to_discard;
+    if base_tree_count == 1 {
+        return create_base_merkle_tree::<Tree>(Some(config), base_tree_len, &mmap[..]);
+    }
+
+    let configs = split_config(config, base_tree_count)?;
+    let mut trees = Vec::with_capacity(base_tree_count);
+    for (data, config) in mmap.chunks(base_tree_len * NODE_SIZE).zip(configs) {
+        trees.push(create_base_merkle_tree::<
+            MerkleTreeWrapper<Tree::Hasher, Tree::Store, Tree::Arity>,
+        >(Some(config), base_tree_len, data)?);
+    }
+
+    let tree = if Tree::TopTreeArity::to_usize() > 0 {
+        ResTree::<Tree>::from_sub_trees_as_trees(trees)?
+    } else {
+        ResTree::<Tree>::from_trees(trees)?
+    };
+
+    Ok(Tree::from_merkle(tree.inner))
+}
+
 /// Construct a new level cache merkle tree, given the specified
 /// config.
 ///
@@ -0,0 +1,270 @@
+use std::fs::File;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use anyhow::{ensure, Context};
+use filecoin_hashers::Hasher;
+use generic_array::typenum::Unsigned;
+use memmap::{Mmap, MmapOptions};
+use merkletree::{
+    hash::Algorithm,
+    merkle::{get_merkle_tree_cache_size, get_merkle_tree_leafs, Element},
+    store::{ReplicaConfig, StoreConfig},
+};
+
+use crate::{
+    error::Result,
+    merkle::{get_base_tree_count, CachedProofSource, MerkleProof, MerkleTreeTrait},
+    util::NODE_SIZE,
+};
+
+/// Sibling hashes and position of the path node at every level of a merkle path.
+type ProofPath<D> = Vec<(Vec<D>, usize)>;
+
+/// Generates the inclusion proofs of a level cache tree, like tree_r_last, from memory maps of
+/// its files.
+///
+/// The base tree files and the replica are mapped once, so proving many challenges of a sector
+/// doesn't read the files again for every one of them. The rows discarded from the base trees
+/// are rebuilt from the replica, the same way `gen_cached_proof` of the tree opened with
+/// `create_lc_tree` does, so the proofs are the same.
+#[derive(Debug)]
+pub struct MmapLCTree<Tree: MerkleTreeTrait> {
+    replica: Mmap,
+    replica_offsets: Vec<usize>,
+    base_trees: Vec<Mmap>,
+    base_tree_leafs: usize,
+    /// Number of nodes in the lowest row stored in the base tree files.
+    cached_leafs: usize,
+    _tree: PhantomData<Tree>,
+}
+
+impl<Tree: MerkleTreeTrait> MmapLCTree<Tree> {
+    /// Maps the tree stored according to `configs`, with the leaves in the replica described
+    /// by `replica_config`. Takes the same arguments as `create_lc_tree`.
+    pub fn new(
+        base_tree_len: usize,
+        configs: &[StoreConfig],
+        replica_config: &ReplicaConfig,
+    ) -> Result<Self> {
+        let arity = Tree::Arity::to_usize();
+        ensure!(
+            configs.len() == get_base_tree_count::<Tree>(),
+            "Invalid tree-shape specified"
+        );
+        ensure!(
+            replica_config.offsets.len() == configs.len(),
+            "Replica offsets don't match the base trees"
+        );
+
+        let base_tree_leafs = get_merkle_tree_leafs(base_tree_len, arity)?;
+        let cache_size =
+            get_merkle_tree_cache_size(base_tree_leafs, arity, configs[0].rows_to_discard)?;
+        let cached_leafs = get_merkle_tree_leafs(cache_size, arity)?;
+
+        let base_trees = configs
+            .iter()
+            .map(|config| {
+                let path = StoreConfig::data_path(&config.path, &config.id);
+                let base_tree = map_file(&path)?;
+                ensure!(
+                    base_tree.len() == cache_size * NODE_SIZE,
+                    "Tree {:?} has {} bytes, expected {}",
+                    path,
+                    base_tree.len(),
+                    cache_size * NODE_SIZE
+                );
+
+                Ok(base_tree)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let replica = map_file(&replica_config.path)?;
+        for offset in &replica_config.offsets {
+            ensure!(
+                offset + base_tree_leafs * NODE_SIZE <= replica.len(),
+                "Replica {:?} is too short for its tree",
+                replica_config.path
+            );
+        }
+
+        Ok(MmapLCTree {
+            replica,
+            replica_offsets: replica_config.offsets.clone(),
+            base_trees,
+            base_tree_leafs,
+            cached_leafs,
+            _tree: PhantomData,
+        })
+    }
+
+    fn replica_node(&self, base_tree: usize, index: usize) -> <Tree::Hasher as Hasher>::Domain {
+        read_node(
+            &self.replica,
+            self.replica_offsets[base_tree] / NODE_SIZE + index,
+        )
+    }
+
+    /// The root is the last node stored for a base tree.
+    fn base_tree_root(&self, base_tree: usize) -> <Tree::Hasher as Hasher>::Domain {
+        let cache = &self.base_trees[base_tree];
+        read_node(cache, cache.len() / NODE_SIZE - 1)
+    }
+
+    /// Returns the path of the leaf at `index` through the base tree `base_tree`.
+    fn base_path(
+        &self,
+        base_tree: usize,
+        index: usize,
+    ) -> Result<ProofPath<<Tree::Hasher as Hasher>::Domain>> {
+        let arity = Tree::Arity::to_usize();
+        let mut path = Vec::new();
+
+        // Rebuild the discarded rows of the segment of leaves containing the leaf, up to the
+        // node which is stored in the lowest cached row.
+        let segment_width = self.base_tree_leafs / self.cached_leafs;
+        let segment_start = index / segment_width * segment_width;
+        let mut row: Vec<_> = (segment_start..segment_start + segment_width)
+            .map(|i| self.replica_node(base_tree, i))
+            .collect();
+        let mut row_index = index - segment_start;
+        let mut height = 0;
+        while row.len() > 1 {
+            let group_start = row_index / arity * arity;
+            path.push(path_element(
+                &row[group_start..group_start + arity],
+                row_index % arity,
+            ));
+
+            row = row
+                .chunks(arity)
+                .map(|nodes| hash_nodes::<Tree::Hasher>(nodes, height))
+                .collect();
+            row_index /= arity;
+            height += 1;
+        }
+
+        let cache = &self.base_trees[base_tree];
+        let mut row_index = index / segment_width;
+        ensure!(
+            row[0] == read_node(cache, row_index),
+            "Base tree {} doesn't match the replica",
+            base_tree
+        );
+
+        // The remaining rows are read from the map.
+        let mut row_start = 0;
+        let mut row_len = self.cached_leafs;
+        while row_len > 1 {
+            let group_start = row_start + row_index / arity * arity;
+            let nodes: Vec<_> = (group_start..group_start + arity)
+                .map(|i| read_node(cache, i))
+                .collect();
+            path.push(path_element(&nodes, row_index % arity));
+
+            row_start += row_len;
+            row_len /= arity;
+            row_index /= arity;
+        }
+
+        Ok(path)
+    }
+}
+
+impl<Tree: MerkleTreeTrait> CachedProofSource<Tree> for MmapLCTree<Tree> {
+    fn leafs(&self) -> usize {
+        self.base_tree_leafs * self.base_trees.len()
+    }
+
+    /// The discarded rows are rebuilt as the tree was stored, the proof is the same for any
+    /// `rows_to_discard`.
+    fn gen_cached_proof(
+        &self,
+        i: usize,
+        _rows_to_discard: Option<usize>,
+    ) -> Result<MerkleProof<Tree::Hasher, Tree::Arity, Tree::SubTreeArity, Tree::TopTreeArity>>
+    {
+        ensure!(
+            i < self.leafs(),
+            "Leaf index {} out of bounds of {} leaves",
+            i,
+            self.leafs()
+        );
+
+        let base_tree = i / self.base_tree_leafs;
+        let index = i % self.base_tree_leafs;
+        let leaf = self.replica_node(base_tree, index);
+        let base_path = self.base_path(base_tree, index)?;
+
+        let sub_tree_arity = Tree::SubTreeArity::to_usize();
+        if sub_tree_arity == 0 {
+            let root = self.base_tree_root(0);
+            return Ok(MerkleProof::from_paths(
+                base_path,
+                Vec::new(),
+                Vec::new(),
+                root,
+                leaf,
+            ));
+        }
+
+        // Every sub tree joins the roots of `sub_tree_arity` consecutive base trees.
+        let base_roots: Vec<_> = (0..self.base_trees.len())
+            .map(|base_tree| self.base_tree_root(base_tree))
+            .collect();
+        let sub_roots: Vec<_> = base_roots
+            .chunks(sub_tree_arity)
+            .map(|roots| hash_nodes::<Tree::Hasher>(roots, 0))
+            .collect();
+        let sub_tree = base_tree / sub_tree_arity;
+        let sub_path = vec![path_element(
+            &base_roots[sub_tree * sub_tree_arity..(sub_tree + 1) * sub_tree_arity],
+            base_tree % sub_tree_arity,
+        )];
+
+        if Tree::TopTreeArity::to_usize() == 0 {
+            return Ok(MerkleProof::from_paths(
+                base_path,
+                sub_path,
+                Vec::new(),
+                sub_roots[0],
+                leaf,
+            ));
+        }
+
+        let top_path = vec![path_element(&sub_roots, sub_tree)];
+        let root = hash_nodes::<Tree::Hasher>(&sub_roots, 0);
+
+        Ok(MerkleProof::from_paths(
+            base_path, sub_path, top_path, root, leaf,
+        ))
+    }
+}
+
+fn map_file(path: &Path) -> Result<Mmap> {
+    let file = File::open(path).with_context(|| format!("could not open {:?}", path))?;
+    let mmap = unsafe { MmapOptions::new().map(&file) }
+        .with_context(|| format!("could not mmap {:?}", path))?;
+
+    Ok(mmap)
+}
+
+fn read_node<D: Element>(data: &[u8], index: usize) -> D {
+    D::from_slice(&data[index * NODE_SIZE..(index + 1) * NODE_SIZE])
+}
+
+fn hash_nodes<H: Hasher>(nodes: &[H::Domain], height: usize) -> H::Domain {
+    H::Function::default().multi_node(nodes, height)
+}
+
+/// Splits `nodes` into the siblings of the node at `index` and its position.
+fn path_element<D: Copy>(nodes: &[D], index: usize) -> (Vec<D>, usize) {
+    let siblings = nodes
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != index)
+        .map(|(_, node)| *node)
+        .collect();
+
+    (siblings, index)
+}
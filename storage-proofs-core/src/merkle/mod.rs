@@ -9,10 +9,12 @@ use generic_array::typenum::{U0, U2, U4, U8};
 use merkletree::store::LevelCacheStore;
 
 mod builders;
+mod mmap_tree;
 mod proof;
 mod tree;
 
 pub use builders::*;
+pub use mmap_tree::*;
 pub use proof::*;
 pub use tree::*;
 
@@ -458,6 +458,39 @@ impl<
         TopTreeArity: PoseidonArity,
     > MerkleProof<H, BaseArity, SubTreeArity, TopTreeArity>
 {
+    /// Builds a proof from its path through the base, sub and top trees. Every element holds
+    /// the sibling hashes at one level and the position of the path node among them, as
+    /// returned by `path`. The sub and top paths are empty for trees without such layers.
+    pub(crate) fn from_paths(
+        base_path: Vec<(Vec<H::Domain>, usize)>,
+        sub_path: Vec<(Vec<H::Domain>, usize)>,
+        top_path: Vec<(Vec<H::Domain>, usize)>,
+        root: H::Domain,
+        leaf: H::Domain,
+    ) -> Self {
+        let base_proof = inclusion_path(base_path);
+        let data = if TopTreeArity::to_usize() > 0 {
+            ProofData::Top(TopProof::new(
+                base_proof,
+                inclusion_path(sub_path),
+                inclusion_path(top_path),
+                root,
+                leaf,
+            ))
+        } else if SubTreeArity::to_usize() > 0 {
+            ProofData::Sub(SubProof::new(
+                base_proof,
+                inclusion_path(sub_path),
+                root,
+                leaf,
+            ))
+        } else {
+            ProofData::Single(SingleProof::new(base_proof, root, leaf))
+        };
+
+        MerkleProof { data }
+    }
+
     pub fn new(n: usize) -> Self {
         let root = Default::default();
         let leaf = Default::default();
@@ -490,6 +523,19 @@ fn proof_to_single<H: Hasher, Arity: PoseidonArity, TargetArity: PoseidonArity>(
     SingleProof::new(path, root, leaf)
 }
 
+fn inclusion_path<H: Hasher, Arity: PoseidonArity>(
+    path: Vec<(Vec<H::Domain>, usize)>,
+) -> InclusionPath<H, Arity> {
+    path.into_iter()
+        .map(|(hashes, index)| PathElement {
+            hashes,
+            index,
+            _arity: Default::default(),
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
 /// 'lemma_start_index' is required because sub/top proofs start at
 /// index 0 and base proofs start at index 1 (skipping the leaf at the
 /// front)
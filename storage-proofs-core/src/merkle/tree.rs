@@ -51,6 +51,44 @@ pub trait MerkleTreeTrait: Send + Sync + Debug {
     ) -> Self;
 }
 
+/// Generates the cached inclusion proofs of a tree, as needed for PoSt.
+///
+/// Implemented by the trees themselves, and by `MmapLCTree`, which generates the same proofs
+/// from memory maps of the files of a level cache tree.
+pub trait CachedProofSource<Tree: MerkleTreeTrait>: Debug + Send + Sync {
+    /// Returns the number of leaves of the tree.
+    fn leafs(&self) -> usize;
+    /// Creates a merkle proof of the leaf at index `i`, like `MerkleTreeTrait::gen_cached_proof`.
+    fn gen_cached_proof(
+        &self,
+        i: usize,
+        rows_to_discard: Option<usize>,
+    ) -> Result<MerkleProof<Tree::Hasher, Tree::Arity, Tree::SubTreeArity, Tree::TopTreeArity>>;
+}
+
+impl<Tree: MerkleTreeTrait> CachedProofSource<Tree>
+    for MerkleTreeWrapper<
+        Tree::Hasher,
+        Tree::Store,
+        Tree::Arity,
+        Tree::SubTreeArity,
+        Tree::TopTreeArity,
+    >
+{
+    fn leafs(&self) -> usize {
+        self.inner.leafs()
+    }
+
+    fn gen_cached_proof(
+        &self,
+        i: usize,
+        rows_to_discard: Option<usize>,
+    ) -> Result<MerkleProof<Tree::Hasher, Tree::Arity, Tree::SubTreeArity, Tree::TopTreeArity>>
+    {
+        MerkleTreeTrait::gen_cached_proof(self, i, rows_to_discard)
+    }
+}
+
 pub struct MerkleTreeWrapper<
     H: Hasher,
     S: Store<<H as Hasher>::Domain>,
@@ -1,8 +1,8 @@
-use std::collections::{BTreeMap, HashSet};
-use std::fs::{create_dir_all, File, OpenOptions};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{create_dir_all, read_to_string, File, OpenOptions};
 use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::{Mutex, RwLock};
 use std::time::Instant;
 
 use anyhow::bail;
@@ -24,6 +24,13 @@ use crate::{
 /// Bump this when circuits change to invalidate the cache.
 pub const VERSION: usize = 28;
 
+/// Parameter versions whose circuits are the same as those of `VERSION`, so verifying keys
+/// published under any of them verify proofs of the current circuits.
+///
+/// When `VERSION` is bumped without changing a circuit, keep the previous versions listed, so
+/// verifiers which only have keys of an older version keep working during the upgrade.
+pub const CIRCUIT_COMPATIBLE_VERSIONS: &[usize] = &[VERSION];
+
 pub const GROTH_PARAMETER_EXT: &str = "params";
 pub const PARAMETER_METADATA_EXT: &str = "meta";
 pub const VERIFYING_KEY_EXT: &str = "vk";
@@ -45,36 +52,123 @@ pub const PARAMETERS_DATA: &str = include_str!("../parameters.json");
 lazy_static! {
     pub static ref PARAMETERS: ParameterMap =
         serde_json::from_str(PARAMETERS_DATA).expect("Invalid parameters.json");
+    static ref DEFAULT_PARAMETER_INDEX: ParameterIndex = ParameterIndex::new(&PARAMETERS);
+    /// Parameters loaded at runtime through `load_parameters_from_path`, which take precedence
+    /// over the builtin `PARAMETERS`.
+    static ref PARAMETER_OVERRIDE: RwLock<Option<&'static ParameterIndex>> = RwLock::new(None);
     /// Contains the parameters that were previously verified. This way the parameter files are
     /// only hashed once and not on every usage.
     static ref VERIFIED_PARAMETERS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
 }
 
+struct ParameterIndex {
+    parameters: &'static ParameterMap,
+    /// The groth parameter entries, keyed by cache id, so that lookups do not need to format
+    /// the parameter id first.
+    by_cache_id: HashMap<String, &'static ParameterData>,
+}
+
+impl ParameterIndex {
+    fn new(parameters: &'static ParameterMap) -> Self {
+        let by_cache_id = parameters
+            .iter()
+            .filter(|(id, _)| id.ends_with(GROTH_PARAMETER_EXT))
+            .filter_map(|(id, data)| Some((cache_id_from_parameter_filename(id)?, data)))
+            .collect();
+
+        ParameterIndex {
+            parameters,
+            by_cache_id,
+        }
+    }
+}
+
+fn parameter_index() -> &'static ParameterIndex {
+    let index = *PARAMETER_OVERRIDE
+        .read()
+        .expect("PARAMETER_OVERRIDE poisoned");
+    index.unwrap_or(&DEFAULT_PARAMETER_INDEX)
+}
+
+/// Replaces the builtin `parameters.json` with the one found at `path`.
+///
+/// This is meant to be called once at startup, as the previously loaded parameters are never
+/// freed.
+pub fn load_parameters_from_path(path: &Path) -> Result<()> {
+    let data = read_to_string(path)?;
+    let parameters: ParameterMap = serde_json::from_str(&data)
+        .map_err(|err| Error::InvalidParameters(format!("{}: {}", path.display(), err)))?;
+
+    let index: &'static ParameterIndex = Box::leak(Box::new(ParameterIndex::new(Box::leak(
+        Box::new(parameters),
+    ))));
+    *PARAMETER_OVERRIDE
+        .write()
+        .expect("PARAMETER_OVERRIDE poisoned") = Some(index);
+
+    Ok(())
+}
+
 pub fn parameter_id(cache_id: &str) -> String {
-    format!("v{}-{}.params", VERSION, cache_id)
+    parameter_id_for_version(VERSION, cache_id)
+}
+
+/// Like `parameter_id`, but for the parameters published under `version`.
+pub fn parameter_id_for_version(version: usize, cache_id: &str) -> String {
+    format!("v{}-{}.{}", version, cache_id, GROTH_PARAMETER_EXT)
 }
 
 pub fn verifying_key_id(cache_id: &str) -> String {
-    format!("v{}-{}.vk", VERSION, cache_id)
+    verifying_key_id_for_version(VERSION, cache_id)
+}
+
+/// Like `verifying_key_id`, but for the verifying key published under `version`.
+pub fn verifying_key_id_for_version(version: usize, cache_id: &str) -> String {
+    format!("v{}-{}.{}", version, cache_id, VERIFYING_KEY_EXT)
+}
+
+/// Returns true if the circuits of parameter version `version` are the same as the current
+/// ones, see `CIRCUIT_COMPATIBLE_VERSIONS`.
+pub fn is_circuit_compatible_version(version: usize) -> bool {
+    CIRCUIT_COMPATIBLE_VERSIONS.contains(&version)
 }
 
 pub fn metadata_id(cache_id: &str) -> String {
     format!("v{}-{}.meta", VERSION, cache_id)
 }
 
+/// Parses the cache id out of a parameter or verifying key filename, as produced by
+/// `parameter_id` and `verifying_key_id`.
+///
+/// Returns `None` if the filename is malformed or was written for a different `VERSION`.
+pub fn cache_id_from_parameter_filename(name: &str) -> Option<String> {
+    let stem = [GROTH_PARAMETER_EXT, VERIFYING_KEY_EXT]
+        .iter()
+        .find_map(|ext| name.strip_suffix(*ext)?.strip_suffix('.'))?;
+    let cache_id = stem.strip_prefix(&format!("v{}-", VERSION))?;
+
+    if cache_id.is_empty() {
+        None
+    } else {
+        Some(cache_id.to_string())
+    }
+}
+
 /// Get the correct parameter data for a given cache id.
 pub fn get_parameter_data_from_id(parameter_id: &str) -> Option<&ParameterData> {
-    PARAMETERS.get(parameter_id)
+    parameter_index().parameters.get(parameter_id)
 }
 
 /// Get the correct parameter data for a given cache id.
 pub fn get_parameter_data(cache_id: &str) -> Option<&ParameterData> {
-    PARAMETERS.get(&parameter_id(cache_id))
+    parameter_index().by_cache_id.get(cache_id).copied()
 }
 
 /// Get the correct verifying key data for a given cache id.
 pub fn get_verifying_key_data(cache_id: &str) -> Option<&ParameterData> {
-    PARAMETERS.get(&verifying_key_id(cache_id))
+    parameter_index()
+        .parameters
+        .get(&verifying_key_id(cache_id))
 }
 
 // TODO: use in memory lock as well, as file locks do not guarantee exclusive access across OSes.
@@ -151,7 +245,10 @@ pub fn parameter_cache_dir() -> PathBuf {
 }
 
 pub fn parameter_cache_params_path(parameter_set_identifier: &str) -> PathBuf {
-    let dir = Path::new(&parameter_cache_dir_name()).to_path_buf();
+    params_path_in(&parameter_cache_dir(), parameter_set_identifier)
+}
+
+fn params_path_in(dir: &Path, parameter_set_identifier: &str) -> PathBuf {
     dir.join(format!(
         "v{}-{}.{}",
         VERSION, parameter_set_identifier, GROTH_PARAMETER_EXT
@@ -167,7 +264,22 @@ pub fn parameter_cache_metadata_path(parameter_set_identifier: &str) -> PathBuf
 }
 
 pub fn parameter_cache_verifying_key_path(parameter_set_identifier: &str) -> PathBuf {
-    let dir = Path::new(&parameter_cache_dir_name()).to_path_buf();
+    verifying_key_path_in(&parameter_cache_dir(), parameter_set_identifier)
+}
+
+/// Like `parameter_cache_verifying_key_path`, but for the verifying key published under
+/// `version`.
+pub fn parameter_cache_verifying_key_path_for_version(
+    version: usize,
+    parameter_set_identifier: &str,
+) -> PathBuf {
+    parameter_cache_dir().join(verifying_key_id_for_version(
+        version,
+        parameter_set_identifier,
+    ))
+}
+
+fn verifying_key_path_in(dir: &Path, parameter_set_identifier: &str) -> PathBuf {
     dir.join(format!(
         "v{}-{}.{}",
         VERSION, parameter_set_identifier, VERIFYING_KEY_EXT
@@ -249,9 +361,20 @@ where
         rng: Option<&mut R>,
         circuit: C,
         pub_params: &P,
+    ) -> Result<groth16::MappedParameters<Bls12>> {
+        Self::get_groth_params_in(&parameter_cache_dir(), rng, circuit, pub_params)
+    }
+
+    /// Like `get_groth_params`, but reads and writes the parameters in `cache_dir` instead
+    /// of the parameter cache.
+    fn get_groth_params_in<R: RngCore>(
+        cache_dir: &Path,
+        rng: Option<&mut R>,
+        circuit: C,
+        pub_params: &P,
     ) -> Result<groth16::MappedParameters<Bls12>> {
         let id = Self::cache_identifier(pub_params);
-        let cache_path = ensure_ancestor_dirs_exist(parameter_cache_params_path(&id))?;
+        let cache_path = ensure_ancestor_dirs_exist(params_path_in(cache_dir, &id))?;
 
         let generate = || -> Result<_> {
             if let Some(rng) = rng {
@@ -301,17 +424,28 @@ where
         rng: Option<&mut R>,
         circuit: C,
         pub_params: &P,
+    ) -> Result<groth16::VerifyingKey<Bls12>> {
+        Self::get_verifying_key_in(&parameter_cache_dir(), rng, circuit, pub_params)
+    }
+
+    /// Like `get_verifying_key`, but reads and writes the verifying key and parameters in
+    /// `cache_dir` instead of the parameter cache.
+    fn get_verifying_key_in<R: RngCore>(
+        cache_dir: &Path,
+        rng: Option<&mut R>,
+        circuit: C,
+        pub_params: &P,
     ) -> Result<groth16::VerifyingKey<Bls12>> {
         let id = Self::cache_identifier(pub_params);
 
         let generate = || -> Result<groth16::VerifyingKey<Bls12>> {
-            let groth_params = Self::get_groth_params(rng, circuit, pub_params)?;
+            let groth_params = Self::get_groth_params_in(cache_dir, rng, circuit, pub_params)?;
             info!("Getting verifying key. (id: {})", &id);
             Ok(groth_params.vk)
         };
 
         // generate (or load) verifying key
-        let cache_path = ensure_ancestor_dirs_exist(parameter_cache_verifying_key_path(&id))?;
+        let cache_path = ensure_ancestor_dirs_exist(verifying_key_path_in(cache_dir, &id))?;
         match read_cached_verifying_key(&cache_path) {
             Ok(key) => Ok(key),
             Err(_) => write_cached_verifying_key(&cache_path, generate()?).map_err(Into::into),
@@ -492,3 +626,98 @@ where
     ensure_parent(&file_path)?;
     f(&mut open_file(&file_path)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_parameter_data() {
+        let cache_id = "proof-of-spacetime-fallback-merkletree-poseidon_hasher-8-0-0-0170db1f394b35d995252228ee359194b13199d259380541dc529fb0099096b0";
+
+        let data = get_parameter_data(cache_id).expect("missing parameter data");
+        assert!(std::ptr::eq(
+            data,
+            PARAMETERS
+                .get(&parameter_id(cache_id))
+                .expect("missing parameter data")
+        ));
+        assert_eq!(data.sector_size, 2048);
+
+        for _ in 0..1000 {
+            assert!(std::ptr::eq(
+                get_parameter_data(cache_id).expect("missing parameter data"),
+                data
+            ));
+        }
+
+        // Only groth parameters are indexed, not verifying keys.
+        assert!(DEFAULT_PARAMETER_INDEX
+            .by_cache_id
+            .keys()
+            .all(|cache_id| PARAMETERS.contains_key(&parameter_id(cache_id))));
+        assert!(get_parameter_data("unknown").is_none());
+    }
+
+    #[test]
+    fn test_cache_id_from_parameter_filename() {
+        let cache_id = "stacked-proof-of-replication-merkletree-poseidon_hasher-8-0-0-sha256_hasher-032d3138d22506ec0082ed72b2dcba18df18477904e35bafee82b3793b06832f";
+
+        assert_eq!(
+            cache_id_from_parameter_filename(&parameter_id(cache_id)).as_deref(),
+            Some(cache_id)
+        );
+        assert_eq!(
+            cache_id_from_parameter_filename(&verifying_key_id(cache_id)).as_deref(),
+            Some(cache_id)
+        );
+
+        // Parameters written for a different version must not be picked up.
+        let stale = format!("v{}-{}.params", VERSION - 1, cache_id);
+        assert_eq!(cache_id_from_parameter_filename(&stale), None);
+
+        // Malformed names.
+        assert_eq!(
+            cache_id_from_parameter_filename(&metadata_id(cache_id)),
+            None
+        );
+        assert_eq!(cache_id_from_parameter_filename(cache_id), None);
+        assert_eq!(
+            cache_id_from_parameter_filename(&format!("v{}-.params", VERSION)),
+            None
+        );
+        assert_eq!(cache_id_from_parameter_filename(".params"), None);
+    }
+
+    #[test]
+    fn test_ids_for_version() {
+        let cache_id = "stacked-proof-of-replication-merkletree-poseidon_hasher-8-0-0-sha256_hasher-032d3138d22506ec0082ed72b2dcba18df18477904e35bafee82b3793b06832f";
+
+        assert_eq!(
+            parameter_id_for_version(VERSION, cache_id),
+            parameter_id(cache_id)
+        );
+        assert_eq!(
+            verifying_key_id_for_version(VERSION, cache_id),
+            verifying_key_id(cache_id)
+        );
+
+        let older = VERSION - 1;
+        assert_eq!(
+            parameter_id_for_version(older, cache_id),
+            format!("v{}-{}.params", older, cache_id)
+        );
+        let vk_id = verifying_key_id_for_version(older, cache_id);
+        assert_eq!(vk_id, format!("v{}-{}.vk", older, cache_id));
+        assert_eq!(
+            parameter_cache_verifying_key_path_for_version(older, cache_id),
+            parameter_cache_dir().join(&vk_id)
+        );
+
+        assert!(is_circuit_compatible_version(VERSION));
+        assert!(CIRCUIT_COMPATIBLE_VERSIONS
+            .iter()
+            .all(|version| *version <= VERSION));
+        assert!(!is_circuit_compatible_version(VERSION + 1));
+    }
+}
@@ -0,0 +1,42 @@
+//! Spans for profiling with `tracing`.
+//!
+//! With the `tracing-spans` feature, `enter_span!` enters a `tracing` span at info level, which
+//! is exited when the returned guard is dropped. Spans entered while another one is entered are
+//! nested in it, so a subscriber can build a flamegraph of sealing and proving from them.
+//! Without the feature the macro expands to a unit guard, and neither the span nor its fields
+//! are evaluated.
+
+#[cfg(feature = "tracing-spans")]
+#[doc(hidden)]
+pub use tracing;
+
+/// Enters the span `name` with the given `tracing` fields until the guard is dropped.
+///
+/// ```ignore
+/// let _span = enter_span!("seal_commit_phase1", sector_id = ?sector_id);
+/// ```
+#[cfg(feature = "tracing-spans")]
+#[macro_export]
+macro_rules! enter_span {
+    ($name:literal $(, $($fields:tt)*)?) => {
+        $crate::spans::tracing::info_span!($name $(, $($fields)*)?).entered()
+    };
+}
+
+/// Enters the span `name` with the given `tracing` fields until the guard is dropped.
+///
+/// ```ignore
+/// let _span = enter_span!("seal_commit_phase1", sector_id = ?sector_id);
+/// ```
+#[cfg(not(feature = "tracing-spans"))]
+#[macro_export]
+macro_rules! enter_span {
+    ($name:literal $(, $($fields:tt)*)?) => {
+        $crate::spans::NoSpan
+    };
+}
+
+/// The guard returned by `enter_span!` without the `tracing-spans` feature.
+#[cfg(not(feature = "tracing-spans"))]
+#[derive(Debug)]
+pub struct NoSpan;
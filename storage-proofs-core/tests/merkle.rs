@@ -0,0 +1,129 @@
+use std::fs::{read, File};
+use std::io::Write;
+
+use filecoin_hashers::{poseidon::PoseidonHasher, Domain, Hasher};
+use generic_array::typenum::{Unsigned, U0, U2, U8};
+use memmap::MmapOptions;
+use merkletree::{merkle::get_merkle_tree_len, store::StoreConfig};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use storage_proofs_core::{
+    merkle::{
+        build_tree_from_mmap, create_base_merkle_tree, create_lc_tree, get_base_tree_count,
+        split_config_and_replica, CachedProofSource, LCTree, MerkleTreeTrait, MmapLCTree,
+    },
+    util::{default_rows_to_discard, NODE_SIZE},
+    TEST_SEED,
+};
+use tempfile::tempdir;
+
+type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+#[test]
+fn test_build_tree_from_mmap_8mib() {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let nodes = (8 << 20) / NODE_SIZE;
+    let rows_to_discard = default_rows_to_discard(nodes, 8);
+
+    let mut data = Vec::with_capacity(nodes * NODE_SIZE);
+    for _ in 0..nodes {
+        data.extend_from_slice(<PoseidonHasher as Hasher>::Domain::random(rng).as_ref());
+    }
+
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let replica_path = temp_dir.path().join("replica");
+    File::create(&replica_path)
+        .and_then(|mut f| f.write_all(&data))
+        .expect("failed to write replica");
+    let replica = File::open(&replica_path).expect("failed to open replica");
+    let mmap = unsafe { MmapOptions::new().map(&replica) }.expect("failed to map replica");
+
+    let mmap_dir = tempdir().expect("failed to create temp dir");
+    let mmap_config = StoreConfig::new(mmap_dir.path(), "tree-r-last".to_string(), 0);
+    let mmap_tree = build_tree_from_mmap::<Tree>(&mmap, mmap_config.clone(), rows_to_discard)
+        .expect("failed to build tree from mmap");
+
+    let disk_dir = tempdir().expect("failed to create temp dir");
+    let disk_config = StoreConfig::new(disk_dir.path(), "tree-r-last".to_string(), rows_to_discard);
+    let disk_tree = create_base_merkle_tree::<Tree>(Some(disk_config.clone()), nodes, &data)
+        .expect("failed to build tree");
+
+    assert_eq!(mmap_tree.root(), disk_tree.root());
+    assert_eq!(mmap_tree.row_count(), disk_tree.row_count());
+    assert_eq!(
+        read(StoreConfig::data_path(&mmap_config.path, &mmap_config.id))
+            .expect("failed to read mmap tree cache"),
+        read(StoreConfig::data_path(&disk_config.path, &disk_config.id))
+            .expect("failed to read tree cache"),
+    );
+}
+
+fn test_mmap_lc_tree<Tree: 'static + MerkleTreeTrait>(base_tree_leafs: usize) {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let base_tree_count = get_base_tree_count::<Tree>();
+    let nodes = base_tree_leafs * base_tree_count;
+    let rows_to_discard = default_rows_to_discard(base_tree_leafs, Tree::Arity::to_usize());
+
+    let mut data = Vec::with_capacity(nodes * NODE_SIZE);
+    for _ in 0..nodes {
+        data.extend_from_slice(<PoseidonHasher as Hasher>::Domain::random(rng).as_ref());
+    }
+
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let replica_path = temp_dir.path().join("replica");
+    File::create(&replica_path)
+        .and_then(|mut f| f.write_all(&data))
+        .expect("failed to write replica");
+    let replica = File::open(&replica_path).expect("failed to open replica");
+    let mmap = unsafe { MmapOptions::new().map(&replica) }.expect("failed to map replica");
+
+    let mut config = StoreConfig::new(temp_dir.path(), "tree-r-last".to_string(), 0);
+    build_tree_from_mmap::<Tree>(&mmap, config.clone(), rows_to_discard)
+        .expect("failed to build tree from mmap");
+
+    let base_tree_len = get_merkle_tree_len(base_tree_leafs, Tree::Arity::to_usize())
+        .expect("invalid base tree leafs");
+    config.rows_to_discard = rows_to_discard;
+    config.size = Some(base_tree_len);
+    let (configs, replica_config) =
+        split_config_and_replica(config, replica_path, base_tree_leafs, base_tree_count)
+            .expect("failed to split configs");
+
+    let tree = create_lc_tree::<Tree>(base_tree_len, &configs, &replica_config)
+        .expect("failed to open tree");
+    let mmap_tree = MmapLCTree::<Tree>::new(base_tree_len, &configs, &replica_config)
+        .expect("failed to map tree");
+
+    assert_eq!(CachedProofSource::<Tree>::leafs(&tree), nodes);
+    assert_eq!(mmap_tree.leafs(), nodes);
+    for i in 0..nodes {
+        let proof = MerkleTreeTrait::gen_cached_proof(&tree, i, Some(rows_to_discard))
+            .expect("failed to generate proof");
+        let mmap_proof = mmap_tree
+            .gen_cached_proof(i, Some(rows_to_discard))
+            .expect("failed to generate mmap proof");
+
+        assert_eq!(
+            serde_json::to_string(&proof).expect("failed to serialize proof"),
+            serde_json::to_string(&mmap_proof).expect("failed to serialize proof"),
+            "proofs of leaf {} differ",
+            i
+        );
+    }
+    assert!(mmap_tree.gen_cached_proof(nodes, None).is_err());
+}
+
+#[test]
+fn test_mmap_lc_tree_base() {
+    test_mmap_lc_tree::<LCTree<PoseidonHasher, U8, U0, U0>>(512);
+}
+
+#[test]
+fn test_mmap_lc_tree_sub() {
+    test_mmap_lc_tree::<LCTree<PoseidonHasher, U8, U2, U0>>(512);
+}
+
+#[test]
+fn test_mmap_lc_tree_top() {
+    test_mmap_lc_tree::<LCTree<PoseidonHasher, U8, U8, U2>>(64);
+}
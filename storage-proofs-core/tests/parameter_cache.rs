@@ -0,0 +1,173 @@
+use std::io::Write;
+use std::sync::Mutex;
+
+use bellperson::{
+    bls::{Bls12, Fr},
+    Circuit, ConstraintSystem, SynthesisError,
+};
+use ff::Field;
+use lazy_static::lazy_static;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use storage_proofs_core::{
+    parameter_cache::{
+        cache_id_from_parameter_filename, get_parameter_data, get_verifying_key_data,
+        load_parameters_from_path, parameter_cache_params_path, parameter_id, verifying_key_id,
+        CacheableParameters, ParameterSetMetadata, PARAMETERS, PARAMETERS_DATA,
+    },
+    TEST_SEED,
+};
+use tempfile::{tempdir, NamedTempFile};
+
+lazy_static! {
+    /// Held by the tests which override or read the parameters of parameters.json, as the
+    /// override applies to the whole process.
+    static ref PARAMETERS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+#[test]
+fn test_load_parameters_from_path() {
+    let _lock = PARAMETERS_LOCK.lock().expect("parameters lock poisoned");
+    let cache_id =
+        "stacked-proof-of-replication-merkletree-poseidon_hasher-8-0-0-sha256_hasher-test";
+    let builtin_cache_id = PARAMETERS
+        .keys()
+        .filter(|id| id.ends_with(".params"))
+        .find_map(|id| cache_id_from_parameter_filename(id))
+        .expect("no builtin parameters");
+    assert!(get_parameter_data(&builtin_cache_id).is_some());
+
+    let mut malformed = NamedTempFile::new().expect("failed to create temp file");
+    malformed
+        .write_all(br#"{ "v28-test.params": { "cid": "Qm" } }"#)
+        .expect("failed to write temp file");
+    assert!(load_parameters_from_path(malformed.path()).is_err());
+    assert!(get_parameter_data(&builtin_cache_id).is_some());
+
+    let json = format!(
+        r#"{{
+            "{}": {{ "cid": "QmParams", "digest": "00112233", "sector_size": 2048 }},
+            "{}": {{ "cid": "QmVerifyingKey", "digest": "44556677", "sector_size": 2048 }}
+        }}"#,
+        parameter_id(cache_id),
+        verifying_key_id(cache_id),
+    );
+    let mut file = NamedTempFile::new().expect("failed to create temp file");
+    file.write_all(json.as_bytes())
+        .expect("failed to write temp file");
+    load_parameters_from_path(file.path()).expect("failed to load parameters");
+
+    let data = get_parameter_data(cache_id).expect("missing loaded parameters");
+    assert_eq!(data.cid, "QmParams");
+    assert_eq!(data.digest, "00112233");
+    assert_eq!(data.sector_size, 2048);
+
+    let data = get_verifying_key_data(cache_id).expect("missing loaded verifying key");
+    assert_eq!(data.cid, "QmVerifyingKey");
+
+    // The loaded parameters replace the builtin ones.
+    assert!(get_parameter_data(&builtin_cache_id).is_none());
+
+    restore_builtin_parameters();
+    assert!(get_parameter_data(&builtin_cache_id).is_some());
+}
+
+/// Loads the builtin parameters.json again, undoing `load_parameters_from_path`.
+fn restore_builtin_parameters() {
+    let mut builtin = NamedTempFile::new().expect("failed to create temp file");
+    builtin
+        .write_all(PARAMETERS_DATA.as_bytes())
+        .expect("failed to write temp file");
+    load_parameters_from_path(builtin.path()).expect("failed to load builtin parameters");
+}
+
+/// Proves knowledge of the square root `x` of the public input.
+struct SquareCircuit {
+    x: Option<Fr>,
+}
+
+impl Circuit<Bls12> for SquareCircuit {
+    fn synthesize<CS: ConstraintSystem<Bls12>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let x = cs.alloc(|| "x", || self.x.ok_or(SynthesisError::AssignmentMissing))?;
+        let y = cs.alloc_input(
+            || "y",
+            || {
+                let mut y = self.x.ok_or(SynthesisError::AssignmentMissing)?;
+                y.square();
+                Ok(y)
+            },
+        )?;
+        cs.enforce(|| "x * x = y", |lc| lc + x, |lc| lc + x, |lc| lc + y);
+
+        Ok(())
+    }
+}
+
+struct SquareParams;
+
+impl ParameterSetMetadata for SquareParams {
+    fn identifier(&self) -> String {
+        "square-circuit".to_string()
+    }
+
+    fn sector_size(&self) -> u64 {
+        0
+    }
+}
+
+struct SquareCompound;
+
+impl CacheableParameters<SquareCircuit, SquareParams> for SquareCompound {
+    fn cache_prefix() -> String {
+        "test-square".to_string()
+    }
+}
+
+#[test]
+fn test_get_groth_params_in() {
+    // Reading parameters checks them against parameters.json if production parameters are
+    // verified.
+    let _lock = PARAMETERS_LOCK.lock().expect("parameters lock poisoned");
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let cache_dir = tempdir().expect("failed to create temp dir");
+    let id = SquareCompound::cache_identifier(&SquareParams);
+
+    let params = SquareCompound::get_groth_params_in(
+        cache_dir.path(),
+        Some(rng),
+        SquareCircuit { x: None },
+        &SquareParams,
+    )
+    .expect("failed to generate parameters");
+    let vk = SquareCompound::get_verifying_key_in::<XorShiftRng>(
+        cache_dir.path(),
+        None,
+        SquareCircuit { x: None },
+        &SquareParams,
+    )
+    .expect("failed to get verifying key");
+    assert!(vk == params.vk);
+
+    assert!(cache_dir.path().join(parameter_id(&id)).exists());
+    assert!(cache_dir.path().join(verifying_key_id(&id)).exists());
+    assert!(!parameter_cache_params_path(&id).exists());
+
+    // Without an rng the parameters can only be loaded from the directory.
+    let loaded = SquareCompound::get_groth_params_in::<XorShiftRng>(
+        cache_dir.path(),
+        None,
+        SquareCircuit { x: None },
+        &SquareParams,
+    )
+    .expect("failed to load parameters");
+    assert!(loaded.vk == params.vk);
+
+    let empty_dir = tempdir().expect("failed to create temp dir");
+    assert!(SquareCompound::get_groth_params_in::<XorShiftRng>(
+        empty_dir.path(),
+        None,
+        SquareCircuit { x: None },
+        &SquareParams,
+    )
+    .is_err());
+}
@@ -1,26 +1,144 @@
+use std::fmt::{self, Debug, Formatter};
+
 use filecoin_hashers::Domain;
 use num_bigint::BigUint;
 use num_traits::cast::ToPrimitive;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Derives the nodes challenged in a partition of a PoRep from the replica id and the seed.
+///
+/// Prover and verifier both derive the challenges, so they must use the same derivation.
+pub trait ChallengeDerivation: Debug + Send + Sync {
+    /// Returns a stable id, unique among derivations, which tells derivations apart when
+    /// comparing and serializing the configs using them.
+    fn id(&self) -> &'static str;
+
+    /// Returns `challenges_count` challenges for partition `k`, each in `1..leaves`, as the
+    /// first node can't be proven.
+    fn derive(
+        &self,
+        challenges_count: usize,
+        leaves: usize,
+        replica_id: &[u8],
+        seed: &[u8; 32],
+        k: u8,
+    ) -> Vec<usize>;
+}
+
+/// The challenge derivation of Filecoin, used unless a custom derivation is set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FilecoinChallengeDerivation;
+
+impl FilecoinChallengeDerivation {
+    pub const ID: &'static str = "filecoin";
+}
+
+impl ChallengeDerivation for FilecoinChallengeDerivation {
+    fn id(&self) -> &'static str {
+        Self::ID
+    }
+
+    fn derive(
+        &self,
+        challenges_count: usize,
+        leaves: usize,
+        replica_id: &[u8],
+        seed: &[u8; 32],
+        k: u8,
+    ) -> Vec<usize> {
+        (0..challenges_count)
+            .map(|i| {
+                let j: u32 = ((challenges_count * k as usize) + i) as u32;
+
+                let hash = Sha256::new()
+                    .chain(replica_id)
+                    .chain(seed)
+                    .chain(&j.to_le_bytes())
+                    .finalize();
+
+                let big_challenge = BigUint::from_bytes_le(hash.as_ref());
+
+                // We cannot try to prove the first node, so make sure the challenge
+                // can never be 0.
+                let big_mod_challenge = big_challenge % (leaves - 1);
+                let big_mod_challenge = big_mod_challenge
+                    .to_usize()
+                    .expect("`big_mod_challenge` exceeds size of `usize`");
+                big_mod_challenge + 1
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct LayerChallenges {
     /// How many layers we are generating challenges for.
     layers: usize,
     /// The maximum count of challenges
     max_count: usize,
+    /// Custom derivation of the challenges, `FilecoinChallengeDerivation` if not set.
+    #[serde(with = "derivation_id")]
+    derivation: Option<&'static dyn ChallengeDerivation>,
+}
+
+/// Serializes a challenge derivation as its id. Only the Filecoin derivation can be
+/// deserialized, as custom derivations can't be looked up by their id.
+mod derivation_id {
+    use super::*;
+
+    use serde::{de::Error, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        derivation: &Option<&'static dyn ChallengeDerivation>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        derivation
+            .map(|derivation| derivation.id())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<&'static dyn ChallengeDerivation>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(id) if id == FilecoinChallengeDerivation::ID => Ok(None),
+            Some(id) => Err(D::Error::custom(format!(
+                "cannot deserialize custom challenge derivation {:?}",
+                id
+            ))),
+        }
+    }
 }
 
 impl LayerChallenges {
     pub const fn new(layers: usize, max_count: usize) -> Self {
-        LayerChallenges { layers, max_count }
+        LayerChallenges {
+            layers,
+            max_count,
+            derivation: None,
+        }
+    }
+
+    /// Derives the challenges with `derivation` instead of the Filecoin derivation.
+    pub fn with_derivation(mut self, derivation: &'static dyn ChallengeDerivation) -> Self {
+        self.derivation = Some(derivation);
+        self
     }
 
     pub fn layers(&self) -> usize {
         self.layers
     }
 
+    /// Returns the id of the derivation of the challenges.
+    pub fn derivation_id(&self) -> &'static str {
+        self.derivation
+            .map_or(FilecoinChallengeDerivation::ID, |derivation| {
+                derivation.id()
+            })
+    }
+
     pub fn challenges_count_all(&self) -> usize {
         self.max_count
     }
@@ -46,27 +164,19 @@ impl LayerChallenges {
     ) -> Vec<usize> {
         assert!(leaves > 2, "Too few leaves: {}", leaves);
 
-        (0..challenges_count)
-            .map(|i| {
-                let j: u32 = ((challenges_count * k as usize) + i) as u32;
-
-                let hash = Sha256::new()
-                    .chain(replica_id.into_bytes())
-                    .chain(seed)
-                    .chain(&j.to_le_bytes())
-                    .finalize();
-
-                let big_challenge = BigUint::from_bytes_le(hash.as_ref());
+        let derivation = self.derivation.unwrap_or(&FilecoinChallengeDerivation);
+        derivation.derive(challenges_count, leaves, &replica_id.into_bytes(), seed, k)
+    }
+}
 
-                // We cannot try to prove the first node, so make sure the challenge
-                // can never be 0.
-                let big_mod_challenge = big_challenge % (leaves - 1);
-                let big_mod_challenge = big_mod_challenge
-                    .to_usize()
-                    .expect("`big_mod_challenge` exceeds size of `usize`");
-                big_mod_challenge + 1
-            })
-            .collect()
+// The debug output is part of the parameter identifiers. The derivation is left out: the
+// circuit takes the challenges as public inputs, so the parameters don't depend on it.
+impl Debug for LayerChallenges {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("LayerChallenges")
+            .field("layers", &self.layers)
+            .field("max_count", &self.max_count)
+            .finish()
     }
 }
 
@@ -6,7 +6,10 @@ use filecoin_hashers::Hasher;
 use log::{info, warn};
 use merkletree::{merkle::Element, store::StoreConfig};
 use storage_proofs_core::{
-    cache_key::CacheKey, drgraph::Graph, error::Result, merkle::MerkleTreeTrait,
+    cache_key::CacheKey,
+    drgraph::Graph,
+    error::{Error, Result},
+    merkle::MerkleTreeTrait,
 };
 
 use crate::stacked::vanilla::{proof::LayerState, StackedBucketGraph};
@@ -14,6 +17,34 @@ use crate::stacked::vanilla::{proof::LayerState, StackedBucketGraph};
 pub mod multi;
 pub mod single;
 
+/// Progress of the labeling, reported after each layer is available on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LabelProgress {
+    /// The layer which was labeled, starting at 1.
+    pub layer: usize,
+    /// The number of nodes labeled in this layer.
+    pub node: usize,
+}
+
+/// Returned from a progress callback to decide whether labeling continues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelControl {
+    Continue,
+    Break,
+}
+
+/// Reports that `layer` is done, failing with `Error::Cancelled` if the callback asks to stop.
+pub fn report_layer(
+    progress: &mut dyn FnMut(LabelProgress) -> LabelControl,
+    layer: usize,
+    nodes: usize,
+) -> Result<()> {
+    match progress(LabelProgress { layer, node: nodes }) {
+        LabelControl::Continue => Ok(()),
+        LabelControl::Break => Err(Error::Cancelled.into()),
+    }
+}
+
 /// Prepares the necessary `StoreConfig`s with which the layers are stored.
 /// Also checks for already existing layers and marks them as such.
 pub fn prepare_layers<Tree: 'static + MerkleTreeTrait>(
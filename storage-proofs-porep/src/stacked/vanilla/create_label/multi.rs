@@ -21,6 +21,7 @@ use merkletree::store::{DiskStore, Store, StoreConfig};
 use storage_proofs_core::{
     cache_key::CacheKey,
     drgraph::{Graph, BASE_DEGREE},
+    enter_span,
     merkle::MerkleTreeTrait,
     settings::SETTINGS,
     util::NODE_SIZE,
@@ -29,7 +30,9 @@ use storage_proofs_core::{
 use crate::stacked::vanilla::{
     cache::ParentCache,
     cores::{bind_core, checkout_core_group, CoreIndex},
-    create_label::{prepare_layers, read_layer, write_layer},
+    create_label::{
+        prepare_layers, read_layer, report_layer, write_layer, LabelControl, LabelProgress,
+    },
     graph::{StackedBucketGraph, DEGREE, EXP_DEGREE},
     memory_handling::{setup_create_label_memory, CacheReader},
     params::{Labels, LabelsCache},
@@ -434,6 +437,7 @@ pub fn create_labels_for_encoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]
     layers: usize,
     replica_id: T,
     config: StoreConfig,
+    progress: &mut dyn FnMut(LabelProgress) -> LabelControl,
 ) -> Result<(Labels<Tree>, Vec<LayerState>)> {
     info!("create labels");
 
@@ -464,6 +468,7 @@ pub fn create_labels_for_encoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]
     )?;
 
     for (layer, layer_state) in (1..=layers).zip(layer_states.iter()) {
+        let _span = enter_span!("label_layer", layer);
         info!("Layer {}", layer);
 
         if layer_state.generated {
@@ -471,6 +476,7 @@ pub fn create_labels_for_encoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]
 
             // load the already generated layer into exp_labels
             read_layer(&layer_state.config, &mut exp_labels)?;
+            report_layer(progress, layer, graph.size())?;
             continue;
         }
 
@@ -512,6 +518,8 @@ pub fn create_labels_for_encoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]
                 layer, layer_config.id
             );
         }
+
+        report_layer(progress, layer, graph.size())?;
     }
 
     Ok((
@@ -9,13 +9,16 @@ use merkletree::store::{DiskStore, Store, StoreConfig};
 use sha2raw::Sha256;
 use storage_proofs_core::{
     drgraph::Graph,
+    enter_span,
     merkle::MerkleTreeTrait,
     util::{data_at_node_offset, NODE_SIZE},
 };
 
 use crate::stacked::vanilla::{
     cache::ParentCache,
-    create_label::{prepare_layers, read_layer, write_layer},
+    create_label::{
+        prepare_layers, read_layer, report_layer, write_layer, LabelControl, LabelProgress,
+    },
     proof::LayerState,
     Labels, LabelsCache, StackedBucketGraph,
 };
@@ -27,6 +30,7 @@ pub fn create_labels_for_encoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]
     layers: usize,
     replica_id: T,
     config: StoreConfig,
+    progress: &mut dyn FnMut(LabelProgress) -> LabelControl,
 ) -> Result<(Labels<Tree>, Vec<LayerState>)> {
     info!("generate labels");
 
@@ -38,12 +42,14 @@ pub fn create_labels_for_encoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]
     let mut exp_labels = vec![0u8; layer_size]; // Buffer for labels of the previous layer, needed for expander parents
 
     for (layer, layer_state) in (1..=layers).zip(layer_states.iter()) {
+        let _span = enter_span!("label_layer", layer);
         info!("generating layer: {}", layer);
         if layer_state.generated {
             info!("skipping layer {}, already generated", layer);
 
             // load the already generated layer into exp_labels
             read_layer(&layer_state.config, &mut exp_labels)?;
+            report_layer(progress, layer, graph.size())?;
             continue;
         }
 
@@ -87,6 +93,8 @@ pub fn create_labels_for_encoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]
 
         info!("  setting exp parents");
         mem::swap(&mut layer_labels, &mut exp_labels);
+
+        report_layer(progress, layer, graph.size())?;
     }
 
     Ok((
@@ -19,9 +19,12 @@ mod proof;
 mod proof_scheme;
 mod utils;
 
-pub use challenges::{ChallengeRequirements, LayerChallenges};
+pub use challenges::{
+    ChallengeDerivation, ChallengeRequirements, FilecoinChallengeDerivation, LayerChallenges,
+};
 pub use column::Column;
 pub use column_proof::ColumnProof;
+pub use create_label::{LabelControl, LabelProgress};
 pub use encoding_proof::EncodingProof;
 pub use graph::{StackedBucketGraph, StackedGraph, EXP_DEGREE};
 pub use labeling_proof::LabelingProof;
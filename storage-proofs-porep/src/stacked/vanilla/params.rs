@@ -381,6 +381,14 @@ impl<Tree: MerkleTreeTrait, G: Hasher> TemporaryAux<Tree, G> {
         self.tree_c_config.path = cp;
     }
 
+    /// Points tree_c and tree_r_last to `tree_path`, for trees stored apart from the cache.
+    /// Call after `set_cache_path`, which points them to the cache.
+    pub fn set_tree_path<P: AsRef<Path>>(&mut self, tree_path: P) {
+        let tp = tree_path.as_ref().to_path_buf();
+        self.tree_r_last_config.path = tp.clone();
+        self.tree_c_config.path = tp;
+    }
+
     pub fn labels_for_layer(
         &self,
         layer: usize,
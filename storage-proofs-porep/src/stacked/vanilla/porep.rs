@@ -58,6 +58,7 @@ impl<'a, 'c, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> PoRep<'a, Tre
             replica_id,
             data,
             config.expect("Missing store config"),
+            0,
         )?;
 
         Ok(())
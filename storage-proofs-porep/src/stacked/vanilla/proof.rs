@@ -4,7 +4,7 @@ use std::panic::panic_any;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
-use anyhow::Context;
+use anyhow::{ensure, Context};
 use bincode::deserialize;
 use fdlimit::raise_fd_limit;
 use filecoin_hashers::{Domain, HashFunction, Hasher, PoseidonArity};
@@ -22,6 +22,7 @@ use storage_proofs_core::{
     cache_key::CacheKey,
     data::Data,
     drgraph::Graph,
+    enter_span,
     error::Result,
     measurements::{measure_op, Operation},
     merkle::{
@@ -38,7 +39,7 @@ use crate::{
     stacked::vanilla::{
         challenges::LayerChallenges,
         column::Column,
-        create_label,
+        create_label::{self, LabelControl, LabelProgress},
         graph::StackedBucketGraph,
         hash::hash_single_column,
         params::{
@@ -128,6 +129,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
 
         (0..partition_count)
             .map(|k| {
+                let _span = enter_span!("prove_partition", partition = k);
                 trace!("proving partition {}/{}", k + 1, partition_count);
 
                 // Derive the set of challenges we are proving over.
@@ -270,12 +272,44 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             .collect()
     }
 
+    /// Decodes the sealed nodes in `data`, the first of which is `first_node` of the replica.
+    ///
+    /// All layers still need to be labeled, but only the nodes covered by `data` are read from
+    /// the last layer and decoded.
+    pub fn extract_range(
+        pp: &PublicParams<Tree>,
+        replica_id: &<Tree::Hasher as Hasher>::Domain,
+        data: &mut [u8],
+        config: StoreConfig,
+        first_node: usize,
+    ) -> Result<()> {
+        ensure!(
+            data.len() % NODE_SIZE == 0,
+            "data length {} is not a multiple of the node size",
+            data.len()
+        );
+        ensure!(
+            first_node + data.len() / NODE_SIZE <= pp.graph.size(),
+            "node range exceeds the replica size"
+        );
+
+        Self::extract_and_invert_transform_layers(
+            &pp.graph,
+            &pp.layer_challenges,
+            replica_id,
+            data,
+            config,
+            first_node,
+        )
+    }
+
     pub(crate) fn extract_and_invert_transform_layers(
         graph: &StackedBucketGraph<Tree::Hasher>,
         layer_challenges: &LayerChallenges,
         replica_id: &<Tree::Hasher as Hasher>::Domain,
         data: &mut [u8],
         config: StoreConfig,
+        first_node: usize,
     ) -> Result<()> {
         trace!("extract_and_invert_transform_layers");
 
@@ -287,9 +321,10 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
 
         let last_layer_labels = labels.labels_for_last_layer()?;
         let size = Store::len(last_layer_labels);
+        let last_node = size.min(first_node + data.len() / NODE_SIZE);
 
         for (key, encoded_node_bytes) in last_layer_labels
-            .read_range(0..size)?
+            .read_range(first_node..last_node)?
             .into_iter()
             .zip(data.chunks_mut(NODE_SIZE))
         {
@@ -310,6 +345,26 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         layer_challenges: &LayerChallenges,
         replica_id: &<Tree::Hasher as Hasher>::Domain,
         config: StoreConfig,
+    ) -> Result<(Labels<Tree>, Vec<LayerState>)> {
+        Self::generate_labels_for_encoding_with_progress(
+            graph,
+            layer_challenges,
+            replica_id,
+            config,
+            &mut |_| LabelControl::Continue,
+        )
+    }
+
+    /// Generates the layers as needed for encoding, calling `progress` after each layer.
+    ///
+    /// Returning `LabelControl::Break` from `progress` stops labeling with `Error::Cancelled`.
+    /// Layers which were completed so far stay on disk and are reused on the next run.
+    pub fn generate_labels_for_encoding_with_progress(
+        graph: &StackedBucketGraph<Tree::Hasher>,
+        layer_challenges: &LayerChallenges,
+        replica_id: &<Tree::Hasher as Hasher>::Domain,
+        config: StoreConfig,
+        progress: &mut dyn FnMut(LabelProgress) -> LabelControl,
     ) -> Result<(Labels<Tree>, Vec<LayerState>)> {
         let mut parent_cache = graph.parent_cache()?;
 
@@ -321,6 +376,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
                 layer_challenges.layers(),
                 replica_id,
                 config,
+                progress,
             )
         } else {
             info!("single core replication");
@@ -330,6 +386,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
                 layer_challenges.layers(),
                 replica_id,
                 config,
+                progress,
             )
         }
     }
@@ -1121,10 +1178,13 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             config,
             replica_path,
             labels,
+            None,
+            None,
         )
         .context("failed to transform")
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn transform_and_replicate_layers_inner(
         graph: &StackedBucketGraph<Tree::Hasher>,
         layer_challenges: &LayerChallenges,
@@ -1133,6 +1193,8 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         config: StoreConfig,
         replica_path: PathBuf,
         label_configs: Labels<Tree>,
+        tree_r_last_rows_to_discard: Option<usize>,
+        tree_path: Option<PathBuf>,
     ) -> Result<TransformedLayers<Tree, G>> {
         trace!("transform_and_replicate_layers");
         let nodes_count = graph.size();
@@ -1178,13 +1240,13 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             Some(get_merkle_tree_len(nodes_count, Tree::Arity::to_usize())?),
         );
 
-        // A default 'rows_to_discard' value will be chosen for tree_r_last, unless the user overrides this value via the
-        // environment setting (FIL_PROOFS_ROWS_TO_DISCARD).  If this value is specified, no checking is done on it and it may
-        // result in a broken configuration.  Use with caution.  It must be noted that if/when this unchecked value is passed
-        // through merkle_light, merkle_light now does a check that does not allow us to discard more rows than is possible
-        // to discard.
-        tree_r_last_config.rows_to_discard =
-            default_rows_to_discard(nodes_count, Tree::Arity::to_usize());
+        // A default 'rows_to_discard' value will be chosen for tree_r_last, unless the caller passes one in, or the user
+        // overrides this value via the environment setting (FIL_PROOFS_ROWS_TO_DISCARD).  If this value is specified, no
+        // checking is done on it and it may result in a broken configuration.  Use with caution.  It must be noted that
+        // if/when this unchecked value is passed through merkle_light, merkle_light now does a check that does not allow us
+        // to discard more rows than is possible to discard.
+        tree_r_last_config.rows_to_discard = tree_r_last_rows_to_discard
+            .unwrap_or_else(|| default_rows_to_discard(nodes_count, Tree::Arity::to_usize()));
         trace!(
             "tree_r_last using rows_to_discard={}",
             tree_r_last_config.rows_to_discard
@@ -1198,6 +1260,12 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         tree_c_config.rows_to_discard =
             default_rows_to_discard(nodes_count, Tree::Arity::to_usize());
 
+        // tree_c and tree_r_last may be stored apart from the labels and tree_d.
+        if let Some(tree_path) = tree_path {
+            tree_c_config.path = tree_path.clone();
+            tree_r_last_config.path = tree_path;
+        }
+
         let labels =
             LabelsCache::<Tree>::new(&label_configs).context("failed to create labels cache")?;
         let configs = split_config(tree_c_config.clone(), tree_count)?;
@@ -1209,6 +1277,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             None => error!("Failed to raise the fd limit"),
         };
 
+        let tree_c_span = enter_span!("build_tree_c");
         let tree_c_root = match layers {
             2 => {
                 let tree_c = Self::generate_tree_c::<U2, Tree::Arity>(
@@ -1242,6 +1311,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             }
             _ => panic_any("Unsupported column arity"),
         };
+        drop(tree_c_span);
         info!("tree_c done");
 
         // Build the MerkleTree over the original data (if needed).
@@ -1271,6 +1341,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         // Encode original data into the last layer.
         info!("building tree_r_last");
         let tree_r_last = measure_op(Operation::GenerateTreeRLast, || {
+            let _span = enter_span!("build_tree_r_last");
             Self::generate_tree_r_last::<Tree::Arity>(
                 &mut data,
                 nodes_count,
@@ -1316,11 +1387,30 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         pp: &'a PublicParams<Tree>,
         replica_id: &<Tree::Hasher as Hasher>::Domain,
         config: StoreConfig,
+    ) -> Result<Labels<Tree>> {
+        Self::replicate_phase1_with_progress(pp, replica_id, config, &mut |_| {
+            LabelControl::Continue
+        })
+    }
+
+    /// Phase1 of replication, reporting progress after each labeled layer.
+    pub fn replicate_phase1_with_progress(
+        pp: &'a PublicParams<Tree>,
+        replica_id: &<Tree::Hasher as Hasher>::Domain,
+        config: StoreConfig,
+        progress: &mut dyn FnMut(LabelProgress) -> LabelControl,
     ) -> Result<Labels<Tree>> {
         info!("replicate_phase1");
 
         let labels = measure_op(Operation::EncodeWindowTimeAll, || {
-            Self::generate_labels_for_encoding(&pp.graph, &pp.layer_challenges, replica_id, config)
+            let _span = enter_span!("labeling");
+            Self::generate_labels_for_encoding_with_progress(
+                &pp.graph,
+                &pp.layer_challenges,
+                replica_id,
+                config,
+                progress,
+            )
         })?
         .0;
 
@@ -1339,6 +1429,59 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
     ) -> Result<(
         <Self as PoRep<'a, Tree::Hasher, G>>::Tau,
         <Self as PoRep<'a, Tree::Hasher, G>>::ProverAux,
+    )> {
+        Self::replicate_phase2_with_rows_to_discard(
+            pp,
+            labels,
+            data,
+            data_tree,
+            config,
+            replica_path,
+            None,
+        )
+    }
+
+    /// Like `replicate_phase2`, but caches tree_r_last with `tree_r_last_rows_to_discard`
+    /// rows discarded instead of the default, if given.
+    pub fn replicate_phase2_with_rows_to_discard(
+        pp: &'a PublicParams<Tree>,
+        labels: Labels<Tree>,
+        data: Data<'a>,
+        data_tree: BinaryMerkleTree<G>,
+        config: StoreConfig,
+        replica_path: PathBuf,
+        tree_r_last_rows_to_discard: Option<usize>,
+    ) -> Result<(
+        <Self as PoRep<'a, Tree::Hasher, G>>::Tau,
+        <Self as PoRep<'a, Tree::Hasher, G>>::ProverAux,
+    )> {
+        Self::replicate_phase2_with_tree_path(
+            pp,
+            labels,
+            data,
+            data_tree,
+            config,
+            replica_path,
+            tree_r_last_rows_to_discard,
+            None,
+        )
+    }
+
+    /// Like `replicate_phase2_with_rows_to_discard`, but writes tree_c and tree_r_last to
+    /// `tree_path` instead of the directory of `config`, if given.
+    #[allow(clippy::too_many_arguments)]
+    pub fn replicate_phase2_with_tree_path(
+        pp: &'a PublicParams<Tree>,
+        labels: Labels<Tree>,
+        data: Data<'a>,
+        data_tree: BinaryMerkleTree<G>,
+        config: StoreConfig,
+        replica_path: PathBuf,
+        tree_r_last_rows_to_discard: Option<usize>,
+        tree_path: Option<PathBuf>,
+    ) -> Result<(
+        <Self as PoRep<'a, Tree::Hasher, G>>::Tau,
+        <Self as PoRep<'a, Tree::Hasher, G>>::ProverAux,
     )> {
         info!("replicate_phase2");
 
@@ -1350,6 +1493,8 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             config,
             replica_path,
             labels,
+            tree_r_last_rows_to_discard,
+            tree_path,
         )?;
 
         Ok((tau, (paux, taux)))
@@ -24,8 +24,9 @@ use storage_proofs_core::{
 };
 use storage_proofs_porep::{
     stacked::{
-        LayerChallenges, PrivateInputs, PublicInputs, SetupParams, StackedBucketGraph, StackedDrg,
-        TemporaryAux, TemporaryAuxCache, BINARY_ARITY, EXP_DEGREE,
+        ChallengeDerivation, FilecoinChallengeDerivation, LayerChallenges, PrivateInputs,
+        PublicInputs, SetupParams, StackedBucketGraph, StackedDrg, TemporaryAux, TemporaryAuxCache,
+        BINARY_ARITY, EXP_DEGREE,
     },
     PoRep,
 };
@@ -341,6 +342,76 @@ fn test_prove_verify_fixed(n: usize) {
     test_prove_verify::<DiskTree<PoseidonHasher, U8, U8, U2>>(n, challenges);
 }
 
+/// Challenges the node after each Filecoin challenge, wrapping around to node 1.
+#[derive(Debug)]
+struct ShiftedChallengeDerivation;
+
+impl ChallengeDerivation for ShiftedChallengeDerivation {
+    fn id(&self) -> &'static str {
+        "shifted"
+    }
+
+    fn derive(
+        &self,
+        challenges_count: usize,
+        leaves: usize,
+        replica_id: &[u8],
+        seed: &[u8; 32],
+        k: u8,
+    ) -> Vec<usize> {
+        FilecoinChallengeDerivation
+            .derive(challenges_count, leaves, replica_id, seed, k)
+            .into_iter()
+            .map(|challenge| challenge % (leaves - 1) + 1)
+            .collect()
+    }
+}
+
+#[test]
+fn test_stacked_porep_custom_challenge_derivation() {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let leaves = 64;
+    let replica_id = <PoseidonHasher as Hasher>::Domain::random(rng);
+    let seed = rng.gen();
+
+    let challenges = LayerChallenges::new(DEFAULT_STACKED_LAYERS, 5);
+    let shifted_challenges = challenges
+        .clone()
+        .with_derivation(&ShiftedChallengeDerivation);
+    for k in 0..2 {
+        let expected: Vec<_> = challenges
+            .derive(leaves, &replica_id, &seed, k)
+            .into_iter()
+            .map(|challenge| challenge % (leaves - 1) + 1)
+            .collect();
+        assert_eq!(
+            shifted_challenges.derive(leaves, &replica_id, &seed, k),
+            expected
+        );
+    }
+
+    // The custom derivation doesn't change the parameter identifiers.
+    assert_eq!(
+        format!("{:?}", shifted_challenges),
+        format!("{:?}", challenges)
+    );
+    assert_eq!(challenges.derivation_id(), FilecoinChallengeDerivation::ID);
+    assert_eq!(shifted_challenges.derivation_id(), "shifted");
+
+    // Only the Filecoin derivation can be restored from its serialized id.
+    let serialized = serde_json::to_string(&challenges).expect("failed to serialize");
+    let deserialized: LayerChallenges =
+        serde_json::from_str(&serialized).expect("failed to deserialize");
+    assert_eq!(
+        deserialized.derivation_id(),
+        FilecoinChallengeDerivation::ID
+    );
+    let serialized = serde_json::to_string(&shifted_challenges).expect("failed to serialize");
+    assert!(serde_json::from_str::<LayerChallenges>(&serialized).is_err());
+
+    test_prove_verify::<DiskTree<PoseidonHasher, U8, U0, U0>>(leaves, shifted_challenges);
+}
+
 fn test_prove_verify<Tree: 'static + MerkleTreeTrait>(n: usize, challenges: LayerChallenges) {
     // This will be called multiple times, only the first one succeeds, and that is ok.
     // femme::pretty::Logger::new()
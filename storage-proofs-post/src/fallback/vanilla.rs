@@ -15,7 +15,7 @@ use sha2::{Digest, Sha256};
 use storage_proofs_core::{
     api_version::ApiVersion,
     error::{Error, Result},
-    merkle::{MerkleProof, MerkleProofTrait, MerkleTreeTrait, MerkleTreeWrapper},
+    merkle::{CachedProofSource, MerkleProof, MerkleProofTrait, MerkleTreeTrait},
     parameter_cache::ParameterSetMetadata,
     proof::ProofScheme,
     sector::SectorId,
@@ -86,13 +86,8 @@ pub struct PublicSector<T: Domain> {
 
 #[derive(Debug)]
 pub struct PrivateSector<'a, Tree: MerkleTreeTrait> {
-    pub tree: &'a MerkleTreeWrapper<
-        Tree::Hasher,
-        Tree::Store,
-        Tree::Arity,
-        Tree::SubTreeArity,
-        Tree::TopTreeArity,
-    >,
+    /// The tree_r_last of the sector, either the tree itself or a `MmapLCTree` of its files.
+    pub tree: &'a dyn CachedProofSource<Tree>,
     pub comm_c: <Tree::Hasher as Hasher>::Domain,
     pub comm_r_last: <Tree::Hasher as Hasher>::Domain,
 }